@@ -0,0 +1,7 @@
+-- Phase 4: Output contracts for spell results
+
+-- output_schema: JSON Schema every successful cast result must satisfy
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS output_schema JSONB;
+
+-- Speed up per-spell health aggregation over cast history
+CREATE INDEX IF NOT EXISTS idx_casts_spell_status ON casts(spell_id, status, created_at);
@@ -0,0 +1,19 @@
+-- Phase 4: Public spell catalog
+
+-- manifest: parsed manifest.toml stored as JSON ([spell], [io], [metadata], ...)
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS manifest JSONB NOT NULL DEFAULT '{}'::jsonb;
+
+-- search_vector: full-text index over names (weighted higher) and descriptions
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS search_vector TSVECTOR
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', coalesce(name, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(description, '')), 'B')
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_spells_search ON spells USING GIN(search_vector);
+CREATE INDEX IF NOT EXISTS idx_spells_tags ON spells USING GIN((manifest->'metadata'->'tags'));
+CREATE INDEX IF NOT EXISTS idx_spells_category ON spells((manifest->'metadata'->>'category'));
+
+-- Keyset pagination orderings
+CREATE INDEX IF NOT EXISTS idx_spells_created_id ON spells(created_at DESC, id DESC) WHERE is_active = true;
+CREATE INDEX IF NOT EXISTS idx_spells_price_id ON spells(price_cents, id) WHERE is_active = true;
@@ -0,0 +1,28 @@
+-- Phase 4: Creator spell management
+
+-- Scheduled price changes: casts switch to next_price_cents once next_price_effective_at passes
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS next_price_cents INTEGER CHECK (next_price_cents >= 0);
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS next_price_effective_at TIMESTAMPTZ;
+
+ALTER TABLE spells ADD CONSTRAINT valid_next_price CHECK (
+    (next_price_cents IS NULL) = (next_price_effective_at IS NULL)
+);
+
+-- spell_versions: every published build of a spell
+CREATE TABLE IF NOT EXISTS spell_versions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    spell_id UUID NOT NULL REFERENCES spells(id) ON DELETE CASCADE,
+    version TEXT NOT NULL,
+    wasm_path TEXT NOT NULL,
+    manifest JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (spell_id, version)
+);
+
+CREATE INDEX IF NOT EXISTS idx_spell_versions_spell ON spell_versions(spell_id, created_at DESC);
+
+-- Backfill: every existing spell gets its current build as its first version
+INSERT INTO spell_versions (spell_id, version, wasm_path, manifest, created_at)
+SELECT id, COALESCE(manifest->'spell'->>'version', '0.1.0'), wasm_path, manifest, created_at
+FROM spells
+ON CONFLICT (spell_id, version) DO NOTHING;
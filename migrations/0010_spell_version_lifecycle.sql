@@ -0,0 +1,15 @@
+-- Phase 4: Spell version deprecation and yanking
+
+-- status: active (default), deprecated (castable with warnings), yanked (pinned casts only)
+ALTER TABLE spell_versions ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'deprecated', 'yanked'));
+ALTER TABLE spell_versions ADD COLUMN IF NOT EXISTS status_reason TEXT;
+ALTER TABLE spell_versions ADD COLUMN IF NOT EXISTS deprecated_at TIMESTAMPTZ;
+ALTER TABLE spell_versions ADD COLUMN IF NOT EXISTS sunset_at TIMESTAMPTZ;
+ALTER TABLE spell_versions ADD COLUMN IF NOT EXISTS yanked_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_spell_versions_status ON spell_versions(status) WHERE status <> 'active';
+
+-- Record which version served each cast (for pinning, feeds and replays)
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS spell_version_id UUID REFERENCES spell_versions(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_casts_user_version ON casts(user_id, spell_version_id, created_at);
@@ -0,0 +1,7 @@
+-- Phase 4: Content-addressed blob storage for spell modules
+
+-- digest: "sha256:<hex>" of the module in the blob store (NULL for legacy file-based versions)
+ALTER TABLE spell_versions ADD COLUMN IF NOT EXISTS digest TEXT;
+ALTER TABLE spell_versions ADD COLUMN IF NOT EXISTS size_bytes BIGINT;
+
+CREATE INDEX IF NOT EXISTS idx_spell_versions_digest ON spell_versions(digest) WHERE digest IS NOT NULL;
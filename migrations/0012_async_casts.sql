@@ -0,0 +1,6 @@
+-- Phase 4: Asynchronous casts
+
+-- Progress timestamps and a human-readable failure message for polling clients
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS started_at TIMESTAMPTZ;
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS finished_at TIMESTAMPTZ;
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS error_message TEXT;
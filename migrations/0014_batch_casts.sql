@@ -0,0 +1,18 @@
+-- Phase 4: Batch casts and budget reservations
+
+-- Casts executed as part of a batch share its id
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS batch_id UUID;
+CREATE INDEX IF NOT EXISTS idx_casts_batch ON casts(batch_id) WHERE batch_id IS NOT NULL;
+
+-- budget_reservations: spend held against a hard limit while multi-cast work runs
+-- Active until released; expires_at bounds reservations leaked by a crashed server
+CREATE TABLE IF NOT EXISTS budget_reservations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    amount_cents INTEGER NOT NULL CHECK (amount_cents >= 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    released_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_budget_reservations_active ON budget_reservations(user_id) WHERE released_at IS NULL;
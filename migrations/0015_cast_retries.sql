@@ -0,0 +1,25 @@
+-- Phase 4: Automatic retries for retryable cast errors
+
+-- Per-spell retry policy; max_attempts includes the first try (1 disables retries).
+-- Existing spells keep running once; creators opt in to retries.
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS retry_max_attempts INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS retry_backoff_ms INTEGER NOT NULL DEFAULT 200;
+ALTER TABLE spells DROP CONSTRAINT IF EXISTS valid_retry_policy;
+ALTER TABLE spells ADD CONSTRAINT valid_retry_policy CHECK (
+    retry_max_attempts BETWEEN 1 AND 10 AND retry_backoff_ms BETWEEN 0 AND 60000
+);
+
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0;
+
+-- One row per execution attempt of a cast
+CREATE TABLE IF NOT EXISTS cast_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    cast_id UUID NOT NULL REFERENCES casts(id) ON DELETE CASCADE,
+    attempt INTEGER NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('COMPLETED', 'FAILED')),
+    error_code TEXT,
+    error_message TEXT,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (cast_id, attempt)
+);
@@ -0,0 +1,7 @@
+-- Phase 4: Cast cancellation
+
+-- Casts may now end as CANCELED (finished_at records when); canceled casts are never charged.
+-- An attempt interrupted by a cancellation is logged as CANCELED.
+ALTER TABLE cast_attempts DROP CONSTRAINT IF EXISTS cast_attempts_status_check;
+ALTER TABLE cast_attempts ADD CONSTRAINT cast_attempts_status_check
+    CHECK (status IN ('COMPLETED', 'FAILED', 'CANCELED'));
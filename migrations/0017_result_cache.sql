@@ -0,0 +1,16 @@
+-- Phase 4: Result caching for pure spells
+
+-- Creators mark versions whose output depends only on their input
+ALTER TABLE spell_versions ADD COLUMN IF NOT EXISTS pure BOOLEAN NOT NULL DEFAULT false;
+
+-- How long cached results live, and the discount applied to casts served from the cache
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS cache_ttl_secs INTEGER NOT NULL DEFAULT 3600;
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS cache_discount_percent INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE spells DROP CONSTRAINT IF EXISTS valid_cache_policy;
+ALTER TABLE spells ADD CONSTRAINT valid_cache_policy CHECK (
+    cache_ttl_secs BETWEEN 1 AND 2592000 AND cache_discount_percent BETWEEN 0 AND 100
+);
+
+-- Whether a cast was served from the cache, and whether the caller asked to skip it
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS cached BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS bypass_cache BOOLEAN NOT NULL DEFAULT false;
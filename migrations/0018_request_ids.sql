@@ -0,0 +1,4 @@
+-- Phase 4: Request ids for correlating casts with logs and error reports
+
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS request_id TEXT;
+CREATE INDEX IF NOT EXISTS idx_casts_request_id ON casts(request_id) WHERE request_id IS NOT NULL;
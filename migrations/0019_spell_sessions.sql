@@ -0,0 +1,29 @@
+-- Phase 4: Interactive WebSocket sessions on a warm spell instance
+
+-- Whether casts are billed per call (each session message) or once per session
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS pricing_model TEXT NOT NULL DEFAULT 'per_call';
+ALTER TABLE spells DROP CONSTRAINT IF EXISTS valid_pricing_model;
+ALTER TABLE spells ADD CONSTRAINT valid_pricing_model CHECK (
+    pricing_model IN ('per_call', 'per_session')
+);
+
+CREATE TABLE IF NOT EXISTS spell_sessions (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE SET NULL,
+    spell_id UUID NOT NULL REFERENCES spells(id),
+    spell_version_id UUID NOT NULL REFERENCES spell_versions(id),
+    status TEXT NOT NULL DEFAULT 'OPEN' CHECK (status IN ('OPEN', 'CLOSED')),
+    messages INTEGER NOT NULL DEFAULT 0,
+    cost_cents INTEGER NOT NULL DEFAULT 0,
+    close_reason TEXT,
+    request_id TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ended_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_spell_sessions_user ON spell_sessions(user_id, started_at DESC);
+
+-- Each session message is recorded as a cast linked to its session
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS session_id UUID REFERENCES spell_sessions(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_casts_session ON casts(session_id) WHERE session_id IS NOT NULL;
@@ -0,0 +1,11 @@
+-- Phase 4: File inputs for casts (multipart and raw binary bodies)
+
+-- Largest input (JSON payload plus files) a cast of the spell may send
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS max_input_bytes INTEGER NOT NULL DEFAULT 10485760;
+ALTER TABLE spells DROP CONSTRAINT IF EXISTS valid_max_input_bytes;
+ALTER TABLE spells ADD CONSTRAINT valid_max_input_bytes CHECK (
+    max_input_bytes BETWEEN 1024 AND 104857600
+);
+
+-- Files sent with the cast: name, content type, size and blob store digest of each
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS input_files JSONB NOT NULL DEFAULT '[]';
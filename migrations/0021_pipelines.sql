@@ -0,0 +1,37 @@
+-- Phase 4: Pipelines of chained casts, run server-side as one request
+
+CREATE TABLE IF NOT EXISTS pipelines (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    description TEXT,
+    definition JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+-- One execution of a saved or inline pipeline; the definition is snapshotted
+CREATE TABLE IF NOT EXISTS pipeline_runs (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    pipeline_id UUID REFERENCES pipelines(id) ON DELETE SET NULL,
+    definition JSONB NOT NULL,
+    input JSONB NOT NULL DEFAULT 'null',
+    status TEXT NOT NULL DEFAULT 'RUNNING' CHECK (
+        status IN ('RUNNING', 'COMPLETED', 'PARTIAL', 'FAILED')
+    ),
+    output JSONB,
+    steps JSONB NOT NULL DEFAULT '[]',
+    cost_cents INTEGER NOT NULL DEFAULT 0,
+    request_id TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_pipeline_runs_user ON pipeline_runs(user_id, created_at DESC);
+
+-- Each step is recorded as a cast linked to its run
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS pipeline_run_id UUID REFERENCES pipeline_runs(id) ON DELETE SET NULL;
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS pipeline_step TEXT;
+CREATE INDEX IF NOT EXISTS idx_casts_pipeline_run ON casts(pipeline_run_id) WHERE pipeline_run_id IS NOT NULL;
@@ -0,0 +1,29 @@
+-- Phase 4: Schedules that cast a spell with a stored payload on a cron expression
+
+CREATE TABLE IF NOT EXISTS cast_schedules (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    spell_name TEXT NOT NULL,
+    version TEXT,
+    payload JSONB NOT NULL DEFAULT 'null',
+    cron TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'ACTIVE' CHECK (status IN ('ACTIVE', 'PAUSED')),
+    -- NULL while paused
+    next_run_at TIMESTAMPTZ,
+    last_run_at TIMESTAMPTZ,
+    last_cast_id UUID REFERENCES casts(id) ON DELETE SET NULL,
+    -- QUEUED when the last run was cast, SKIPPED when it was refused before casting
+    last_status TEXT,
+    last_error_code TEXT,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_cast_schedules_due ON cast_schedules(next_run_at) WHERE status = 'ACTIVE';
+
+-- Each run is recorded as a cast linked to its schedule
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS schedule_id UUID REFERENCES cast_schedules(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_casts_schedule ON casts(schedule_id, created_at DESC) WHERE schedule_id IS NOT NULL;
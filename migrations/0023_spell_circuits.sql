@@ -0,0 +1,19 @@
+-- Phase 4: Circuit breaking for failing spell versions
+-- Live breaker state is kept in Redis; transitions are recorded here for creators.
+
+CREATE TABLE IF NOT EXISTS spell_circuit_events (
+    id UUID PRIMARY KEY,
+    spell_id UUID NOT NULL REFERENCES spells(id) ON DELETE CASCADE,
+    spell_version_id UUID NOT NULL REFERENCES spell_versions(id) ON DELETE CASCADE,
+    event TEXT NOT NULL CHECK (event IN ('opened', 'closed')),
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_spell_circuit_events_spell ON spell_circuit_events(spell_id, created_at DESC);
+
+-- Creators are notified of circuit transitions at this URL, signed like cast webhooks
+ALTER TABLE spells ADD COLUMN IF NOT EXISTS alert_url TEXT;
+
+-- Creator alerts are not about any one cast
+ALTER TABLE webhook_deliveries ALTER COLUMN cast_id DROP NOT NULL;
@@ -0,0 +1,31 @@
+-- Phase 4: Synthetic health checks from the examples declared in spell manifests
+-- Each version with examples is checked after publishing and periodically after that.
+
+ALTER TABLE spell_versions
+    ADD COLUMN IF NOT EXISTS health_status TEXT CHECK (health_status IN ('PASSING', 'FAILING')),
+    ADD COLUMN IF NOT EXISTS health_checked_at TIMESTAMPTZ,
+    -- NULL until first claimed, so fresh versions are checked first
+    ADD COLUMN IF NOT EXISTS next_health_check_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS spell_health_checks (
+    id UUID PRIMARY KEY,
+    spell_id UUID NOT NULL REFERENCES spells(id) ON DELETE CASCADE,
+    spell_version_id UUID NOT NULL REFERENCES spell_versions(id) ON DELETE CASCADE,
+    triggered_by TEXT NOT NULL CHECK (triggered_by IN ('publish', 'scheduled')),
+    status TEXT NOT NULL,
+    passed INTEGER NOT NULL,
+    failed INTEGER NOT NULL,
+    results JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- ERRORED: some examples couldn't run for reasons outside the spell; the version keeps
+-- its previous health_status
+ALTER TABLE spell_health_checks DROP CONSTRAINT IF EXISTS spell_health_checks_status_check;
+ALTER TABLE spell_health_checks ADD CONSTRAINT spell_health_checks_status_check CHECK (
+    status IN ('PASSING', 'FAILING', 'ERRORED')
+);
+
+CREATE INDEX IF NOT EXISTS idx_spell_health_checks_spell ON spell_health_checks(spell_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_spell_versions_health_due ON spell_versions(next_health_check_at NULLS FIRST)
+    WHERE status <> 'yanked' AND manifest ? 'examples';
@@ -0,0 +1,11 @@
+-- Phase 4: Recovery of asynchronous casts lost with their worker
+
+-- Casts handed to the worker queue, as opposed to run within the request
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS run_async BOOLEAN NOT NULL DEFAULT false;
+-- Touched by the worker while the cast runs, and when it is (re)queued
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS heartbeat_at TIMESTAMPTZ;
+-- Times the cast was put back on the queue after its worker was lost
+ALTER TABLE casts ADD COLUMN IF NOT EXISTS requeue_count INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_casts_async_pending ON casts(status, heartbeat_at)
+    WHERE run_async AND status IN ('QUEUED', 'RUNNING');
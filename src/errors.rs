@@ -2,9 +2,13 @@ use actix_web::{http::StatusCode, HttpResponse, ResponseError};
 use serde::Serialize;
 use std::fmt;
 
+use crate::middleware::request_id;
 use crate::models::billing::BudgetExceededError;
+use crate::services::stripe_service::SetupIntentFailure;
 
-#[derive(Debug, Serialize)]
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub enum ErrorCategory {
     #[serde(rename = "NETWORK_RETRYABLE")]
     NetworkRetryable,
@@ -16,39 +20,193 @@ pub enum ErrorCategory {
     PermRuntime,
 }
 
+impl ErrorCategory {
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCategory::NetworkRetryable | ErrorCategory::TransientRuntime
+        )
+    }
+}
+
+/// RFC 7807 problem details, the body of every error response
+/// `error_code` is the stable identifier clients branch on; `detail` is for humans.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub error_code: String,
+    pub category: ErrorCategory,
+    pub retryable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Problem-specific members, e.g. budget figures
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<serde_json::Value>,
+}
+
+impl Problem {
+    pub fn new(
+        status: StatusCode,
+        error_code: &str,
+        category: ErrorCategory,
+        detail: String,
+    ) -> Self {
+        Self {
+            type_uri: format!("urn:magicspell:error:{}", error_code.to_ascii_lowercase()),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail,
+            error_code: error_code.to_string(),
+            category,
+            retryable: category.is_retryable(),
+            retry_after: None,
+            request_id: request_id::current(),
+            extensions: None,
+        }
+    }
+
+    /// Problem for an error produced outside our handlers (routing, extractors)
+    pub fn from_status(status: StatusCode, detail: String) -> Self {
+        let (error_code, category) = match status {
+            StatusCode::UNAUTHORIZED => ("UNAUTHORIZED", ErrorCategory::PermConfig),
+            StatusCode::FORBIDDEN => ("FORBIDDEN", ErrorCategory::PermConfig),
+            StatusCode::NOT_FOUND => ("NOT_FOUND", ErrorCategory::PermConfig),
+            StatusCode::METHOD_NOT_ALLOWED => ("METHOD_NOT_ALLOWED", ErrorCategory::PermConfig),
+            StatusCode::PAYLOAD_TOO_LARGE => ("PAYLOAD_TOO_LARGE", ErrorCategory::PermConfig),
+            StatusCode::TOO_MANY_REQUESTS => ("RATE_LIMITED", ErrorCategory::TransientRuntime),
+            s if s.is_client_error() => ("INVALID_REQUEST", ErrorCategory::PermConfig),
+            StatusCode::SERVICE_UNAVAILABLE => {
+                ("SERVICE_UNAVAILABLE", ErrorCategory::NetworkRetryable)
+            }
+            _ => ("INTERNAL_ERROR", ErrorCategory::NetworkRetryable),
+        };
+        Self::new(status, error_code, category, detail)
+    }
+
+    pub fn with_retry_after(mut self, secs: Option<u64>) -> Self {
+        self.retry_after = secs;
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: serde_json::Value) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    pub fn to_response(&self) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut builder = HttpResponse::build(status);
+        builder.content_type(PROBLEM_CONTENT_TYPE);
+        if let Some(secs) = self.retry_after {
+            builder.insert_header(("Retry-After", secs.to_string()));
+        }
+        builder.body(serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
 #[derive(Debug)]
 pub enum CastError {
     DatabaseError(sqlx::Error),
+    SpellNotFound(String),
     WasmNotFound(String),
     WasmExecutionFailed(String),
     WasmTimeout,
+    OutputContractViolation(String),
     InvalidInput(String),
+    InputTooLarge {
+        size_bytes: usize,
+        limit_bytes: i32,
+    },
     InternalError(String),
     BudgetExceeded(BudgetExceededError),
+    IdempotencyInProgress,
+    IdempotencyKeyReused,
+    Canceled,
+    /// The version's circuit is open after repeated failures
+    SpellUnavailable {
+        spell_name: String,
+        retry_after_secs: u64,
+    },
 }
 
 impl CastError {
     pub fn category(&self) -> ErrorCategory {
         match self {
             CastError::DatabaseError(_) => ErrorCategory::NetworkRetryable,
+            CastError::SpellNotFound(_) => ErrorCategory::PermConfig,
             CastError::WasmNotFound(_) => ErrorCategory::PermConfig,
             CastError::WasmExecutionFailed(_) => ErrorCategory::PermRuntime,
             CastError::WasmTimeout => ErrorCategory::TransientRuntime,
+            CastError::OutputContractViolation(_) => ErrorCategory::PermRuntime,
             CastError::InvalidInput(_) => ErrorCategory::PermConfig,
+            CastError::InputTooLarge { .. } => ErrorCategory::PermConfig,
             CastError::InternalError(_) => ErrorCategory::NetworkRetryable,
             CastError::BudgetExceeded(_) => ErrorCategory::PermConfig,
+            CastError::IdempotencyInProgress => ErrorCategory::TransientRuntime,
+            CastError::IdempotencyKeyReused => ErrorCategory::PermConfig,
+            CastError::Canceled => ErrorCategory::PermRuntime,
+            CastError::SpellUnavailable { .. } => ErrorCategory::TransientRuntime,
+        }
+    }
+
+    /// Whether retrying the same cast might succeed
+    pub fn is_retryable(&self) -> bool {
+        self.category().is_retryable()
+    }
+
+    /// The problem details this error is reported as, over HTTP or in a cast event stream
+    pub fn problem(&self) -> Problem {
+        let problem = Problem::new(
+            self.status_code(),
+            self.error_code(),
+            self.category(),
+            self.to_string(),
+        )
+        .with_retry_after(self.retry_after());
+
+        match self {
+            // Budget figures stay at the top level, as clients already read them there
+            CastError::BudgetExceeded(budget_err) => {
+                problem.with_extensions(serde_json::to_value(budget_err).unwrap_or_default())
+            }
+            _ => problem,
+        }
+    }
+
+    /// Seconds a client should wait before retrying, when we know
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            CastError::DatabaseError(_) => Some(1),
+            CastError::IdempotencyInProgress => Some(1),
+            CastError::SpellUnavailable {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            _ => None,
         }
     }
 
     pub fn error_code(&self) -> &str {
         match self {
             CastError::DatabaseError(_) => "DB_ERROR",
+            CastError::SpellNotFound(_) => "SPELL_NOT_FOUND",
             CastError::WasmNotFound(_) => "WASM_NOT_FOUND",
             CastError::WasmExecutionFailed(_) => "WASM_EXEC_FAILED",
             CastError::WasmTimeout => "WASM_TIMEOUT",
+            CastError::OutputContractViolation(_) => "OUTPUT_CONTRACT_VIOLATION",
             CastError::InvalidInput(_) => "INVALID_INPUT",
+            CastError::InputTooLarge { .. } => "INPUT_TOO_LARGE",
             CastError::InternalError(_) => "INTERNAL_ERROR",
             CastError::BudgetExceeded(_) => "BUDGET_EXCEEDED",
+            CastError::IdempotencyInProgress => "IDEMPOTENCY_IN_PROGRESS",
+            CastError::IdempotencyKeyReused => "IDEMPOTENCY_KEY_REUSED",
+            CastError::Canceled => "CAST_CANCELED",
+            CastError::SpellUnavailable { .. } => "SPELL_UNAVAILABLE",
         }
     }
 }
@@ -57,16 +215,40 @@ impl fmt::Display for CastError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CastError::DatabaseError(e) => write!(f, "Database error: {e}"),
+            CastError::SpellNotFound(name) => write!(f, "Spell '{name}' not found or inactive"),
             CastError::WasmNotFound(name) => write!(f, "WASM module not found: {name}"),
             CastError::WasmExecutionFailed(msg) => write!(f, "WASM execution failed: {msg}"),
             CastError::WasmTimeout => write!(f, "WASM execution timeout"),
+            CastError::OutputContractViolation(msg) => {
+                write!(f, "Spell output violates its declared schema: {msg}")
+            }
             CastError::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
+            CastError::InputTooLarge {
+                size_bytes,
+                limit_bytes,
+            } => write!(
+                f,
+                "Input of {size_bytes} bytes exceeds the spell's limit of {limit_bytes} bytes"
+            ),
             CastError::InternalError(msg) => write!(f, "Internal error: {msg}"),
             CastError::BudgetExceeded(err) => write!(
                 f,
                 "Budget exceeded: {} cents spent, limit is {} cents",
                 err.spent_cents, err.hard_limit_cents
             ),
+            CastError::IdempotencyInProgress => write!(
+                f,
+                "A request with this Idempotency-Key is still in progress"
+            ),
+            CastError::IdempotencyKeyReused => write!(
+                f,
+                "Idempotency-Key was already used with a different request body"
+            ),
+            CastError::Canceled => write!(f, "Cast was canceled"),
+            CastError::SpellUnavailable { spell_name, .. } => write!(
+                f,
+                "Spell '{spell_name}' is temporarily unavailable after repeated failures"
+            ),
         }
     }
 }
@@ -75,41 +257,295 @@ impl ResponseError for CastError {
     fn status_code(&self) -> StatusCode {
         match self {
             CastError::DatabaseError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            CastError::SpellNotFound(_) => StatusCode::NOT_FOUND,
             CastError::WasmNotFound(_) => StatusCode::NOT_FOUND,
             CastError::WasmExecutionFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
             CastError::WasmTimeout => StatusCode::REQUEST_TIMEOUT,
+            CastError::OutputContractViolation(_) => StatusCode::BAD_GATEWAY,
             CastError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            CastError::InputTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
             CastError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             CastError::BudgetExceeded(_) => StatusCode::PAYMENT_REQUIRED,
+            CastError::IdempotencyInProgress => StatusCode::CONFLICT,
+            CastError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            CastError::Canceled => StatusCode::CONFLICT,
+            CastError::SpellUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
+        self.problem().to_response()
+    }
+}
+
+impl From<sqlx::Error> for CastError {
+    fn from(err: sqlx::Error) -> Self {
+        CastError::DatabaseError(err)
+    }
+}
+
+/// Platform-wide error for every route outside the cast pipeline
+/// Cast failures are wrapped as-is so their codes stay identical on every endpoint.
+#[derive(Debug)]
+pub enum ApiError {
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    SpellNotFound(String),
+    InvalidRequest(String),
+    SchemaInvalid(String),
+    Conflict(String),
+    RateLimited { retry_after_secs: u64 },
+    PaymentMethodMissing(String),
+    BillingUnavailable(String),
+    PaymentProvider(SetupIntentFailure),
+    Unavailable(String),
+    Internal(String),
+    Cast(CastError),
+}
+
+impl ApiError {
+    /// Log a failed query and hide its details from the client
+    pub fn database(context: &str, err: sqlx::Error) -> Self {
+        log::error!("{context}: {err}");
+        ApiError::Internal("Database error".to_string())
+    }
+
+    pub fn category(&self) -> ErrorCategory {
         match self {
-            CastError::BudgetExceeded(budget_err) => {
-                // Return the full budget exceeded error with details
-                HttpResponse::build(self.status_code()).json(budget_err)
-            }
-            _ => {
-                #[derive(Serialize)]
-                struct ErrorResponse {
-                    error: String,
-                    error_code: String,
-                    category: ErrorCategory,
-                }
-
-                HttpResponse::build(self.status_code()).json(ErrorResponse {
-                    error: self.to_string(),
-                    error_code: self.error_code().to_string(),
-                    category: self.category(),
-                })
+            ApiError::Unauthorized(_) => ErrorCategory::PermConfig,
+            ApiError::Forbidden(_) => ErrorCategory::PermConfig,
+            ApiError::NotFound(_) => ErrorCategory::PermConfig,
+            ApiError::SpellNotFound(_) => ErrorCategory::PermConfig,
+            ApiError::InvalidRequest(_) => ErrorCategory::PermConfig,
+            ApiError::SchemaInvalid(_) => ErrorCategory::PermConfig,
+            ApiError::Conflict(_) => ErrorCategory::PermConfig,
+            ApiError::RateLimited { .. } => ErrorCategory::TransientRuntime,
+            ApiError::PaymentMethodMissing(_) => ErrorCategory::PermConfig,
+            ApiError::BillingUnavailable(_) => ErrorCategory::NetworkRetryable,
+            ApiError::PaymentProvider(_) => ErrorCategory::NetworkRetryable,
+            ApiError::Unavailable(_) => ErrorCategory::NetworkRetryable,
+            ApiError::Internal(_) => ErrorCategory::NetworkRetryable,
+            ApiError::Cast(e) => e.category(),
+        }
+    }
+
+    pub fn error_code(&self) -> &str {
+        match self {
+            ApiError::Unauthorized(_) => "UNAUTHORIZED",
+            ApiError::Forbidden(_) => "FORBIDDEN",
+            ApiError::NotFound(_) => "NOT_FOUND",
+            ApiError::SpellNotFound(_) => "SPELL_NOT_FOUND",
+            ApiError::InvalidRequest(_) => "INVALID_REQUEST",
+            ApiError::SchemaInvalid(_) => "SCHEMA_INVALID",
+            ApiError::Conflict(_) => "CONFLICT",
+            ApiError::RateLimited { .. } => "RATE_LIMITED",
+            ApiError::PaymentMethodMissing(_) => "PAYMENT_METHOD_MISSING",
+            ApiError::BillingUnavailable(_) => "BILLING_UNAVAILABLE",
+            ApiError::PaymentProvider(_) => "PAYMENT_PROVIDER_ERROR",
+            ApiError::Unavailable(_) => "SERVICE_UNAVAILABLE",
+            ApiError::Internal(_) => "INTERNAL_ERROR",
+            ApiError::Cast(e) => e.error_code(),
+        }
+    }
+
+    /// Seconds a client should wait before retrying, when we know
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            ApiError::RateLimited { retry_after_secs } => Some(*retry_after_secs),
+            ApiError::Unavailable(_) => Some(5),
+            ApiError::Cast(e) => e.retry_after(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Unauthorized(msg)
+            | ApiError::Forbidden(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::InvalidRequest(msg)
+            | ApiError::Conflict(msg)
+            | ApiError::PaymentMethodMissing(msg)
+            | ApiError::BillingUnavailable(msg)
+            | ApiError::Unavailable(msg)
+            | ApiError::Internal(msg) => write!(f, "{msg}"),
+            ApiError::SpellNotFound(name) => write!(f, "Spell '{name}' not found"),
+            ApiError::PaymentProvider(_) => write!(f, "Failed to create setup intent"),
+            ApiError::SchemaInvalid(msg) => write!(f, "Invalid schema: {msg}"),
+            ApiError::RateLimited { retry_after_secs } => {
+                write!(f, "Rate limit exceeded, retry in {retry_after_secs}s")
             }
+            ApiError::Cast(e) => write!(f, "{e}"),
         }
     }
 }
 
-impl From<sqlx::Error> for CastError {
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::SpellNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::SchemaInvalid(_) => StatusCode::BAD_REQUEST,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::PaymentMethodMissing(_) => StatusCode::NOT_FOUND,
+            ApiError::BillingUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::PaymentProvider(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Cast(e) => e.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let problem = Problem::new(
+            self.status_code(),
+            self.error_code(),
+            self.category(),
+            self.to_string(),
+        )
+        .with_retry_after(self.retry_after());
+
+        match self {
+            ApiError::Cast(e) => e.error_response(),
+            // Stripe's own identifiers help support trace the failed request
+            ApiError::PaymentProvider(failure) => problem
+                .with_extensions(serde_json::json!({
+                    "stripe_status": failure.status,
+                    "stripe_code": failure.code,
+                    "stripe_message": failure.message,
+                    "stripe_request_id": failure.request_id,
+                }))
+                .to_response(),
+            _ => problem.to_response(),
+        }
+    }
+}
+
+impl From<CastError> for ApiError {
+    fn from(err: CastError) -> Self {
+        ApiError::Cast(err)
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
     fn from(err: sqlx::Error) -> Self {
-        CastError::DatabaseError(err)
+        ApiError::database("Query failed", err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // The catalog is part of the public API: codes may be added, never renamed or reused
+    #[test]
+    fn error_codes_are_unique_and_stable() {
+        let api = [
+            ApiError::Unauthorized(String::new()),
+            ApiError::Forbidden(String::new()),
+            ApiError::NotFound(String::new()),
+            ApiError::SpellNotFound(String::new()),
+            ApiError::InvalidRequest(String::new()),
+            ApiError::SchemaInvalid(String::new()),
+            ApiError::Conflict(String::new()),
+            ApiError::RateLimited {
+                retry_after_secs: 1,
+            },
+            ApiError::PaymentMethodMissing(String::new()),
+            ApiError::BillingUnavailable(String::new()),
+            ApiError::PaymentProvider(SetupIntentFailure {
+                status: None,
+                code: None,
+                message: None,
+                request_id: None,
+            }),
+            ApiError::Unavailable(String::new()),
+            ApiError::Internal(String::new()),
+        ];
+        let cast = [
+            CastError::DatabaseError(sqlx::Error::RowNotFound),
+            CastError::SpellNotFound(String::new()),
+            CastError::WasmNotFound(String::new()),
+            CastError::WasmExecutionFailed(String::new()),
+            CastError::WasmTimeout,
+            CastError::OutputContractViolation(String::new()),
+            CastError::InvalidInput(String::new()),
+            CastError::InputTooLarge {
+                size_bytes: 2,
+                limit_bytes: 1,
+            },
+            CastError::InternalError(String::new()),
+            CastError::BudgetExceeded(BudgetExceededError::new("monthly".to_string(), 1, 1)),
+            CastError::IdempotencyInProgress,
+            CastError::IdempotencyKeyReused,
+            CastError::Canceled,
+            CastError::SpellUnavailable {
+                spell_name: String::new(),
+                retry_after_secs: 1,
+            },
+        ];
+
+        let api_codes: HashSet<&str> = api.iter().map(|e| e.error_code()).collect();
+        let cast_codes: HashSet<&str> = cast.iter().map(|e| e.error_code()).collect();
+        assert_eq!(api_codes.len(), api.len());
+        assert_eq!(cast_codes.len(), cast.len());
+
+        // A code shared by both types must mean the same thing on both
+        for a in &api {
+            for c in cast.iter().filter(|c| c.error_code() == a.error_code()) {
+                assert_eq!(a.status_code(), c.status_code(), "{}", a.error_code());
+                assert_eq!(a.category(), c.category(), "{}", a.error_code());
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn problem_body_carries_code_category_and_retry_hint() {
+        let response = ApiError::RateLimited {
+            retry_after_secs: 60,
+        }
+        .error_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            PROBLEM_CONTENT_TYPE
+        );
+        assert_eq!(response.headers().get("retry-after").unwrap(), "60");
+
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["type"], "urn:magicspell:error:rate_limited");
+        assert_eq!(problem["status"], 429);
+        assert_eq!(problem["error_code"], "RATE_LIMITED");
+        assert_eq!(problem["category"], "TRANSIENT_RUNTIME");
+        assert_eq!(problem["retryable"], true);
+        assert_eq!(problem["retry_after"], 60);
+    }
+
+    #[actix_web::test]
+    async fn budget_exceeded_keeps_budget_figures() {
+        let response =
+            CastError::BudgetExceeded(BudgetExceededError::new("monthly".to_string(), 500, 510))
+                .error_response();
+
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["error_code"], "BUDGET_EXCEEDED");
+        assert_eq!(problem["status"], 402);
+        assert_eq!(problem["hard_limit_cents"], 500);
+        assert_eq!(problem["spent_cents"], 510);
     }
 }
@@ -4,6 +4,7 @@ mod middleware;
 mod models;
 mod routes;
 mod services;
+mod storage;
 mod utils;
 mod wasm;
 
@@ -25,7 +26,12 @@ fn cors() -> Cors {
         .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
         .allowed_header("Stripe-Signature")
         .allowed_header("X-CSRF-Token")
+        .allowed_header("Idempotency-Key")
+        .allowed_header(header::CACHE_CONTROL)
+        .allowed_header("X-Request-Id")
         .expose_headers(vec![
+            "Idempotent-Replayed",
+            "X-Request-Id",
             "RateLimit-Limit",
             "RateLimit-Remaining",
             "RateLimit-Reset",
@@ -37,7 +43,23 @@ fn cors() -> Cors {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    env_logger::init();
+    // Log lines emitted while handling a request (or running its cast) carry its id
+    env_logger::Builder::from_default_env()
+        .format(|buf, record| {
+            use std::io::Write;
+            let request_id = middleware::request_id::current()
+                .map(|id| format!(" request_id={id}"))
+                .unwrap_or_default();
+            writeln!(
+                buf,
+                "[{} {:<5} {}{request_id}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                record.args()
+            )
+        })
+        .init();
 
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let redis_url = env::var("REDIS_URL").expect("REDIS_URL must be set");
@@ -54,8 +76,15 @@ async fn main() -> std::io::Result<()> {
         .create_pool(Some(deadpool_redis::Runtime::Tokio1))
         .expect("Failed to create Redis pool");
 
+    log::info!("Initializing blob storage...");
+    let blob_store = storage::from_env().expect("Failed to configure blob storage");
+
     log::info!("Initializing WASM runtime...");
-    let wasm_runtime = wasm::WasmRuntime::new(&wasm_path);
+    let module_cache_size = env::var("WASM_MODULE_CACHE_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(64);
+    let wasm_runtime = wasm::WasmRuntime::new(&wasm_path, blob_store.clone(), module_cache_size);
 
     log::info!("Initializing Stripe service...");
     let stripe_service = StripeService::new();
@@ -73,12 +102,23 @@ async fn main() -> std::io::Result<()> {
     let app_data = web::Data::new(AppState {
         db: pool,
         wasm: wasm_runtime,
+        storage: blob_store,
         redis: redis_pool.clone(),
         stripe: stripe_data,
     });
 
     let metrics_data = web::Data::new(metrics.clone());
 
+    let cast_workers = env::var("CAST_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(4);
+    log::info!("Starting {cast_workers} async cast workers...");
+    services::cast_queue::CastQueue::spawn_workers(app_data.clone(), cast_workers);
+    services::webhook_service::WebhookService::spawn_dispatcher(app_data.db.clone());
+    services::schedule_service::ScheduleService::spawn_scheduler(app_data.clone());
+    services::health_check_service::HealthCheckService::spawn_checker(app_data.clone());
+
     log::info!("Starting server on 0.0.0.0:8080");
 
     HttpServer::new(move || {
@@ -99,6 +139,7 @@ async fn main() -> std::io::Result<()> {
                     .add(("Vary", "Origin")),
             )
             .wrap(middleware::rate_limit::RateLimit::new(redis_pool.clone()))
+            .wrap(middleware::request_id::RequestIdMiddleware)
             .app_data(app_data.clone())
             .app_data(metrics_data.clone())
             .route("/healthz", web::get().to(healthz))
@@ -112,6 +153,14 @@ async fn main() -> std::io::Result<()> {
             .service(
                 web::scope("/v1")
                     .configure(routes::cast::configure)
+                    .configure(routes::casts::configure)
+                    .configure(routes::catalog::configure)
+                    .configure(routes::spells::configure)
+                    .configure(routes::sessions::configure)
+                    .configure(routes::pipelines::configure)
+                    .configure(routes::schedules::configure)
+                    .configure(routes::deprecations::configure)
+                    .configure(routes::webhooks::configure)
                     .configure(routes::billing::configure),
             )
     })
@@ -130,6 +179,7 @@ async fn healthz() -> HttpResponse {
 pub struct AppState {
     pub db: sqlx::PgPool,
     pub wasm: wasm::WasmRuntime,
+    pub storage: Arc<dyn storage::BlobStore>,
     pub redis: deadpool_redis::Pool,
     pub stripe: Option<StripeService>,
 }
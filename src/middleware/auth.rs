@@ -1,3 +1,4 @@
+use crate::errors::ApiError;
 use crate::models::{ApiKey, Session, User};
 use crate::utils::apikey::{extract_prefix, verify_api_key};
 use actix_web::{dev::ServiceRequest, Error, HttpMessage, HttpRequest};
@@ -15,7 +16,7 @@ pub async fn validator(
         Some(data) => &data.db,
         None => {
             return Err((
-                actix_web::error::ErrorInternalServerError("Database pool not found"),
+                ApiError::Internal("Database pool not found".to_string()).into(),
                 req,
             ));
         }
@@ -24,8 +25,10 @@ pub async fn validator(
     // Check if token is an API key (starts with "sk_")
     if token.starts_with("sk_") {
         match authenticate_api_key(pool, token).await {
-            Ok(user) => {
+            Ok((user, api_key)) => {
+                // Handlers use the key for per-key settings such as the default callback URL
                 req.extensions_mut().insert(user);
+                req.extensions_mut().insert(api_key);
                 return Ok(req);
             }
             Err(e) => {
@@ -42,20 +45,20 @@ pub async fn validator(
             Ok(req)
         }
         Err(_) => Err((
-            actix_web::error::ErrorUnauthorized("Invalid or expired credentials"),
+            ApiError::Unauthorized("Invalid or expired credentials".to_string()).into(),
             req,
         )),
     }
 }
 
-async fn authenticate_api_key(pool: &PgPool, token: &str) -> Result<User, anyhow::Error> {
+async fn authenticate_api_key(pool: &PgPool, token: &str) -> Result<(User, ApiKey), anyhow::Error> {
     // Extract prefix from API key
     let prefix = extract_prefix(token).ok_or_else(|| anyhow::anyhow!("Invalid API key format"))?;
 
     // Fetch all API keys with matching prefix
     let api_keys: Vec<ApiKey> = sqlx::query_as::<_, ApiKey>(
         r#"
-        SELECT id, user_id, name, prefix, hash, created_at, last_used_at
+        SELECT id, user_id, name, prefix, hash, created_at, last_used_at, default_callback_url
         FROM api_keys
         WHERE prefix = $1
         "#,
@@ -89,7 +92,7 @@ async fn authenticate_api_key(pool: &PgPool, token: &str) -> Result<User, anyhow
             .fetch_one(pool)
             .await?;
 
-            return Ok(user);
+            return Ok((user, api_key));
         }
     }
 
@@ -1,9 +1,10 @@
+use crate::errors::ApiError;
 use crate::models::User;
 use actix_web::dev::{Service, Transform};
 use actix_web::{
     body::{BoxBody, EitherBody, MessageBody},
     dev::{ServiceRequest, ServiceResponse},
-    Error, HttpMessage, HttpResponse,
+    Error, HttpMessage, ResponseError,
 };
 use deadpool_redis::Pool;
 use futures::future::{ok, Ready};
@@ -11,6 +12,14 @@ use futures::Future;
 use std::pin::Pin;
 use std::rc::Rc;
 use std::task::{Context, Poll};
+use uuid::Uuid;
+
+/// Requests per minute allowed to an authenticated user; scheduled casts count too
+pub const USER_LIMIT_PER_MIN: u64 = 60;
+
+pub fn user_key(user_id: &Uuid) -> String {
+    format!("rate:user:{user_id}")
+}
 
 pub struct RateLimit {
     redis_pool: Pool,
@@ -76,7 +85,7 @@ where
                 let user_id = req.extensions().get::<User>().map(|u| u.id);
                 if let Some(user_id) = user_id {
                     // Authenticated: 60 req/min per user
-                    (format!("rate:user:{user_id}"), 60)
+                    (user_key(&user_id), USER_LIMIT_PER_MIN)
                 } else {
                     // Unauthenticated: 10 req/min per IP
                     let ip = req
@@ -92,13 +101,10 @@ where
             match check_rate_limit(&redis_pool, &key, limit).await {
                 Ok(allowed) => {
                     if !allowed {
-                        let retry_after = 60; // seconds
-                        let response = HttpResponse::TooManyRequests()
-                            .insert_header(("Retry-After", retry_after.to_string()))
-                            .json(serde_json::json!({
-                                "error": "rate_limited",
-                                "retry_after": retry_after
-                            }));
+                        let response = ApiError::RateLimited {
+                            retry_after_secs: 60,
+                        }
+                        .error_response();
                         return Ok(req.into_response(response).map_into_right_body());
                     }
                 }
@@ -113,7 +119,7 @@ where
     }
 }
 
-async fn check_rate_limit(pool: &Pool, key: &str, limit: u64) -> Result<bool, anyhow::Error> {
+pub async fn check_rate_limit(pool: &Pool, key: &str, limit: u64) -> Result<bool, anyhow::Error> {
     let mut conn = pool.get().await?;
 
     // INCR and check
@@ -0,0 +1,223 @@
+use actix_web::body::{to_bytes, BoxBody, EitherBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::Error;
+use futures::future::{ok, Ready};
+use futures::Future;
+use serde_json::Value;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use uuid::Uuid;
+
+use crate::errors::{Problem, PROBLEM_CONTENT_TYPE};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// Request id of the task currently running, if it is handling a request or a cast
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+/// Run `fut` with `id` as the current request id, e.g. when a worker resumes a queued cast
+pub async fn scope<F: Future>(id: Option<String>, fut: F) -> F::Output {
+    match id {
+        Some(id) => CURRENT.scope(id, fut).await,
+        None => fut.await,
+    }
+}
+
+/// Accept a caller-supplied id only if it is short and made of safe characters
+fn accepted(raw: &str) -> bool {
+    !raw.is_empty()
+        && raw.len() <= MAX_REQUEST_ID_LEN
+        && raw
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Assigns every request an `X-Request-Id` (the caller's, if valid, else a new UUID)
+/// The id is echoed in the response header, added to error bodies and visible to
+/// `current()` for the whole handler, so log lines and cast rows can carry it.
+pub struct RequestIdMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, BoxBody>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestIdService {
+            service: Rc::new(service),
+        })
+    }
+}
+
+pub struct RequestIdService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, BoxBody>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        let id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| accepted(v))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        Box::pin(CURRENT.scope(id.clone(), async move {
+            // Handler and auth failures arrive here as error responses, not as Err
+            let res = service.call(req).await?;
+            let mut res = if res.status().is_client_error() || res.status().is_server_error() {
+                with_request_id_in_body(res, &id)
+                    .await
+                    .map_into_right_body()
+            } else {
+                res.map_into_left_body()
+            };
+
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(res)
+        }))
+    }
+}
+
+/// Add `request_id` to a JSON error body, or turn any other error body into problem+json
+/// Errors raised before our handlers (routing, extractors) arrive here as plain text.
+async fn with_request_id_in_body<B: MessageBody>(
+    res: ServiceResponse<B>,
+    id: &str,
+) -> ServiceResponse<BoxBody> {
+    let (http_req, res) = res.into_parts();
+    let status = res.status();
+    let (mut res, body) = res.into_parts();
+    let bytes = to_bytes(body).await.unwrap_or_default();
+
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(mut map)) => {
+            map.entry("request_id")
+                .or_insert_with(|| Value::String(id.to_string()));
+            Value::Object(map).to_string()
+        }
+        _ => {
+            let text = String::from_utf8_lossy(&bytes);
+            let detail = if text.trim().is_empty() {
+                status.canonical_reason().unwrap_or("Error").to_string()
+            } else {
+                text.into_owned()
+            };
+            res.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(PROBLEM_CONTENT_TYPE),
+            );
+            let mut problem = Problem::from_status(status, detail);
+            problem.request_id = Some(id.to_string());
+            serde_json::to_string(&problem).unwrap_or_default()
+        }
+    };
+
+    ServiceResponse::new(http_req, res.set_body(BoxBody::new(body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{
+        call_and_read_body_json, call_service, init_service, read_body, read_body_json, TestRequest,
+    };
+    use actix_web::{web, App, HttpResponse};
+
+    #[test]
+    fn accepts_only_short_safe_ids() {
+        assert!(accepted("req-42_a.b"));
+        assert!(!accepted(""));
+        assert!(!accepted("has space"));
+        assert!(!accepted(&"x".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+
+    #[actix_web::test]
+    async fn error_bodies_and_headers_carry_the_id() {
+        let app = init_service(
+            App::new()
+                .wrap(RequestIdMiddleware)
+                .route(
+                    "/ok",
+                    web::get().to(|| async { HttpResponse::Ok().body(current().unwrap()) }),
+                )
+                .route(
+                    "/json",
+                    web::get().to(|| async {
+                        HttpResponse::BadRequest().json(serde_json::json!({"error": "bad"}))
+                    }),
+                )
+                .route(
+                    "/text",
+                    web::get().to(|| async {
+                        Err::<HttpResponse, _>(actix_web::error::ErrorNotFound("Cast not found"))
+                    }),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/ok")
+            .insert_header((REQUEST_ID_HEADER, "abc-123"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "abc-123");
+        assert_eq!(read_body(res).await, "abc-123");
+
+        let req = TestRequest::get()
+            .uri("/json")
+            .insert_header((REQUEST_ID_HEADER, "abc-123"))
+            .to_request();
+        let body: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(body["error"], "bad");
+        assert_eq!(body["request_id"], "abc-123");
+
+        let req = TestRequest::get().uri("/text").to_request();
+        let res = call_service(&app, req).await;
+        let generated = res.headers().get(REQUEST_ID_HEADER).unwrap().clone();
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            PROBLEM_CONTENT_TYPE
+        );
+        let body: Value = read_body_json(res).await;
+        assert_eq!(body["detail"], "Cast not found");
+        assert_eq!(body["error_code"], "NOT_FOUND");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["request_id"], generated.to_str().unwrap());
+    }
+}
@@ -12,6 +12,7 @@ pub struct ApiKey {
     pub hash: String,
     pub created_at: DateTime<Utc>,
     pub last_used_at: Option<DateTime<Utc>>,
+    pub default_callback_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -33,4 +34,11 @@ pub struct ListApiKeyResponse {
     pub prefix: String,
     pub created_at: DateTime<Utc>,
     pub last_used_at: Option<DateTime<Utc>>,
+    pub default_callback_url: Option<String>,
+}
+
+/// Set (or clear, with null) the webhook URL used for casts made with this key
+#[derive(Debug, Deserialize)]
+pub struct SetCallbackUrlRequest {
+    pub url: Option<String>,
 }
@@ -66,6 +66,14 @@ pub struct CheckoutSessionResponse {
     pub session_id: String,
 }
 
+/// Where a user stands against their hard limit in the current period
+#[derive(Debug, Serialize)]
+pub struct HardLimitStatus {
+    pub period: String,
+    pub hard_limit_cents: i32,
+    pub spent_cents: i32,
+}
+
 #[derive(Debug, Serialize)]
 pub struct BudgetExceededError {
     pub error: String,
@@ -1,35 +1,157 @@
 pub mod apikey;
 pub mod billing;
+pub mod pipeline;
+pub mod replay;
+pub mod schedule;
 pub mod spell;
 pub mod user;
+pub mod webhook;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-pub use apikey::{ApiKey, CreateApiKeyRequest, CreateApiKeyResponse, ListApiKeyResponse};
+pub use apikey::{
+    ApiKey, CreateApiKeyRequest, CreateApiKeyResponse, ListApiKeyResponse, SetCallbackUrlRequest,
+};
 pub use spell::Spell;
 pub use user::{GitHubAccessTokenResponse, GitHubUser, Session, User};
 
-// Future implementation: Cast history tracking
-#[allow(dead_code)]
+/// A recorded cast as shown in the caster's history, including payload and result
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Cast {
     pub id: Uuid,
     pub spell_name: String,
+    #[sqlx(default)]
+    pub spell_version: Option<String>,
     pub payload: serde_json::Value,
     pub status: String,
     pub result: Option<serde_json::Value>,
     pub error_code: Option<String>,
+    #[serde(rename = "error")]
+    pub error_message: Option<String>,
+    pub callback_url: Option<String>,
     pub user_id: Option<Uuid>,
     pub cost_cents: Option<i32>,
+    pub attempts: i32,
+    pub cached: bool,
+    /// X-Request-Id of the request that created the cast
+    pub request_id: Option<String>,
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "input_files_empty")]
+    pub input_files: sqlx::types::Json<Vec<InputFile>>,
+    /// Pipeline run the cast is a step of, and which step
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline_run_id: Option<Uuid>,
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline_step: Option<String>,
+    /// Schedule the cast was a run of
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Per-attempt outcomes, filled in for single-cast lookups only
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempt_log: Vec<CastAttempt>,
+}
+
+fn input_files_empty(files: &sqlx::types::Json<Vec<InputFile>>) -> bool {
+    files.0.is_empty()
+}
+
+/// A file sent with a cast (multipart part or raw body), kept in the blob store
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InputFile {
+    pub name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub digest: String,
+}
+
+/// One execution attempt of a cast; retryable failures may be followed by another
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CastAttempt {
+    pub attempt: i32,
+    pub status: String,
+    pub error_code: Option<String>,
+    pub error_message: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Filters for a caster's cast history
+#[derive(Debug, Deserialize)]
+pub struct CastHistoryQuery {
+    pub spell: Option<String>,
+    pub status: Option<String>,
+    pub error_code: Option<String>,
+    /// X-Request-Id from a support report
+    pub request_id: Option<String>,
+    /// Runs of one cast schedule
+    pub schedule_id: Option<Uuid>,
+    /// Inclusive lower bound on created_at
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on created_at
+    pub to: Option<DateTime<Utc>>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Keyset position of the last cast on a history page (newest first)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CastHistoryCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+/// History entry without payload and result; fetch the cast by id for those
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CastSummary {
+    pub id: Uuid,
+    pub spell_name: String,
+    pub spell_version: Option<String>,
+    pub status: String,
+    pub error_code: Option<String>,
+    pub cost_cents: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Totals across every cast matching the filters, not just the current page
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CastHistoryTotals {
+    pub count: i64,
+    pub cost_cents: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CastHistoryPage {
+    pub casts: Vec<CastSummary>,
+    pub totals: CastHistoryTotals,
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CastRequest {
     pub spell_name: String,
+    /// Pin an exact spell version; yanked versions are only reachable this way
+    pub version: Option<String>,
+    /// Optional when the input is sent as files
+    #[serde(default)]
     pub payload: serde_json::Value,
+    /// Return 202 immediately and run the cast on the worker pool
+    #[serde(default, rename = "async")]
+    pub run_async: bool,
+    /// Webhook notified with the final result; defaults to the API key's callback URL
+    pub callback_url: Option<String>,
+    /// Execute even if a cached result exists (also `Cache-Control: no-cache`)
+    #[serde(default)]
+    pub bypass_cache: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -38,5 +160,89 @@ pub struct CastResponse {
     pub status: String,
     pub result: Option<serde_json::Value>,
     pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spell_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    /// Execution attempts made so far (0 while queued or when served from the cache)
+    pub attempts: i32,
+    /// Served from the result cache of a pure spell version
+    pub cached: bool,
     pub created_at: DateTime<Utc>,
 }
+
+/// What a cast would cost and whether it would be accepted, without running it
+#[derive(Debug, Serialize)]
+pub struct CastEstimate {
+    pub spell_name: String,
+    pub spell_version: String,
+    pub price_cents: i32,
+    /// Price when served from the result cache (pure versions only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_price_cents: Option<i32>,
+    /// None when the caller has no hard budget limit
+    pub budget: Option<BudgetEstimate>,
+    pub would_be_rejected: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<CastRejection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BudgetEstimate {
+    pub period: String,
+    pub hard_limit_cents: i32,
+    pub spent_cents: i32,
+    /// Negative when the cast would take spend past the limit
+    pub remaining_cents_after: i32,
+}
+
+/// Why a real cast with the same request would be refused
+#[derive(Debug, Serialize)]
+pub struct CastRejection {
+    pub error_code: String,
+    pub error: String,
+}
+
+/// Cast one spell over many payloads in a single request
+#[derive(Debug, Deserialize)]
+pub struct BatchCastRequest {
+    pub spell_name: String,
+    pub version: Option<String>,
+    pub payloads: Vec<serde_json::Value>,
+    /// Items executed concurrently (default 8, max 32)
+    pub max_parallelism: Option<usize>,
+    #[serde(default)]
+    pub bypass_cache: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchItemResult {
+    pub index: usize,
+    /// None if the item could not even be recorded
+    pub cast_id: Option<Uuid>,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<i32>,
+    pub cached: bool,
+    pub cost_cents: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchCastResponse {
+    pub batch_id: Uuid,
+    pub spell_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub total_cost_cents: i64,
+    pub items: Vec<BatchItemResult>,
+}
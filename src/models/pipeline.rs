@@ -0,0 +1,140 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const MAX_PIPELINE_STEPS: usize = 16;
+/// Mapping source naming the input the whole pipeline was run with
+pub const PIPELINE_INPUT: &str = "$input";
+
+pub const RUN_COMPLETED: &str = "COMPLETED";
+/// Some steps completed, others failed or were skipped
+pub const RUN_PARTIAL: &str = "PARTIAL";
+pub const RUN_FAILED: &str = "FAILED";
+/// A step never cast because a step it depends on did not complete
+pub const STEP_SKIPPED: &str = "SKIPPED";
+
+/// A sequence or small DAG of casts, wired together with JSON-pointer mappings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineDefinition {
+    pub steps: Vec<PipelineStep>,
+    /// Step whose result is the pipeline's output; defaults to the last step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_step: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStep {
+    pub id: String,
+    pub spell_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Starting payload that mapped values are written into (default `{}`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+    /// Values copied into the payload from the pipeline input or earlier results
+    /// A step with neither mappings nor a payload takes the result of the step listed
+    /// before it, or the pipeline input if it is first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<InputMapping>,
+    /// Steps to wait for besides those mapped from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputMapping {
+    /// Step id, or `$input`
+    pub from: String,
+    /// JSON pointer into the source; empty for the whole value
+    #[serde(default)]
+    pub pointer: String,
+    /// JSON pointer in this step's payload; empty replaces the payload
+    #[serde(default)]
+    pub to: String,
+}
+
+/// A saved pipeline definition, owned by one user and addressed by name
+#[derive(Debug, Serialize, FromRow)]
+pub struct Pipeline {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub definition: Json<PipelineDefinition>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SavePipelineRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub definition: PipelineDefinition,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePipelineRequest {
+    pub description: Option<String>,
+    pub definition: PipelineDefinition,
+}
+
+/// Run a saved pipeline
+#[derive(Debug, Deserialize)]
+pub struct RunPipelineRequest {
+    #[serde(default)]
+    pub input: Value,
+    #[serde(default)]
+    pub bypass_cache: bool,
+}
+
+/// Run a pipeline given inline, without saving it
+#[derive(Debug, Deserialize)]
+pub struct RunInlinePipelineRequest {
+    pub definition: PipelineDefinition,
+    #[serde(default)]
+    pub input: Value,
+    #[serde(default)]
+    pub bypass_cache: bool,
+}
+
+/// Outcome of one step; every step is reported, including skipped ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineStepResult {
+    pub id: String,
+    pub spell_name: String,
+    /// COMPLETED, FAILED, CANCELED or SKIPPED
+    pub status: String,
+    /// None if the step was skipped or its payload could not be built
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cast_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<i32>,
+    pub cached: bool,
+    pub cost_cents: i32,
+}
+
+/// One execution of a pipeline, with the definition it ran
+#[derive(Debug, Serialize, FromRow)]
+pub struct PipelineRun {
+    pub id: Uuid,
+    pub pipeline_id: Option<Uuid>,
+    /// RUNNING, COMPLETED, PARTIAL or FAILED
+    pub status: String,
+    pub input: Value,
+    /// Result of the output step, if it completed
+    pub output: Option<Value>,
+    pub steps: Json<Vec<PipelineStepResult>>,
+    pub cost_cents: i32,
+    pub definition: Json<PipelineDefinition>,
+    pub request_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::utils::json_diff::DiffEntry;
+
+pub const MAX_REPLAY_CASTS: usize = 50;
+pub const DEFAULT_REPLAY_CASTS: usize = 20;
+
+/// Replay past casts of a spell against one of its versions
+/// Either list `cast_ids`, or filter the spell's cast history (newest first).
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    #[serde(default)]
+    pub cast_ids: Vec<Uuid>,
+    /// Only casts with this status; defaults to COMPLETED when filtering
+    pub status: Option<String>,
+    /// Only casts that ran this version
+    pub from_version: Option<String>,
+    /// Inclusive lower bound on created_at
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on created_at
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+/// How one replayed cast compares with the original
+#[derive(Debug, Serialize)]
+pub struct ReplayResult {
+    pub cast_id: Uuid,
+    /// Version the original cast ran
+    pub original_version: Option<String>,
+    pub original_status: String,
+    /// COMPLETED or FAILED
+    pub replay_status: String,
+    /// Both completed with equal results, or both failed with the same error code
+    pub identical: bool,
+    /// Differences from the original result to the replay's, when both completed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diff: Vec<DiffEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Replays of past casts against one version; nothing is recorded or billed
+#[derive(Debug, Serialize)]
+pub struct ReplayReport {
+    pub spell_name: String,
+    pub version: String,
+    pub replayed: usize,
+    pub identical: usize,
+    pub changed: usize,
+    pub results: Vec<ReplayResult>,
+}
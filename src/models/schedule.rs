@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const SCHEDULE_ACTIVE: &str = "ACTIVE";
+pub const SCHEDULE_PAUSED: &str = "PAUSED";
+
+/// The last run was cast and queued; its cast's own status supersedes this
+pub const RUN_QUEUED: &str = "QUEUED";
+/// The last run was refused before casting, e.g. over budget or rate limited
+pub const RUN_SKIPPED: &str = "SKIPPED";
+/// The last run was cast but failed before a worker picked it up
+pub const RUN_FAILED: &str = "FAILED";
+
+/// A spell cast on a cron expression with a stored payload, owned by one user
+#[derive(Debug, Serialize, FromRow)]
+pub struct CastSchedule {
+    pub id: Uuid,
+    pub name: String,
+    pub spell_name: String,
+    /// Pinned version; the newest castable version when None
+    pub version: Option<String>,
+    pub payload: Value,
+    pub cron: String,
+    /// ACTIVE or PAUSED
+    pub status: String,
+    /// None while paused
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_cast_id: Option<Uuid>,
+    /// Status of the last run's cast, or SKIPPED if it was never cast
+    pub last_status: Option<String>,
+    pub last_error_code: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduleRequest {
+    pub name: String,
+    pub spell_name: String,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub payload: Value,
+    /// Five-field cron expression (minute hour day-of-month month day-of-week), in UTC
+    pub cron: String,
+    /// Create the schedule paused
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Replace what a schedule casts and when; runs already made are unaffected
+#[derive(Debug, Deserialize)]
+pub struct UpdateScheduleRequest {
+    pub spell_name: String,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub payload: Value,
+    pub cron: String,
+}
@@ -1,8 +1,11 @@
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::utils::codec::Codec;
+use crate::utils::json_diff::DiffEntry;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Spell {
     pub id: Uuid,
@@ -12,10 +15,220 @@ pub struct Spell {
     pub price_cents: i32,
     pub wasm_path: String,
     pub is_active: bool,
+    pub output_schema: Option<serde_json::Value>,
+    pub manifest: serde_json::Value,
+    pub next_price_cents: Option<i32>,
+    pub next_price_effective_at: Option<DateTime<Utc>>,
+    pub retry_max_attempts: i32,
+    pub retry_backoff_ms: i32,
+    pub cache_ttl_secs: i32,
+    pub cache_discount_percent: i32,
+    /// `per_call` or `per_session`; how WebSocket session messages are billed
+    pub pricing_model: String,
+    /// Largest cast input (JSON payload plus files) accepted for this spell
+    pub max_input_bytes: i32,
+    /// Where the creator is alerted when a version's circuit opens or closes
+    pub alert_url: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Spell {
+    pub const MIN_INPUT_BYTES: i32 = 1024;
+    pub const MAX_INPUT_BYTES: i32 = 100 * 1024 * 1024;
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry_max_attempts,
+            backoff_ms: self.retry_backoff_ms,
+        }
+    }
+
+    pub fn cache_policy(&self) -> CachePolicy {
+        CachePolicy {
+            ttl_secs: self.cache_ttl_secs,
+            discount_percent: self.cache_discount_percent,
+        }
+    }
+
+    /// Whether a session is charged once, on its first message, rather than per message
+    pub fn bills_per_session(&self) -> bool {
+        self.pricing_model == PRICING_PER_SESSION
+    }
+
+    /// Price a cast starting at `now` is charged, honouring any scheduled price change
+    pub fn effective_price_cents(&self, now: DateTime<Utc>) -> i32 {
+        match (self.next_price_cents, self.next_price_effective_at) {
+            (Some(next), Some(effective_at)) if effective_at <= now => next,
+            _ => self.price_cents,
+        }
+    }
+}
+
+/// Server-side retries for casts failing with a retryable error category
+/// `max_attempts` includes the first try; the delay doubles after each failed attempt.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: i32,
+    pub backoff_ms: i32,
+}
+
+impl RetryPolicy {
+    pub const MAX_ATTEMPTS: i32 = 10;
+    pub const MAX_BACKOFF_MS: i32 = 60_000;
+
+    /// Check the bounds enforced by the `valid_retry_policy` constraint
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=Self::MAX_ATTEMPTS).contains(&self.max_attempts) {
+            return Err(format!(
+                "retry_policy.max_attempts must be between 1 and {}",
+                Self::MAX_ATTEMPTS
+            ));
+        }
+        if !(0..=Self::MAX_BACKOFF_MS).contains(&self.backoff_ms) {
+            return Err(format!(
+                "retry_policy.backoff_ms must be between 0 and {}",
+                Self::MAX_BACKOFF_MS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Delay before retrying after `attempt` (1-based) failed
+    pub fn delay_after(&self, attempt: i32) -> std::time::Duration {
+        let exponent = (attempt - 1).clamp(0, 16) as u32;
+        let ms = i64::from(self.backoff_ms.max(0)).saturating_mul(1 << exponent);
+        std::time::Duration::from_millis(ms.min(i64::from(Self::MAX_BACKOFF_MS)) as u64)
+    }
+}
+
+/// How results of the spell's pure versions are cached and priced when reused
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct CachePolicy {
+    pub ttl_secs: i32,
+    pub discount_percent: i32,
+}
+
+impl CachePolicy {
+    pub const MAX_TTL_SECS: i32 = 30 * 24 * 60 * 60;
+
+    /// Check the bounds enforced by the `valid_cache_policy` constraint
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=Self::MAX_TTL_SECS).contains(&self.ttl_secs) {
+            return Err(format!(
+                "cache_policy.ttl_secs must be between 1 and {}",
+                Self::MAX_TTL_SECS
+            ));
+        }
+        if !(0..=100).contains(&self.discount_percent) {
+            return Err("cache_policy.discount_percent must be between 0 and 100".to_string());
+        }
+        Ok(())
+    }
+
+    /// Price of a cast served from the cache, rounded down
+    pub fn cached_price_cents(&self, price_cents: i32) -> i32 {
+        let discount = self.discount_percent.clamp(0, 100);
+        (i64::from(price_cents) * i64::from(100 - discount) / 100) as i32
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SpellVersion {
+    pub id: Uuid,
+    pub spell_id: Uuid,
+    pub version: String,
+    pub wasm_path: String,
+    pub manifest: serde_json::Value,
+    pub status: String,
+    pub status_reason: Option<String>,
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub sunset_at: Option<DateTime<Utc>>,
+    pub yanked_at: Option<DateTime<Utc>>,
+    pub digest: Option<String>,
+    pub size_bytes: Option<i64>,
+    /// Output depends only on input, so results may be cached
+    pub pure: bool,
+    /// Outcome of the latest health check, if the manifest declares examples
+    pub health_status: Option<String>,
+    pub health_checked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SpellVersion {
+    /// JSON Schema for cast payloads, if the manifest declares `input_schema`
+    pub fn input_schema(&self) -> Option<&serde_json::Value> {
+        self.manifest.get("input_schema").filter(|s| s.is_object())
+    }
+
+    /// Encoding the spell reads its payload in, from the manifest's `input_format`
+    pub fn input_format(&self) -> Codec {
+        manifest_format(&self.manifest, "input_format")
+    }
+
+    /// Encoding the spell writes its result in, from the manifest's `output_format`
+    pub fn output_format(&self) -> Codec {
+        manifest_format(&self.manifest, "output_format")
+    }
+
+    /// Health check examples declared in the manifest
+    pub fn examples(&self) -> Vec<SpellExample> {
+        manifest_examples(&self.manifest)
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.status == VERSION_DEPRECATED
+    }
+
+    /// Warning surfaced to casters of a deprecated or yanked (pinned) version
+    pub fn caster_warning(&self, spell_name: &str) -> Option<String> {
+        let reason = self
+            .status_reason
+            .as_deref()
+            .map(|r| format!(": {r}"))
+            .unwrap_or_default();
+
+        match self.status.as_str() {
+            VERSION_DEPRECATED => Some(match self.sunset_at {
+                Some(sunset) => format!(
+                    "{spell_name}@{} is deprecated and will be removed on {}{reason}",
+                    self.version,
+                    sunset.to_rfc3339()
+                ),
+                None => format!("{spell_name}@{} is deprecated{reason}", self.version),
+            }),
+            VERSION_YANKED => Some(format!(
+                "{spell_name}@{} has been yanked and is only available to pinned casts{reason}",
+                self.version
+            )),
+            _ => None,
+        }
+    }
+}
+
+pub const PRICING_PER_CALL: &str = "per_call";
+pub const PRICING_PER_SESSION: &str = "per_session";
+
+pub const VERSION_DEPRECATED: &str = "deprecated";
+pub const VERSION_YANKED: &str = "yanked";
+
+pub const HEALTH_PASSING: &str = "PASSING";
+pub const HEALTH_FAILING: &str = "FAILING";
+/// A check that couldn't run some examples for reasons outside the spell; says nothing
+/// about the version's health
+pub const HEALTH_ERRORED: &str = "ERRORED";
+pub const MAX_EXAMPLES: usize = 10;
+
+/// An example cast a spell's health is checked with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpellExample {
+    pub name: String,
+    pub payload: serde_json::Value,
+    /// Result the cast must produce; without it the cast only has to complete
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<serde_json::Value>,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct CreateSpellRequest {
@@ -33,6 +246,7 @@ pub struct SpellResponse {
     pub description: Option<String>,
     pub price_cents: i32,
     pub is_active: bool,
+    pub output_schema: Option<serde_json::Value>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -45,7 +259,446 @@ impl From<Spell> for SpellResponse {
             description: spell.description,
             price_cents: spell.price_cents,
             is_active: spell.is_active,
+            output_schema: spell.output_schema,
             created_at: spell.created_at,
         }
     }
 }
+
+/// Distinguishes an absent field (None) from an explicit null (Some(None))
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSpellRequest {
+    #[serde(default, deserialize_with = "double_option")]
+    pub description: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub category: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub output_schema: Option<Option<serde_json::Value>>,
+    pub retry_policy: Option<RetryPolicy>,
+    pub cache_policy: Option<CachePolicy>,
+    pub pricing_model: Option<String>,
+    pub max_input_bytes: Option<i32>,
+    /// `json`, `msgpack` or `cbor`; applies to versions published afterwards
+    pub input_format: Option<String>,
+    pub output_format: Option<String>,
+    /// https URL alerted of circuit breaker transitions; null stops alerts
+    #[serde(default, deserialize_with = "double_option")]
+    pub alert_url: Option<Option<String>>,
+    /// Health check examples; apply to versions published afterwards, empty removes them
+    pub examples: Option<Vec<SpellExample>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePriceRequest {
+    pub price_cents: i32,
+    pub effective_from: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeprecateVersionRequest {
+    pub reason: Option<String>,
+    pub sunset_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YankVersionRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetVersionPurityRequest {
+    pub pure: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpellVersionResponse {
+    pub id: Uuid,
+    pub version: String,
+    pub digest: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub pure: bool,
+    pub status: String,
+    pub status_reason: Option<String>,
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub sunset_at: Option<DateTime<Utc>>,
+    pub yanked_at: Option<DateTime<Utc>>,
+    pub health_status: Option<String>,
+    pub health_checked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<SpellVersion> for SpellVersionResponse {
+    fn from(version: SpellVersion) -> Self {
+        Self {
+            id: version.id,
+            version: version.version,
+            digest: version.digest,
+            size_bytes: version.size_bytes,
+            pure: version.pure,
+            status: version.status,
+            status_reason: version.status_reason,
+            deprecated_at: version.deprecated_at,
+            sunset_at: version.sunset_at,
+            yanked_at: version.yanked_at,
+            health_status: version.health_status,
+            health_checked_at: version.health_checked_at,
+            created_at: version.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeprecationFeedQuery {
+    pub days: Option<i32>,
+}
+
+/// A non-active version the caller has cast recently
+#[derive(Debug, Serialize, FromRow)]
+pub struct DeprecationNotice {
+    pub spell_name: String,
+    pub version: String,
+    pub status: String,
+    pub status_reason: Option<String>,
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub sunset_at: Option<DateTime<Utc>>,
+    pub yanked_at: Option<DateTime<Utc>>,
+    pub replacement_version: Option<String>,
+    pub recent_casts: i64,
+    pub last_cast_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatorSpellResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub price_cents: i32,
+    pub next_price_cents: Option<i32>,
+    pub next_price_effective_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub output_schema: Option<serde_json::Value>,
+    pub retry_policy: RetryPolicy,
+    pub cache_policy: CachePolicy,
+    pub pricing_model: String,
+    pub max_input_bytes: i32,
+    pub alert_url: Option<String>,
+    pub versions: Vec<SpellVersionResponse>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl CreatorSpellResponse {
+    pub fn new(spell: Spell, versions: Vec<SpellVersion>) -> Self {
+        let now = Utc::now();
+        let price_cents = spell.effective_price_cents(now);
+        let retry_policy = spell.retry_policy();
+        let cache_policy = spell.cache_policy();
+        // A scheduled change that already took effect is reported as the current price
+        let (next_price_cents, next_price_effective_at) = match spell.next_price_effective_at {
+            Some(at) if at > now => (spell.next_price_cents, Some(at)),
+            _ => (None, None),
+        };
+
+        Self {
+            id: spell.id,
+            tags: manifest_tags(&spell.manifest),
+            category: manifest_category(&spell.manifest),
+            name: spell.name,
+            description: spell.description,
+            price_cents,
+            next_price_cents,
+            next_price_effective_at,
+            is_active: spell.is_active,
+            retry_policy,
+            cache_policy,
+            pricing_model: spell.pricing_model,
+            max_input_bytes: spell.max_input_bytes,
+            alert_url: spell.alert_url,
+            output_schema: spell.output_schema,
+            versions: versions.into_iter().map(Into::into).collect(),
+            created_at: spell.created_at,
+            updated_at: spell.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpellStatsQuery {
+    pub days: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpellHealthStats {
+    pub spell_id: Uuid,
+    pub spell_name: String,
+    pub window_days: i32,
+    pub total_casts: i64,
+    pub completed: i64,
+    pub failed: i64,
+    pub contract_violations: i64,
+    pub last_contract_violation_at: Option<DateTime<Utc>>,
+}
+
+pub const CIRCUIT_CLOSED: &str = "CLOSED";
+pub const CIRCUIT_OPEN: &str = "OPEN";
+/// Cooled down after opening; a few probe casts decide whether it closes again
+pub const CIRCUIT_HALF_OPEN: &str = "HALF_OPEN";
+
+/// Live circuit breaker state of one version
+#[derive(Debug, Serialize)]
+pub struct CircuitStatus {
+    pub version: String,
+    /// CLOSED, OPEN or HALF_OPEN
+    pub state: &'static str,
+    /// While OPEN, seconds until probes are let through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+/// A recorded circuit transition
+#[derive(Debug, Serialize, FromRow)]
+pub struct SpellCircuitEvent {
+    pub version: String,
+    /// `opened` or `closed`
+    pub event: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpellCircuitReport {
+    pub spell_name: String,
+    pub versions: Vec<CircuitStatus>,
+    /// Most recent transitions first
+    pub events: Vec<SpellCircuitEvent>,
+}
+
+/// How one example fared in a health check
+#[derive(Debug, Serialize)]
+pub struct ExampleResult {
+    pub name: String,
+    pub passed: bool,
+    /// The example couldn't be run, e.g. the module failed to load; not a spell failure
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub errored: bool,
+    /// Differences from the expected result to the actual one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diff: Vec<DiffEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// A recorded health check of one version
+#[derive(Debug, Serialize, FromRow)]
+pub struct SpellHealthCheck {
+    pub version: String,
+    /// `publish` or `scheduled`
+    pub triggered_by: String,
+    pub status: String,
+    pub passed: i32,
+    pub failed: i32,
+    /// One ExampleResult per example
+    pub results: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpellHealthReport {
+    pub spell_name: String,
+    pub examples: Vec<SpellExample>,
+    /// Most recent checks first
+    pub checks: Vec<SpellHealthCheck>,
+}
+
+fn manifest_format(manifest: &serde_json::Value, key: &str) -> Codec {
+    manifest[key]
+        .as_str()
+        .and_then(Codec::from_name)
+        .unwrap_or_default()
+}
+
+/// Tags declared under `[metadata]` in a spell manifest
+pub fn manifest_tags(manifest: &serde_json::Value) -> Vec<String> {
+    manifest["metadata"]["tags"]
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|t| t.as_str().map(ToString::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Health check examples declared under `[[examples]]` in a spell manifest
+/// Malformed entries are skipped.
+pub fn manifest_examples(manifest: &serde_json::Value) -> Vec<SpellExample> {
+    manifest["examples"]
+        .as_array()
+        .map(|examples| {
+            examples
+                .iter()
+                .filter_map(|e| serde_json::from_value(e.clone()).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Category declared under `[metadata]` in a spell manifest
+pub fn manifest_category(manifest: &serde_json::Value) -> Option<String> {
+    manifest["metadata"]["category"]
+        .as_str()
+        .map(ToString::to_string)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogSort {
+    Newest,
+    Name,
+    PriceAsc,
+    PriceDesc,
+    Relevance,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogQuery {
+    pub q: Option<String>,
+    pub tag: Option<String>,
+    pub category: Option<String>,
+    pub sort: Option<CatalogSort>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Keyset position of the last spell on a catalog page
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CatalogCursor {
+    pub sort: CatalogSort,
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub name: String,
+    pub price_cents: i32,
+    pub rank: Option<f32>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct CatalogRow {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub price_cents: i32,
+    pub next_price_cents: Option<i32>,
+    pub next_price_effective_at: Option<DateTime<Utc>>,
+    pub manifest: serde_json::Value,
+    pub output_schema: Option<serde_json::Value>,
+    pub creator_login: String,
+    pub health_status: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub rank: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogSpell {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub price_cents: i32,
+    pub next_price_cents: Option<i32>,
+    pub next_price_effective_at: Option<DateTime<Utc>>,
+    pub creator: String,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    /// PASSING or FAILING health checks of the version casts resolve to, if it has examples
+    pub health_status: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&CatalogRow> for CatalogSpell {
+    fn from(row: &CatalogRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name.clone(),
+            description: row.description.clone(),
+            price_cents: row.price_cents,
+            next_price_cents: row.next_price_cents,
+            next_price_effective_at: row.next_price_effective_at,
+            creator: row.creator_login.clone(),
+            tags: manifest_tags(&row.manifest),
+            category: manifest_category(&row.manifest),
+            health_status: row.health_status.clone(),
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogPage {
+    pub spells: Vec<CatalogSpell>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogSpellDetail {
+    #[serde(flatten)]
+    pub spell: CatalogSpell,
+    pub output_schema: Option<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn retry_delay_doubles_and_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            backoff_ms: 200,
+        };
+        assert_eq!(policy.delay_after(1), Duration::from_millis(200));
+        assert_eq!(policy.delay_after(3), Duration::from_millis(800));
+
+        let slow = RetryPolicy {
+            max_attempts: 10,
+            backoff_ms: 60_000,
+        };
+        assert_eq!(slow.delay_after(9), Duration::from_millis(60_000));
+        assert!(RetryPolicy {
+            max_attempts: 0,
+            backoff_ms: 0
+        }
+        .validate()
+        .is_err());
+        assert!(policy.validate().is_ok());
+    }
+
+    #[test]
+    fn cached_price_applies_discount_rounding_down() {
+        let policy = CachePolicy {
+            ttl_secs: 60,
+            discount_percent: 25,
+        };
+        assert_eq!(policy.cached_price_cents(10), 7);
+        assert_eq!(policy.cached_price_cents(0), 0);
+        assert!(CachePolicy {
+            ttl_secs: 0,
+            discount_percent: 0
+        }
+        .validate()
+        .is_err());
+    }
+}
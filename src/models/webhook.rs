@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const DELIVERY_PENDING: &str = "pending";
+pub const DELIVERY_DELIVERED: &str = "delivered";
+pub const DELIVERY_FAILED: &str = "failed";
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookSecret {
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One webhook to deliver, as shown in the delivery log
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    /// None for creator alerts, which are not about one cast
+    pub cast_id: Option<Uuid>,
+    pub url: String,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDeliveryAttempt {
+    pub attempted_at: DateTime<Utc>,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDeliveryDetail {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub delivery: WebhookDelivery,
+    pub payload: serde_json::Value,
+    #[sqlx(skip)]
+    pub attempts_log: Vec<WebhookDeliveryAttempt>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryLogQuery {
+    pub cast_id: Option<Uuid>,
+    pub status: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Keyset position of the last delivery on a log page (newest first)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeliveryLogCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeliveryLogPage {
+    pub deliveries: Vec<WebhookDelivery>,
+    pub next_cursor: Option<String>,
+}
@@ -1,6 +1,7 @@
 use actix_web::{web, HttpRequest, HttpResponse};
 use std::env;
 
+use crate::errors::ApiError;
 use crate::services::billing_service::BillingService;
 use crate::AppState;
 
@@ -16,7 +17,7 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
 async fn process_monthly_billing(
     state: web::Data<AppState>,
     req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     // Check admin secret
     let admin_secret = env::var("ADMIN_SECRET").ok();
     let request_secret = req
@@ -29,8 +30,8 @@ async fn process_monthly_billing(
             // Authorized
         }
         _ => {
-            return Err(actix_web::error::ErrorUnauthorized(
-                "Invalid or missing admin secret",
+            return Err(ApiError::Unauthorized(
+                "Invalid or missing admin secret".to_string(),
             ));
         }
     }
@@ -38,13 +39,13 @@ async fn process_monthly_billing(
     let stripe = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     BillingService::process_monthly_billing(stripe, &state.db)
         .await
         .map_err(|e| {
             log::error!("Failed to process monthly billing: {e}");
-            actix_web::error::ErrorInternalServerError("Failed to process monthly billing")
+            ApiError::Internal("Failed to process monthly billing".to_string())
         })?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
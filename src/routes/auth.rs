@@ -1,8 +1,9 @@
+use crate::errors::ApiError;
 use crate::models::{GitHubAccessTokenResponse, GitHubUser, User};
 use crate::AppState;
 use actix_web::cookie::{Cookie, SameSite};
 use actix_web::http::header;
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use chrono::{Duration, Utc};
 use rand::Rng;
 use serde::Deserialize;
@@ -59,9 +60,8 @@ async fn github_callback(
         Ok(resp) => resp,
         Err(e) => {
             log::error!("Failed to exchange code for token: {e}");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to authenticate with GitHub"
-            }));
+            return ApiError::Internal("Failed to authenticate with GitHub".to_string())
+                .error_response();
         }
     };
 
@@ -69,9 +69,8 @@ async fn github_callback(
         Ok(data) => data,
         Err(e) => {
             log::error!("Failed to parse token response: {e}");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to parse GitHub response"
-            }));
+            return ApiError::Internal("Failed to parse GitHub response".to_string())
+                .error_response();
         }
     };
 
@@ -89,9 +88,8 @@ async fn github_callback(
         Ok(resp) => resp,
         Err(e) => {
             log::error!("Failed to get user info: {e}");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to get user info from GitHub"
-            }));
+            return ApiError::Internal("Failed to get user info from GitHub".to_string())
+                .error_response();
         }
     };
 
@@ -99,9 +97,7 @@ async fn github_callback(
         Ok(data) => data,
         Err(e) => {
             log::error!("Failed to parse user response: {e}");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to parse user data"
-            }));
+            return ApiError::Internal("Failed to parse user data".to_string()).error_response();
         }
     };
 
@@ -130,9 +126,7 @@ async fn github_callback(
         Ok(user) => user,
         Err(e) => {
             log::error!("Failed to upsert user: {e}");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to save user"
-            }));
+            return ApiError::Internal("Failed to save user".to_string()).error_response();
         }
     };
 
@@ -155,9 +149,7 @@ async fn github_callback(
         Ok(_) => {}
         Err(e) => {
             log::error!("Failed to create session: {e}");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to create session"
-            }));
+            return ApiError::Internal("Failed to create session".to_string()).error_response();
         }
     };
 
@@ -180,9 +172,8 @@ async fn get_session(req: HttpRequest, state: web::Data<AppState>) -> HttpRespon
     let session_token = match extract_session_token(&req) {
         Some(token) => token,
         None => {
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "No session token provided"
-            }));
+            return ApiError::Unauthorized("No session token provided".to_string())
+                .error_response();
         }
     };
 
@@ -200,9 +191,8 @@ async fn get_session(req: HttpRequest, state: web::Data<AppState>) -> HttpRespon
     {
         Ok(user) => user,
         Err(_) => {
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "Invalid or expired session"
-            }));
+            return ApiError::Unauthorized("Invalid or expired session".to_string())
+                .error_response();
         }
     };
 
@@ -223,9 +213,8 @@ async fn logout(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
     let session_token = match extract_session_token(&req) {
         Some(token) => token,
         None => {
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "No session token provided"
-            }));
+            return ApiError::Unauthorized("No session token provided".to_string())
+                .error_response();
         }
     };
 
@@ -356,9 +345,7 @@ async fn dev_login(state: web::Data<AppState>) -> HttpResponse {
         Ok(user) => user,
         Err(e) => {
             log::error!("Failed to create dev user: {e}");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to create test user"
-            }));
+            return ApiError::Internal("Failed to create test user".to_string()).error_response();
         }
     };
 
@@ -381,9 +368,7 @@ async fn dev_login(state: web::Data<AppState>) -> HttpResponse {
         Ok(_) => {}
         Err(e) => {
             log::error!("Failed to create dev session: {e}");
-            return HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "Failed to create session"
-            }));
+            return ApiError::Internal("Failed to create session".to_string()).error_response();
         }
     };
 
@@ -2,6 +2,7 @@ use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
 use actix_web_httpauth::middleware::HttpAuthentication;
 use serde::Deserialize;
 
+use crate::errors::ApiError;
 use crate::middleware::auth::authenticate_from_cookie;
 use crate::models::{billing::Budget, User};
 use crate::services::budget_service::BudgetService;
@@ -38,23 +39,23 @@ struct AttachPaymentMethodRequest {
 async fn create_checkout_session(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     // Get authenticated user
     let user_id = {
         let ext = http_req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     if !stripe_service.is_enabled() {
-        return Err(actix_web::error::ErrorServiceUnavailable(
-            "Billing features are currently disabled",
+        return Err(ApiError::BillingUnavailable(
+            "Billing features are currently disabled".to_string(),
         ));
     }
 
@@ -67,22 +68,20 @@ async fn create_checkout_session(
         .await
         .map_err(|e| {
             log::error!("Failed to create checkout session: {e}");
-            actix_web::error::ErrorInternalServerError("Failed to create checkout session")
+            ApiError::Internal("Failed to create checkout session".to_string())
         })?;
 
     Ok(HttpResponse::Ok().json(session))
 }
 
 /// Dev-only endpoint that bypasses authentication (controlled by DEV_MODE_USER_ID env var)
-async fn dev_create_setup_intent(
-    state: web::Data<AppState>,
-) -> Result<HttpResponse, actix_web::Error> {
+async fn dev_create_setup_intent(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
     let dev_user_id_str = std::env::var("DEV_MODE_USER_ID")
-        .map_err(|_| actix_web::error::ErrorServiceUnavailable("DEV_MODE_USER_ID not set"))?;
+        .map_err(|_| ApiError::Unavailable("DEV_MODE_USER_ID not set".to_string()))?;
 
-    let dev_user_id: uuid::Uuid = dev_user_id_str.parse().map_err(|_| {
-        actix_web::error::ErrorInternalServerError("Invalid DEV_MODE_USER_ID (must be UUID)")
-    })?;
+    let dev_user_id: uuid::Uuid = dev_user_id_str
+        .parse()
+        .map_err(|_| ApiError::Internal("Invalid DEV_MODE_USER_ID (must be UUID)".to_string()))?;
 
     log::warn!(
         "🚧 DEV MODE: Creating setup intent for user_id={}",
@@ -97,17 +96,17 @@ async fn dev_create_setup_intent(
     .await
     .map_err(|e| {
         log::error!("DEV MODE: Failed to fetch user {}: {}", dev_user_id, e);
-        actix_web::error::ErrorInternalServerError("Dev user not found")
+        ApiError::Internal("Dev user not found".to_string())
     })?;
 
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     if !stripe_service.is_enabled() {
-        return Err(actix_web::error::ErrorServiceUnavailable(
-            "Billing features are currently disabled",
+        return Err(ApiError::BillingUnavailable(
+            "Billing features are currently disabled".to_string(),
         ));
     }
 
@@ -118,7 +117,7 @@ async fn dev_create_setup_intent(
         .await
         .map_err(|e| {
             log::error!("Failed to get/create customer: {e:?}");
-            actix_web::error::ErrorInternalServerError("Failed to create customer")
+            ApiError::Internal("Failed to create customer".to_string())
         })?;
     log::info!("Successfully got/created customer: {customer_id}");
 
@@ -127,36 +126,27 @@ async fn dev_create_setup_intent(
         Ok(setup_intent) => Ok(HttpResponse::Ok().json(serde_json::json!({
             "client_secret": setup_intent.client_secret
         }))),
-        Err(failure) => {
-            let payload = serde_json::json!({
-                "error": "Failed to create setup intent",
-                "stripe_status": failure.status,
-                "stripe_code": failure.code,
-                "stripe_message": failure.message,
-                "stripe_request_id": failure.request_id,
-            });
-            Ok(HttpResponse::InternalServerError().json(payload))
-        }
+        Err(failure) => Err(ApiError::PaymentProvider(failure)),
     }
 }
 
 async fn create_setup_intent(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     // Authenticate user from cookie
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     if !stripe_service.is_enabled() {
-        return Err(actix_web::error::ErrorServiceUnavailable(
-            "Billing features are currently disabled",
+        return Err(ApiError::BillingUnavailable(
+            "Billing features are currently disabled".to_string(),
         ));
     }
 
@@ -168,7 +158,7 @@ async fn create_setup_intent(
         .map_err(|e| {
             log::error!("Failed to get/create customer: {e:?}");
             log::error!("Error chain: {:?}", e.chain().collect::<Vec<_>>());
-            actix_web::error::ErrorInternalServerError("Failed to create customer")
+            ApiError::Internal("Failed to create customer".to_string())
         })?;
     log::info!("Successfully got/created customer: {customer_id}");
 
@@ -177,16 +167,7 @@ async fn create_setup_intent(
         Ok(setup_intent) => Ok(HttpResponse::Ok().json(serde_json::json!({
             "client_secret": setup_intent.client_secret
         }))),
-        Err(failure) => {
-            let payload = serde_json::json!({
-                "error": "Failed to create setup intent",
-                "stripe_status": failure.status,
-                "stripe_code": failure.code,
-                "stripe_message": failure.message,
-                "stripe_request_id": failure.request_id,
-            });
-            Ok(HttpResponse::InternalServerError().json(payload))
-        }
+        Err(failure) => Err(ApiError::PaymentProvider(failure)),
     }
 }
 
@@ -194,20 +175,20 @@ async fn attach_payment_method(
     state: web::Data<AppState>,
     http_req: HttpRequest,
     req: web::Json<AttachPaymentMethodRequest>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     // Authenticate user from cookie
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     if !stripe_service.is_enabled() {
-        return Err(actix_web::error::ErrorServiceUnavailable(
-            "Billing features are currently disabled",
+        return Err(ApiError::BillingUnavailable(
+            "Billing features are currently disabled".to_string(),
         ));
     }
 
@@ -217,7 +198,7 @@ async fn attach_payment_method(
         .await
         .map_err(|e| {
             log::error!("Failed to get/create customer: {e}");
-            actix_web::error::ErrorInternalServerError("Failed to get customer")
+            ApiError::Internal("Failed to get customer".to_string())
         })?;
 
     stripe_service
@@ -225,7 +206,7 @@ async fn attach_payment_method(
         .await
         .map_err(|e| {
             log::error!("Failed to attach payment method: {e}");
-            actix_web::error::ErrorInternalServerError("Failed to attach payment method")
+            ApiError::Internal("Failed to attach payment method".to_string())
         })?;
 
     // Save payment method ID in database
@@ -245,7 +226,7 @@ async fn attach_payment_method(
     .await
     .map_err(|e| {
         log::error!("Failed to save payment method: {e}");
-        actix_web::error::ErrorInternalServerError("Failed to save payment method")
+        ApiError::Internal("Failed to save payment method".to_string())
     })?;
 
     // Set initial budget limit ($50)
@@ -263,7 +244,7 @@ async fn attach_payment_method(
     .await
     .map_err(|e| {
         log::error!("Failed to set budget: {e}");
-        actix_web::error::ErrorInternalServerError("Failed to set budget")
+        ApiError::Internal("Failed to set budget".to_string())
     })?;
 
     log::info!(
@@ -281,25 +262,25 @@ async fn stripe_webhook(
     state: web::Data<AppState>,
     req: HttpRequest,
     body: web::Bytes,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     // Get Stripe signature from header
     let signature = req
         .headers()
         .get("stripe-signature")
         .and_then(|v| v.to_str().ok())
-        .ok_or_else(|| actix_web::error::ErrorBadRequest("Missing stripe-signature header"))?;
+        .ok_or_else(|| ApiError::InvalidRequest("Missing stripe-signature header".to_string()))?;
 
     // Verify webhook signature
     let event = stripe_service
         .verify_webhook_signature(&body, signature)
         .map_err(|e| {
             log::error!("Webhook signature verification failed: {e}");
-            actix_web::error::ErrorUnauthorized("Invalid webhook signature")
+            ApiError::Unauthorized("Invalid webhook signature".to_string())
         })?;
 
     log::info!("Received Stripe webhook event: {:?}", event.type_);
@@ -310,7 +291,7 @@ async fn stripe_webhook(
         .await
         .map_err(|e| {
             log::error!("Failed to handle webhook event: {e}");
-            actix_web::error::ErrorInternalServerError("Failed to process webhook")
+            ApiError::Internal("Failed to process webhook".to_string())
         })?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
@@ -322,10 +303,10 @@ async fn stripe_webhook(
 async fn get_payment_method(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     // Get billing account with payment method
     let billing_account: Option<(String,)> = sqlx::query_as(
@@ -338,15 +319,15 @@ async fn get_payment_method(
     .await
     .map_err(|e| {
         log::error!("Failed to fetch billing account: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     let payment_method_id = match billing_account {
         Some((pm_id,)) => pm_id,
         None => {
-            return Ok(HttpResponse::NotFound().json(serde_json::json!({
-                "error": "No payment method configured"
-            })));
+            return Err(ApiError::PaymentMethodMissing(
+                "No payment method configured".to_string(),
+            ));
         }
     };
 
@@ -354,20 +335,21 @@ async fn get_payment_method(
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     let payment_method = stripe_service
         .get_payment_method(&payment_method_id)
         .await
         .map_err(|e| {
             log::error!("Failed to get payment method from Stripe: {e}");
-            actix_web::error::ErrorInternalServerError("Failed to get payment method")
+            ApiError::Internal("Failed to get payment method".to_string())
         })?;
 
     // Extract card details
-    let card = payment_method.card.as_ref().ok_or_else(|| {
-        actix_web::error::ErrorInternalServerError("Payment method is not a card")
-    })?;
+    let card = payment_method
+        .card
+        .as_ref()
+        .ok_or_else(|| ApiError::Internal("Payment method is not a card".to_string()))?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "brand": card.brand,
@@ -381,10 +363,10 @@ async fn get_payment_method(
 async fn delete_payment_method(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     // Get billing account
     let billing_account: Option<(String,)> = sqlx::query_as(
@@ -397,15 +379,15 @@ async fn delete_payment_method(
     .await
     .map_err(|e| {
         log::error!("Failed to fetch billing account: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     let payment_method_id = match billing_account {
         Some((pm_id,)) => pm_id,
         None => {
-            return Ok(HttpResponse::NotFound().json(serde_json::json!({
-                "error": "No payment method configured"
-            })));
+            return Err(ApiError::PaymentMethodMissing(
+                "No payment method configured".to_string(),
+            ));
         }
     };
 
@@ -413,14 +395,14 @@ async fn delete_payment_method(
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     stripe_service
         .detach_payment_method(&payment_method_id)
         .await
         .map_err(|e| {
             log::error!("Failed to detach payment method from Stripe: {e}");
-            actix_web::error::ErrorInternalServerError("Failed to delete payment method")
+            ApiError::Internal("Failed to delete payment method".to_string())
         })?;
 
     // Remove from database
@@ -436,7 +418,7 @@ async fn delete_payment_method(
     .await
     .map_err(|e| {
         log::error!("Failed to remove payment method from database: {e}");
-        actix_web::error::ErrorInternalServerError("Failed to delete payment method")
+        ApiError::Internal("Failed to delete payment method".to_string())
     })?;
 
     log::info!("Payment method deleted for user {}", user.github_login);
@@ -451,10 +433,10 @@ async fn delete_payment_method(
 async fn get_usage_cookie(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     // Get budget to determine period
     let budget: Option<Budget> = sqlx::query_as(
@@ -467,7 +449,7 @@ async fn get_usage_cookie(
     .await
     .map_err(|e| {
         log::error!("Failed to fetch budget: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     let period = "monthly";
@@ -475,7 +457,7 @@ async fn get_usage_cookie(
         .await
         .map_err(|e| {
             log::error!("Failed to get usage: {e}");
-            actix_web::error::ErrorInternalServerError("Database error")
+            ApiError::Internal("Database error".to_string())
         })?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
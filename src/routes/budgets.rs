@@ -1,6 +1,7 @@
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
 use actix_web_httpauth::middleware::HttpAuthentication;
 
+use crate::errors::ApiError;
 use crate::middleware::auth::authenticate_from_cookie;
 use crate::models::billing::{Budget, BudgetResponse, CreateBudgetRequest};
 use crate::models::User;
@@ -33,11 +34,11 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
 async fn get_budget(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user_id = {
         let ext = http_req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
@@ -51,7 +52,7 @@ async fn get_budget(
     .await
     .map_err(|e| {
         log::error!("Failed to fetch budget: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     match budget {
@@ -69,9 +70,7 @@ async fn get_budget(
                 updated_at: b.updated_at,
             }))
         }
-        None => Ok(HttpResponse::NotFound().json(serde_json::json!({
-            "error": "No budget configured"
-        }))),
+        None => Err(ApiError::NotFound("No budget configured".to_string())),
     }
 }
 
@@ -79,11 +78,11 @@ async fn create_budget(
     state: web::Data<AppState>,
     http_req: HttpRequest,
     req: web::Json<CreateBudgetRequest>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user_id = {
         let ext = http_req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
@@ -94,7 +93,7 @@ async fn create_budget(
     // Validate limits
     if let Some(hard) = req.hard_limit_cents {
         if !(MIN_BUDGET_CENTS..=MAX_BUDGET_CENTS).contains(&hard) {
-            return Err(actix_web::error::ErrorBadRequest(format!(
+            return Err(ApiError::InvalidRequest(format!(
                 "Hard limit must be between ${} and ${}",
                 MIN_BUDGET_CENTS / 100,
                 MAX_BUDGET_CENTS / 100
@@ -104,7 +103,7 @@ async fn create_budget(
 
     if let Some(soft) = req.soft_limit_cents {
         if !(MIN_BUDGET_CENTS..=MAX_BUDGET_CENTS).contains(&soft) {
-            return Err(actix_web::error::ErrorBadRequest(format!(
+            return Err(ApiError::InvalidRequest(format!(
                 "Soft limit must be between ${} and ${}",
                 MIN_BUDGET_CENTS / 100,
                 MAX_BUDGET_CENTS / 100
@@ -114,8 +113,8 @@ async fn create_budget(
 
     if let (Some(soft), Some(hard)) = (req.soft_limit_cents, req.hard_limit_cents) {
         if soft > hard {
-            return Err(actix_web::error::ErrorBadRequest(
-                "Soft limit cannot exceed hard limit",
+            return Err(ApiError::InvalidRequest(
+                "Soft limit cannot exceed hard limit".to_string(),
             ));
         }
     }
@@ -142,7 +141,7 @@ async fn create_budget(
     .await
     .map_err(|e| {
         log::error!("Failed to create budget: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     let thresholds: Vec<i32> =
@@ -163,7 +162,7 @@ async fn update_budget(
     state: web::Data<AppState>,
     http_req: HttpRequest,
     req: web::Json<CreateBudgetRequest>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     // Same as create_budget (uses ON CONFLICT DO UPDATE)
     create_budget(state, http_req, req).await
 }
@@ -171,11 +170,11 @@ async fn update_budget(
 async fn delete_budget(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user_id = {
         let ext = http_req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
@@ -189,7 +188,7 @@ async fn delete_budget(
     .await
     .map_err(|e| {
         log::error!("Failed to delete budget: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     Ok(HttpResponse::NoContent().finish())
@@ -198,11 +197,11 @@ async fn delete_budget(
 async fn get_usage(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user_id = {
         let ext = http_req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
@@ -217,7 +216,7 @@ async fn get_usage(
     .await
     .map_err(|e| {
         log::error!("Failed to fetch budget: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     let period = budget
@@ -229,7 +228,7 @@ async fn get_usage(
         .await
         .map_err(|e| {
             log::error!("Failed to get usage: {e}");
-            actix_web::error::ErrorInternalServerError("Database error")
+            ApiError::Internal("Database error".to_string())
         })?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
@@ -247,10 +246,10 @@ async fn get_usage(
 async fn get_budget_cookie(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let budget: Option<Budget> = sqlx::query_as(
         r#"
@@ -262,7 +261,7 @@ async fn get_budget_cookie(
     .await
     .map_err(|e| {
         log::error!("Failed to fetch budget: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     match budget {
@@ -280,9 +279,7 @@ async fn get_budget_cookie(
                 updated_at: b.updated_at,
             }))
         }
-        None => Ok(HttpResponse::NotFound().json(serde_json::json!({
-            "error": "No budget configured"
-        }))),
+        None => Err(ApiError::NotFound("No budget configured".to_string())),
     }
 }
 
@@ -290,10 +287,10 @@ async fn update_budget_cookie(
     state: web::Data<AppState>,
     http_req: HttpRequest,
     req: web::Json<CreateBudgetRequest>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let period = req.period.as_deref().unwrap_or("monthly");
     let thresholds_json = serde_json::to_value(req.notify_thresholds.clone().unwrap_or_default())
@@ -302,7 +299,7 @@ async fn update_budget_cookie(
     // Validate limits
     if let Some(hard) = req.hard_limit_cents {
         if !(MIN_BUDGET_CENTS..=MAX_BUDGET_CENTS).contains(&hard) {
-            return Err(actix_web::error::ErrorBadRequest(format!(
+            return Err(ApiError::InvalidRequest(format!(
                 "Hard limit must be between ${} and ${}",
                 MIN_BUDGET_CENTS / 100,
                 MAX_BUDGET_CENTS / 100
@@ -312,7 +309,7 @@ async fn update_budget_cookie(
 
     if let Some(soft) = req.soft_limit_cents {
         if !(MIN_BUDGET_CENTS..=MAX_BUDGET_CENTS).contains(&soft) {
-            return Err(actix_web::error::ErrorBadRequest(format!(
+            return Err(ApiError::InvalidRequest(format!(
                 "Soft limit must be between ${} and ${}",
                 MIN_BUDGET_CENTS / 100,
                 MAX_BUDGET_CENTS / 100
@@ -322,8 +319,8 @@ async fn update_budget_cookie(
 
     if let (Some(soft), Some(hard)) = (req.soft_limit_cents, req.hard_limit_cents) {
         if soft > hard {
-            return Err(actix_web::error::ErrorBadRequest(
-                "Soft limit cannot exceed hard limit",
+            return Err(ApiError::InvalidRequest(
+                "Soft limit cannot exceed hard limit".to_string(),
             ));
         }
     }
@@ -349,7 +346,7 @@ async fn update_budget_cookie(
     .await
     .map_err(|e| {
         log::error!("Failed to update budget: {e}");
-        actix_web::error::ErrorInternalServerError("Database error")
+        ApiError::Internal("Database error".to_string())
     })?;
 
     let thresholds: Vec<i32> =
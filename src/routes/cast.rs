@@ -1,132 +1,907 @@
-use crate::errors::CastError;
-use crate::models::{CastRequest, CastResponse, Spell, User};
+use crate::errors::{ApiError, CastError, ErrorCategory};
+use crate::middleware::request_id;
+use crate::models::billing::BudgetExceededError;
+use crate::models::spell::{SpellVersion, CIRCUIT_OPEN};
+use crate::models::{
+    ApiKey, BatchCastRequest, BatchCastResponse, BatchItemResult, BudgetEstimate, CastEstimate,
+    CastRejection, CastRequest, CastResponse, InputFile, Spell, User,
+};
 use crate::services::budget_service::BudgetService;
+use crate::services::cast_events::{CastEvent, EventSink};
+use crate::services::cast_service::{CastOptions, CastService, PreparedCast};
+use crate::services::circuit_breaker::CircuitBreaker;
+use crate::services::idempotency_service::{IdempotencyService, IdempotencyStart, StoredResponse};
+use crate::services::spell_service::SpellService;
+use crate::services::webhook_service::WebhookService;
+use crate::storage::{digest_of, put_blob};
+use crate::utils::canonical::canonical_hash;
+use crate::utils::codec::{Codec, Encoded};
+use crate::wasm::InputFileData;
 use crate::AppState;
-use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::guard::{self, GuardContext};
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
 use actix_web_httpauth::middleware::HttpAuthentication;
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
 use uuid::Uuid;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     let auth = HttpAuthentication::bearer(crate::middleware::auth::validator);
     cfg.service(
         web::resource("/cast")
-            .wrap(auth)
+            .app_data(web::PayloadConfig::new(Spell::MAX_INPUT_BYTES as usize))
+            .wrap(auth.clone())
+            .route(
+                web::post()
+                    .guard(guard::fn_guard(|ctx| body_kind(ctx) == BodyKind::Multipart))
+                    .to(cast_multipart),
+            )
+            .route(
+                web::post()
+                    .guard(guard::fn_guard(|ctx| body_kind(ctx) == BodyKind::Binary))
+                    .to(cast_binary),
+            )
             .route(web::post().to(cast_spell)),
+    )
+    .service(
+        web::resource("/cast/dry-run")
+            .wrap(auth.clone())
+            .route(web::post().to(dry_run)),
+    )
+    .service(
+        web::resource("/cast/stream")
+            .wrap(auth.clone())
+            .route(web::post().to(cast_stream)),
+    )
+    .service(
+        web::resource("/cast/batch")
+            .app_data(web::JsonConfig::default().limit(MAX_BATCH_BODY_BYTES))
+            .app_data(web::PayloadConfig::new(MAX_BATCH_BODY_BYTES))
+            .wrap(auth)
+            .route(web::post().to(cast_batch)),
     );
 }
 
+const MAX_BATCH_ITEMS: usize = 1000;
+const DEFAULT_BATCH_PARALLELISM: usize = 8;
+const MAX_BATCH_PARALLELISM: usize = 32;
+const MAX_BATCH_BODY_BYTES: usize = 10 * 1024 * 1024;
+// Content type recorded for files sent without one
+const DEFAULT_FILE_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// How the body of a `POST /cast` is encoded
+#[derive(Debug, PartialEq)]
+enum BodyKind {
+    /// A `CastRequest` in JSON, MessagePack or CBOR; also assumed without a content type
+    Request,
+    /// A `request` part with the `CastRequest` plus one part per file
+    Multipart,
+    /// Any other content type: the body is the single input file
+    Binary,
+}
+
+fn body_kind(ctx: &GuardContext) -> BodyKind {
+    let Some(content_type) = ctx
+        .head()
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return BodyKind::Request;
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if Codec::from_media_type(&essence).is_some() {
+        BodyKind::Request
+    } else if essence == "multipart/form-data" {
+        BodyKind::Multipart
+    } else {
+        BodyKind::Binary
+    }
+}
+
+/// Cast settings for a raw binary body, which has no room for a `CastRequest`
+#[derive(Debug, Deserialize)]
+struct BinaryCastQuery {
+    spell_name: String,
+    version: Option<String>,
+    #[serde(default, rename = "async")]
+    run_async: bool,
+    callback_url: Option<String>,
+    #[serde(default)]
+    bypass_cache: bool,
+    /// Name the spell sees for the file; defaults to "input"
+    filename: Option<String>,
+}
+
+/// Whether the caller asked for an asynchronous cast, via the body or `Prefer: respond-async`
+fn wants_async(http_req: &HttpRequest, req: &CastRequest) -> bool {
+    req.run_async
+        || http_req
+            .headers()
+            .get("Prefer")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| {
+                v.split(',')
+                    .any(|p| p.trim().eq_ignore_ascii_case("respond-async"))
+            })
+}
+
+/// Whether the caller asked to skip the result cache, via the body or `Cache-Control`
+fn bypasses_cache(http_req: &HttpRequest, bypass_cache: bool) -> bool {
+    bypass_cache
+        || http_req
+            .headers()
+            .get("Cache-Control")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| {
+                v.split(',').any(|d| {
+                    let d = d.trim();
+                    d.eq_ignore_ascii_case("no-cache") || d.eq_ignore_ascii_case("no-store")
+                })
+            })
+}
+
+/// Webhook target for the cast: the request's callback_url, else the API key's default
+fn resolve_callback_url(
+    http_req: &HttpRequest,
+    req: &CastRequest,
+) -> Result<Option<String>, CastError> {
+    match &req.callback_url {
+        Some(url) => {
+            WebhookService::validate_callback_url(url).map_err(CastError::InvalidInput)?;
+            Ok(Some(url.clone()))
+        }
+        None => Ok(http_req
+            .extensions()
+            .get::<ApiKey>()
+            .and_then(|key| key.default_callback_url.clone())),
+    }
+}
+
 async fn cast_spell(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-    req: web::Json<CastRequest>,
-) -> Result<HttpResponse, CastError> {
+    req: Encoded<CastRequest>,
+) -> Result<HttpResponse, ApiError> {
+    cast_with_files(state, http_req, req.into_inner(), Vec::new()).await
+}
+
+/// Cast with files: a `request` part holding the `CastRequest` JSON, and any number of
+/// parts with a filename, each passed to the spell as an input file
+async fn cast_multipart(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    mut multipart: Multipart,
+) -> Result<HttpResponse, ApiError> {
+    let mut req: Option<CastRequest> = None;
+    let mut uploads = Vec::new();
+    let mut total_bytes = 0;
+
+    while let Some(field) = multipart.next().await {
+        let mut field = field.map_err(invalid_multipart)?;
+        let part = field.name().unwrap_or_default().to_string();
+        let filename = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename())
+            .map(str::to_string);
+        let content_type = field
+            .content_type()
+            .map_or(DEFAULT_FILE_CONTENT_TYPE.to_string(), |mime| {
+                mime.to_string()
+            });
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(invalid_multipart)?;
+            total_bytes += chunk.len();
+            // The spell's own (lower) limit is checked once the request part is known
+            if total_bytes > Spell::MAX_INPUT_BYTES as usize {
+                return Err(CastError::InputTooLarge {
+                    size_bytes: total_bytes,
+                    limit_bytes: Spell::MAX_INPUT_BYTES,
+                }
+                .into());
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        match filename {
+            Some(name) => uploads.push(InputFileData {
+                name,
+                content_type,
+                bytes,
+            }),
+            None if part == "request" => {
+                req =
+                    Some(serde_json::from_slice(&bytes).map_err(|e| {
+                        ApiError::InvalidRequest(format!("Invalid request part: {e}"))
+                    })?);
+            }
+            None => {
+                return Err(ApiError::InvalidRequest(format!(
+                    "Unexpected form field '{part}'; file parts need a filename"
+                )))
+            }
+        }
+    }
+
+    let req = req.ok_or_else(|| {
+        ApiError::InvalidRequest("Multipart casts need a 'request' part".to_string())
+    })?;
+    let files = store_input_files(&state, &req, uploads).await?;
+    cast_with_files(state, http_req, req, files).await
+}
+
+/// Cast with the raw body as the only input file; cast settings come from the query string
+async fn cast_binary(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<BinaryCastQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    let content_type = http_req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(DEFAULT_FILE_CONTENT_TYPE)
+        .to_string();
+
+    let req = CastRequest {
+        spell_name: query.spell_name,
+        version: query.version,
+        payload: serde_json::Value::Null,
+        run_async: query.run_async,
+        callback_url: query.callback_url,
+        bypass_cache: query.bypass_cache,
+    };
+    let upload = InputFileData {
+        name: query.filename.unwrap_or_else(|| "input".to_string()),
+        content_type,
+        bytes: body.to_vec(),
+    };
+    let files = store_input_files(&state, &req, vec![upload]).await?;
+    cast_with_files(state, http_req, req, files).await
+}
+
+fn invalid_multipart(e: MultipartError) -> ApiError {
+    ApiError::InvalidRequest(format!("Invalid multipart body: {e}"))
+}
+
+/// Check uploads against the spell's input limit, then write them to the blob store
+/// Nothing is stored for casts that would be rejected as too large.
+async fn store_input_files(
+    state: &web::Data<AppState>,
+    req: &CastRequest,
+    uploads: Vec<InputFileData>,
+) -> Result<Vec<InputFile>, ApiError> {
+    let files: Vec<InputFile> = uploads
+        .iter()
+        .map(|upload| InputFile {
+            name: upload.name.clone(),
+            content_type: upload.content_type.clone(),
+            size_bytes: upload.bytes.len() as i64,
+            digest: digest_of(&upload.bytes),
+        })
+        .collect();
+
+    let (spell, _) =
+        CastService::resolve(&req.spell_name, req.version.as_deref(), &state.db).await?;
+    CastService::check_input_size(&spell, &req.payload, &files)?;
+
+    for upload in &uploads {
+        put_blob(state.storage.as_ref(), &upload.bytes)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to store input file {}: {e}", upload.name);
+                ApiError::Unavailable("Failed to store input file".to_string())
+            })?;
+    }
+    Ok(files)
+}
+
+/// Shared by every `POST /cast` body encoding once files are stored
+/// The cast runs in a detached task: once recorded it must finish, and with an
+/// Idempotency-Key have its response stored, even if the client disconnects.
+async fn cast_with_files(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: CastRequest,
+    files: Vec<InputFile>,
+) -> Result<HttpResponse, ApiError> {
     // Get authenticated user
     let user_id = {
         let ext = http_req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| CastError::WasmExecutionFailed("User not authenticated".to_string()))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
-    // Check budget hard limit BEFORE execution
-    if let Err(budget_err) = BudgetService::check_hard_limit(&user_id, &state.db).await {
-        log::warn!("Budget exceeded for user {user_id}: {budget_err:?}");
-        return Err(CastError::BudgetExceeded(budget_err));
+    let callback_url = resolve_callback_url(&http_req, &req)?;
+    let mode = CastMode::of(&http_req, &req);
+
+    let idempotency = match http_req.headers().get("Idempotency-Key") {
+        None => None,
+        Some(key) => {
+            let key = key.to_str().map_err(|_| {
+                CastError::InvalidInput("Idempotency-Key must be ASCII".to_string())
+            })?;
+            IdempotencyService::validate_key(key)?;
+
+            // Retries must match the original request exactly, including sync/async mode
+            let fingerprint = canonical_hash(&serde_json::json!({
+                "spell_name": req.spell_name,
+                "version": req.version,
+                "payload": req.payload,
+                "async": mode.run_async,
+                "callback_url": callback_url,
+                "bypass_cache": mode.bypass_cache,
+                "files": files,
+                "format": mode.codec.name(),
+            }));
+
+            match IdempotencyService::start(&state.redis, &user_id, key, &fingerprint).await? {
+                IdempotencyStart::Replay(stored) => {
+                    log::info!("Replaying response for Idempotency-Key {key} (user {user_id})");
+                    return Ok(stored.to_response(true));
+                }
+                IdempotencyStart::Proceed => Some((key.to_string(), fingerprint)),
+            }
+        }
+    };
+
+    let run = tokio::spawn(request_id::scope(request_id::current(), async move {
+        let outcome = match prepare_cast(&state, &req, files, user_id, callback_url, mode).await {
+            Ok(cast) => perform_cast(&state, &req, mode, cast).await,
+            // No cast was recorded, so nothing ran or was billed; let the client retry with the same key
+            Err(e) if matches!(e.category(), ErrorCategory::NetworkRetryable) => {
+                if let Some((key, _)) = &idempotency {
+                    if let Err(release_err) =
+                        IdempotencyService::release(&state.redis, &user_id, key).await
+                    {
+                        log::error!("Failed to release Idempotency-Key {key}: {release_err}");
+                    }
+                }
+                return Err(e);
+            }
+            Err(e) => Err(e),
+        };
+
+        // Once the cast is recorded, any outcome is final: it may have run and been billed
+        if let Some((key, fingerprint)) = &idempotency {
+            let stored = store_outcome(mode.codec, &outcome);
+            if let Err(e) =
+                IdempotencyService::complete(&state.redis, &user_id, key, fingerprint, stored).await
+            {
+                log::error!("Failed to store response for Idempotency-Key {key}: {e}");
+            }
+        }
+        outcome
+    }));
+    let outcome = run
+        .await
+        .unwrap_or_else(|e| Err(CastError::InternalError(format!("Cast task failed: {e}"))))?;
+
+    Ok(outcome.respond(mode.codec))
+}
+
+/// How the caller asked for a cast to run, read off the request before it's detached
+#[derive(Debug, Clone, Copy)]
+struct CastMode {
+    run_async: bool,
+    bypass_cache: bool,
+    codec: Codec,
+}
+
+impl CastMode {
+    fn of(http_req: &HttpRequest, req: &CastRequest) -> Self {
+        CastMode {
+            run_async: wants_async(http_req, req),
+            bypass_cache: bypasses_cache(http_req, req.bypass_cache),
+            codec: Codec::negotiate(http_req),
+        }
+    }
+}
+
+/// A queued or finished cast, before it becomes a response
+/// Unlike `HttpResponse` this can leave the task the cast runs in.
+struct CastOutcome {
+    response: CastResponse,
+    version: SpellVersion,
+}
+
+impl CastOutcome {
+    fn respond(&self, codec: Codec) -> HttpResponse {
+        let mut builder = if self.response.status == "QUEUED" {
+            let mut builder = HttpResponse::Accepted();
+            builder.insert_header(("Location", format!("/v1/casts/{}", self.response.id)));
+            builder
+        } else {
+            HttpResponse::Ok()
+        };
+        SpellService::add_deprecation_headers(&mut builder, &self.version);
+        codec.respond(&mut builder, &self.response)
     }
+}
 
-    let cast_id = Uuid::new_v4();
+/// The response to replay for an Idempotency-Key, errors included
+fn store_outcome(codec: Codec, outcome: &Result<CastOutcome, CastError>) -> StoredResponse {
+    let response = match outcome {
+        Ok(outcome) => outcome.respond(codec),
+        Err(e) => e.error_response(),
+    };
+    StoredResponse::capture(response).0
+}
+
+/// Validate the cast and record it; on error no casts row exists
+async fn prepare_cast(
+    state: &web::Data<AppState>,
+    req: &CastRequest,
+    input_files: Vec<InputFile>,
+    user_id: Uuid,
+    callback_url: Option<String>,
+    mode: CastMode,
+) -> Result<PreparedCast, CastError> {
+    let options = CastOptions {
+        callback_url,
+        bypass_cache: mode.bypass_cache,
+        input_files,
+        run_async: mode.run_async,
+        ..Default::default()
+    };
+    CastService::prepare(&user_id, req, options, &state.db).await
+}
+
+/// Queue or run a recorded cast
+async fn perform_cast(
+    state: &web::Data<AppState>,
+    req: &CastRequest,
+    mode: CastMode,
+    cast: PreparedCast,
+) -> Result<CastOutcome, CastError> {
+    let user_id = cast.user_id;
+    let cast_id = cast.id;
     let spell_name = &req.spell_name;
-    let payload = &req.payload;
 
     log::info!("Cast {cast_id} starting for spell: {spell_name} by user {user_id}");
 
-    // Fetch spell to get price
-    let spell: Option<Spell> = sqlx::query_as(
-        r#"
-        SELECT * FROM spells WHERE name = $1 AND is_active = true
-        "#,
-    )
-    .bind(spell_name)
-    .fetch_optional(&state.db)
-    .await?;
-
-    let spell = spell.ok_or_else(|| {
-        CastError::WasmExecutionFailed(format!("Spell '{}' not found or inactive", spell_name))
-    })?;
+    let warning = cast.version.caster_warning(spell_name);
+    if let Some(warning) = &warning {
+        log::warn!("Cast {cast_id}: {warning}");
+    }
 
-    let cost_cents = spell.price_cents;
+    if mode.run_async {
+        CastService::enqueue(state, &cast_id).await?;
 
-    // Insert initial record with user_id and spell_id
-    sqlx::query(
-        r#"
-        INSERT INTO casts (id, spell_name, payload, status, user_id, spell_id, created_at)
-        VALUES ($1, $2, $3, 'QUEUED', $4, $5, NOW())
-        "#,
-    )
-    .bind(cast_id)
-    .bind(spell_name)
-    .bind(payload)
-    .bind(user_id)
-    .bind(spell.id)
-    .execute(&state.db)
-    .await?;
-
-    // Execute WASM
-    let result = match state.wasm.execute_spell(spell_name, payload.clone()) {
-        Ok(output) => {
-            // Update with success
-            sqlx::query(
-                r#"
-                UPDATE casts
-                SET status = 'COMPLETED', result = $2
-                WHERE id = $1
-                "#,
-            )
-            .bind(cast_id)
-            .bind(&output)
-            .execute(&state.db)
-            .await?;
-
-            log::info!("Cast {cast_id} completed successfully");
-
-            // Record usage and cost based on spell price
-            if cost_cents > 0 {
-                if let Err(e) =
-                    BudgetService::record_usage(&user_id, cost_cents, &cast_id, &state.db).await
-                {
-                    log::error!("Failed to record usage for cast {cast_id}: {e}");
-                    // Continue anyway - don't fail the cast
-                }
-            }
+        log::info!("Cast {cast_id} queued");
 
-            CastResponse {
+        return Ok(CastOutcome {
+            response: CastResponse {
                 id: cast_id,
-                status: "COMPLETED".to_string(),
-                result: Some(output),
+                status: "QUEUED".to_string(),
+                result: None,
                 error_code: None,
+                spell_version: Some(cast.version.version.clone()),
+                warning,
+                attempts: 0,
+                cached: false,
                 created_at: chrono::Utc::now(),
+            },
+            version: cast.version,
+        });
+    }
+
+    CastService::mark_running(&cast, &state.db).await?;
+    let execution = CastService::execute(state, &cast).await?;
+
+    Ok(CastOutcome {
+        response: CastResponse {
+            id: cast_id,
+            status: "COMPLETED".to_string(),
+            result: Some(execution.output),
+            error_code: None,
+            spell_version: Some(cast.version.version.clone()),
+            warning,
+            attempts: execution.attempts,
+            cached: execution.cached,
+            created_at: chrono::Utc::now(),
+        },
+        version: cast.version,
+    })
+}
+
+/// Run a cast synchronously, streaming its progress as Server-Sent Events
+/// Emits `state` transitions, the spell's `log` lines and `output` chunks, then a final
+/// `result` (with cost) or `error` problem. Errors found before the cast is recorded are
+/// returned as a normal problem response instead of a stream.
+async fn cast_stream(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: Encoded<CastRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = {
+        let ext = http_req.extensions();
+        ext.get::<User>()
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+            .id
+    };
+
+    let options = CastOptions {
+        callback_url: resolve_callback_url(&http_req, &req)?,
+        bypass_cache: bypasses_cache(&http_req, req.bypass_cache),
+        ..Default::default()
+    };
+    let cast = CastService::prepare(&user_id, &req, options, &state.db).await?;
+    let cast_id = cast.id;
+
+    log::info!(
+        "Cast {cast_id} streaming for spell: {} by user {user_id}",
+        req.spell_name
+    );
+
+    let (events, rx) = EventSink::channel();
+    events.emit(CastEvent::State {
+        cast_id,
+        status: "QUEUED",
+        attempt: 0,
+        retry_in_ms: None,
+    });
+
+    let mut response = HttpResponse::Ok();
+    SpellService::add_deprecation_headers(&mut response, &cast.version);
+
+    // The cast runs to completion (and is billed) even if the caller disconnects
+    let worker_state = state.clone();
+    tokio::spawn(request_id::scope(request_id::current(), async move {
+        let result = match CastService::mark_running(&cast, &worker_state.db).await {
+            Ok(_) => CastService::execute_with_events(&worker_state, &cast, &events).await,
+            Err(e) => {
+                events.emit(CastEvent::Error(e.problem()));
+                Err(e)
             }
+        };
+        if let Err(e) = result {
+            log::warn!("Streamed cast {} ended with {}", cast.id, e.error_code());
         }
-        Err(e) => {
-            // Update with error
-            let error_code = e.error_code();
-            sqlx::query(
-                r#"
-                UPDATE casts
-                SET status = 'FAILED', error_code = $2
-                WHERE id = $1
-                "#,
-            )
-            .bind(cast_id)
-            .bind(error_code)
-            .execute(&state.db)
-            .await?;
+    }));
+
+    let body = stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        Some((
+            Ok::<_, actix_web::Error>(web::Bytes::from(event.to_sse())),
+            rx,
+        ))
+    });
+
+    Ok(response
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(body))
+}
+
+/// Quote a cast without running or billing it
+/// Resolves the version and applies a real cast's payload, circuit and budget checks.
+/// Input files aren't sent, so only the payload counts toward the size limit.
+async fn dry_run(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: Encoded<CastRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = {
+        let ext = http_req.extensions();
+        ext.get::<User>()
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+            .id
+    };
+
+    let (spell, version) =
+        CastService::resolve(&req.spell_name, req.version.as_deref(), &state.db).await?;
+
+    let price_cents = spell.effective_price_cents(chrono::Utc::now());
+    let cached_price_cents = (version.pure && version.digest.is_some())
+        .then(|| spell.cache_policy().cached_price_cents(price_cents));
+
+    let mut rejection = CastService::check_payload(&spell, &version, &req.payload, &[])
+        .err()
+        .map(|e| CastRejection {
+            error_code: e.error_code().to_string(),
+            error: e.to_string(),
+        });
+
+    // Read-only: admitting here would use up a half-open circuit's probes
+    let circuit = CircuitBreaker::status(&state.redis, &version).await;
+    if circuit.state == CIRCUIT_OPEN {
+        let e = CastError::SpellUnavailable {
+            spell_name: spell.name.clone(),
+            retry_after_secs: circuit.retry_after_secs.unwrap_or(0),
+        };
+        rejection.get_or_insert(CastRejection {
+            error_code: e.error_code().to_string(),
+            error: e.to_string(),
+        });
+    }
+
+    // A failed lookup is an error, not an estimate of an exceeded budget
+    let status = BudgetService::hard_limit_status(&user_id, &state.db)
+        .await
+        .map_err(CastError::from)?;
+
+    if let Some(status) = status
+        .as_ref()
+        .filter(|s| s.spent_cents >= s.hard_limit_cents)
+    {
+        let e = CastError::BudgetExceeded(BudgetExceededError::new(
+            status.period.clone(),
+            status.hard_limit_cents,
+            status.spent_cents,
+        ));
+        rejection.get_or_insert(CastRejection {
+            error_code: e.error_code().to_string(),
+            error: e.to_string(),
+        });
+    }
+
+    let budget = status.map(|status| BudgetEstimate {
+        remaining_cents_after: status.hard_limit_cents - status.spent_cents - price_cents,
+        period: status.period,
+        hard_limit_cents: status.hard_limit_cents,
+        spent_cents: status.spent_cents,
+    });
+
+    Ok(Codec::negotiate(&http_req).respond(
+        &mut HttpResponse::Ok(),
+        &CastEstimate {
+            spell_name: spell.name.clone(),
+            spell_version: version.version.clone(),
+            price_cents,
+            cached_price_cents,
+            budget,
+            would_be_rejected: rejection.is_some(),
+            rejection,
+            warning: version.caster_warning(&spell.name),
+        },
+    ))
+}
 
-            log::error!("Cast {cast_id} failed: {e}");
+/// Cast one spell over many payloads
+/// The whole batch is reserved against the budget up front, items run with bounded
+/// parallelism, and each item is recorded (and billed only on success) as its own cast.
+async fn cast_batch(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: Encoded<BatchCastRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = {
+        let ext = http_req.extensions();
+        ext.get::<User>()
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+            .id
+    };
 
-            return Err(e);
+    let count = req.payloads.len();
+    if count == 0 || count > MAX_BATCH_ITEMS {
+        return Err(ApiError::from(CastError::InvalidInput(format!(
+            "payloads must contain 1-{MAX_BATCH_ITEMS} items"
+        ))));
+    }
+    let parallelism = req
+        .max_parallelism
+        .unwrap_or(DEFAULT_BATCH_PARALLELISM)
+        .clamp(1, MAX_BATCH_PARALLELISM);
+
+    let (spell, version) =
+        CastService::resolve(&req.spell_name, req.version.as_deref(), &state.db).await?;
+
+    let price = spell.effective_price_cents(chrono::Utc::now());
+    let reserve_cents = i32::try_from(i64::from(price) * count as i64)
+        .map_err(|_| CastError::InvalidInput("Batch cost exceeds the maximum".to_string()))?;
+
+    let reservation = BudgetService::reserve(&user_id, reserve_cents, &state.db).await?;
+
+    let batch_id = Uuid::new_v4();
+    let bypass_cache = bypasses_cache(&http_req, req.bypass_cache);
+    let warning = version.caster_warning(&spell.name);
+
+    log::info!(
+        "Batch {batch_id} starting: {count} casts of {}@{} by user {user_id} (parallelism {parallelism})",
+        spell.name,
+        version.version
+    );
+
+    // Detached, so a disconnect can't leave item casts RUNNING or the reservation held
+    let codec = Codec::negotiate(&http_req);
+    let payloads = req.into_inner().payloads;
+    let batch_version = version.clone();
+    let run = tokio::spawn(request_id::scope(request_id::current(), async move {
+        let items: Vec<BatchItemResult> = stream::iter(payloads.into_iter().enumerate())
+            .map(|(index, payload)| {
+                let (state, reservation) = (&state, &reservation);
+                let (spell, version) = (&spell, &batch_version);
+                async move {
+                    let cast = CastService::record(
+                        &user_id,
+                        spell,
+                        version,
+                        &payload,
+                        CastOptions {
+                            batch_id: Some(batch_id),
+                            bypass_cache,
+                            ..Default::default()
+                        },
+                        &state.db,
+                    )
+                    .await;
+                    let item = match cast {
+                        Ok(cast) => run_batch_item(state, index, &cast).await,
+                        Err(e) => BatchItemResult {
+                            index,
+                            cast_id: None,
+                            status: "FAILED".to_string(),
+                            result: None,
+                            error_code: Some(e.error_code().to_string()),
+                            error: Some(e.to_string()),
+                            attempts: None,
+                            cached: false,
+                            cost_cents: 0,
+                        },
+                    };
+                    if let Some(reservation_id) = reservation {
+                        if let Err(e) =
+                            BudgetService::draw_down(reservation_id, price, &state.db).await
+                        {
+                            log::error!("Failed to draw down reservation {reservation_id}: {e}");
+                        }
+                    }
+                    item
+                }
+            })
+            .buffered(parallelism)
+            .collect()
+            .await;
+
+        if let Some(reservation_id) = reservation {
+            if let Err(e) = BudgetService::release(&reservation_id, &state.db).await {
+                log::error!("Failed to release budget reservation {reservation_id}: {e}");
+            }
         }
+        items
+    }));
+    let items = run
+        .await
+        .map_err(|e| CastError::InternalError(format!("Batch task failed: {e}")))?;
+
+    let succeeded = items.iter().filter(|i| i.status == "COMPLETED").count();
+    let total_cost_cents = items.iter().map(|i| i64::from(i.cost_cents)).sum();
+
+    log::info!(
+        "Batch {batch_id} finished: {succeeded}/{count} succeeded, {total_cost_cents} cents"
+    );
+
+    let mut response = HttpResponse::Ok();
+    SpellService::add_deprecation_headers(&mut response, &version);
+
+    Ok(codec.respond(
+        &mut response,
+        &BatchCastResponse {
+            batch_id,
+            spell_version: version.version.clone(),
+            warning,
+            succeeded,
+            failed: count - succeeded,
+            total_cost_cents,
+            items,
+        },
+    ))
+}
+
+async fn run_batch_item(
+    state: &web::Data<AppState>,
+    index: usize,
+    cast: &PreparedCast,
+) -> BatchItemResult {
+    let outcome = match CastService::mark_running(cast, &state.db).await {
+        Ok(_) => CastService::execute(state, cast).await,
+        Err(e) => Err(e),
     };
 
-    Ok(HttpResponse::Ok().json(result))
+    match outcome {
+        Ok(execution) => BatchItemResult {
+            index,
+            cast_id: Some(cast.id),
+            status: "COMPLETED".to_string(),
+            result: Some(execution.output),
+            error_code: None,
+            error: None,
+            attempts: Some(execution.attempts),
+            cached: execution.cached,
+            cost_cents: execution.cost_cents,
+        },
+        Err(e) => BatchItemResult {
+            index,
+            cast_id: Some(cast.id),
+            status: match e {
+                CastError::Canceled => "CANCELED",
+                _ => "FAILED",
+            }
+            .to_string(),
+            result: None,
+            error_code: Some(e.error_code().to_string()),
+            error: Some(e.to_string()),
+            attempts: None,
+            cached: false,
+            cost_cents: 0,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn request(run_async: bool) -> CastRequest {
+        CastRequest {
+            spell_name: "resize".to_string(),
+            version: None,
+            payload: serde_json::json!({}),
+            run_async,
+            callback_url: None,
+            bypass_cache: false,
+        }
+    }
+
+    #[test]
+    fn async_mode_from_body_or_prefer_header() {
+        let plain = TestRequest::default().to_http_request();
+        assert!(!wants_async(&plain, &request(false)));
+        assert!(wants_async(&plain, &request(true)));
+
+        let prefer = TestRequest::default()
+            .insert_header(("Prefer", "return=minimal, Respond-Async"))
+            .to_http_request();
+        assert!(wants_async(&prefer, &request(false)));
+    }
+
+    #[test]
+    fn cache_bypass_from_body_or_cache_control() {
+        let plain = TestRequest::default().to_http_request();
+        assert!(!bypasses_cache(&plain, false));
+        assert!(bypasses_cache(&plain, true));
+
+        let no_cache = TestRequest::default()
+            .insert_header(("Cache-Control", "max-age=0, No-Cache"))
+            .to_http_request();
+        assert!(bypasses_cache(&no_cache, false));
+    }
+
+    #[test]
+    fn body_kind_follows_content_type() {
+        let kind = |content_type: Option<&str>| {
+            let mut req = TestRequest::post();
+            if let Some(content_type) = content_type {
+                req = req.insert_header((CONTENT_TYPE, content_type));
+            }
+            body_kind(&req.to_srv_request().guard_ctx())
+        };
+        assert_eq!(kind(None), BodyKind::Request);
+        assert_eq!(
+            kind(Some("application/json; charset=utf-8")),
+            BodyKind::Request
+        );
+        assert_eq!(kind(Some("application/vnd.spell+json")), BodyKind::Request);
+        assert_eq!(
+            kind(Some("multipart/form-data; boundary=xyz")),
+            BodyKind::Multipart
+        );
+        assert_eq!(kind(Some("application/msgpack")), BodyKind::Request);
+        assert_eq!(kind(Some("image/png")), BodyKind::Binary);
+    }
 }
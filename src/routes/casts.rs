@@ -0,0 +1,321 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+use crate::models::{
+    Cast, CastHistoryCursor, CastHistoryPage, CastHistoryQuery, CastHistoryTotals, CastSummary,
+    User,
+};
+use crate::services::cast_queue::CastQueue;
+use crate::services::webhook_service::WebhookService;
+use crate::utils::codec::Codec;
+use crate::utils::cursor::{decode_cursor, encode_cursor};
+use crate::AppState;
+
+const CAST_STATUSES: &[&str] = &["QUEUED", "RUNNING", "COMPLETED", "FAILED", "CANCELED"];
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(crate::middleware::auth::validator);
+    cfg.service(
+        web::scope("/casts")
+            .wrap(auth)
+            .route("", web::get().to(list_casts))
+            .route("/{id}", web::get().to(get_cast))
+            .route("/{id}/cancel", web::post().to(cancel_cast)),
+    );
+}
+
+fn authenticated_user_id(http_req: &HttpRequest) -> Result<Uuid, ApiError> {
+    let ext = http_req.extensions();
+    Ok(ext
+        .get::<User>()
+        .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+        .id)
+}
+
+/// Append the caller's history filters (shared by the page and the totals query)
+fn push_filters<'a>(
+    qb: &mut QueryBuilder<'a, Postgres>,
+    user_id: Uuid,
+    query: &'a CastHistoryQuery,
+    status: Option<String>,
+) {
+    qb.push(" WHERE c.user_id = ").push_bind(user_id);
+
+    if let Some(spell) = &query.spell {
+        qb.push(" AND c.spell_name = ").push_bind(spell);
+    }
+    if let Some(status) = status {
+        qb.push(" AND c.status = ").push_bind(status);
+    }
+    if let Some(error_code) = &query.error_code {
+        qb.push(" AND c.error_code = ").push_bind(error_code);
+    }
+    if let Some(request_id) = &query.request_id {
+        qb.push(" AND c.request_id = ").push_bind(request_id);
+    }
+    if let Some(schedule_id) = query.schedule_id {
+        qb.push(" AND c.schedule_id = ").push_bind(schedule_id);
+    }
+    if let Some(from) = query.from {
+        qb.push(" AND c.created_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.to {
+        qb.push(" AND c.created_at < ").push_bind(to);
+    }
+}
+
+/// The caller's casts, newest first, with totals across all matching casts
+async fn list_casts(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<CastHistoryQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+
+    let status = match query.status.as_deref() {
+        Some(raw) => {
+            let status = raw.to_ascii_uppercase();
+            if !CAST_STATUSES.contains(&status.as_str()) {
+                return Err(ApiError::InvalidRequest(format!(
+                    "status must be one of {}",
+                    CAST_STATUSES.join(", ")
+                )));
+            }
+            Some(status)
+        }
+        None => None,
+    };
+
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from >= to {
+            return Err(ApiError::InvalidRequest(
+                "from must be before to".to_string(),
+            ));
+        }
+    }
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => Some(
+            decode_cursor::<CastHistoryCursor>(raw)
+                .ok_or_else(|| ApiError::InvalidRequest("Invalid cursor".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT c.id, c.spell_name, v.version AS spell_version, c.status, c.error_code, \
+         c.cost_cents, c.created_at, c.finished_at \
+         FROM casts c LEFT JOIN spell_versions v ON v.id = c.spell_version_id",
+    );
+    push_filters(&mut qb, user_id, &query, status.clone());
+
+    if let Some(c) = &cursor {
+        qb.push(" AND (c.created_at, c.id) < (")
+            .push_bind(c.created_at)
+            .push(", ")
+            .push_bind(c.id)
+            .push(")");
+    }
+
+    // Fetch one extra row to know whether another page exists
+    qb.push(" ORDER BY c.created_at DESC, c.id DESC LIMIT ")
+        .push_bind(limit + 1);
+
+    let mut casts: Vec<CastSummary> =
+        qb.build_query_as()
+            .fetch_all(&state.db)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to query cast history: {e}");
+                ApiError::Internal("Database error".to_string())
+            })?;
+
+    let mut totals_qb: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT COUNT(*) AS count, COALESCE(SUM(c.cost_cents), 0)::BIGINT AS cost_cents \
+         FROM casts c",
+    );
+    push_filters(&mut totals_qb, user_id, &query, status);
+
+    let totals: CastHistoryTotals = totals_qb
+        .build_query_as()
+        .fetch_one(&state.db)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to total cast history: {e}");
+            ApiError::Internal("Database error".to_string())
+        })?;
+
+    let next_cursor = if casts.len() as i64 > limit {
+        casts.truncate(limit as usize);
+        casts.last().map(|last| {
+            encode_cursor(&CastHistoryCursor {
+                created_at: last.created_at,
+                id: last.id,
+            })
+        })
+    } else {
+        None
+    };
+
+    Ok(Codec::negotiate(&http_req).respond(
+        &mut HttpResponse::Ok(),
+        &CastHistoryPage {
+            casts,
+            totals,
+            next_cursor,
+        },
+    ))
+}
+
+fn db_error(e: sqlx::Error) -> ApiError {
+    log::error!("Cast query failed: {e}");
+    ApiError::Internal("Database error".to_string())
+}
+
+async fn fetch_cast(
+    state: &AppState,
+    cast_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<Cast>, ApiError> {
+    sqlx::query_as(
+        r#"
+        SELECT c.*, v.version AS spell_version
+        FROM casts c
+        LEFT JOIN spell_versions v ON v.id = c.spell_version_id
+        WHERE c.id = $1 AND c.user_id = $2
+        "#,
+    )
+    .bind(cast_id)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)
+}
+
+/// A single cast with payload and result
+/// Also used to poll asynchronous casts: QUEUED, RUNNING, then COMPLETED, FAILED or CANCELED.
+async fn get_cast(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+
+    let mut cast = fetch_cast(&state, path.into_inner(), user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Cast not found".to_string()))?;
+
+    cast.attempt_log = sqlx::query_as(
+        r#"
+        SELECT attempt, status, error_code, error_message, started_at, finished_at
+        FROM cast_attempts
+        WHERE cast_id = $1
+        ORDER BY attempt
+        "#,
+    )
+    .bind(cast.id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    Ok(Codec::negotiate(&http_req).respond(&mut HttpResponse::Ok(), &cast))
+}
+
+/// Cancel a queued or running cast
+/// Queued casts are dropped before they run; running casts are interrupted at the next
+/// epoch tick. Canceled casts are never charged. A cast that already finished can't be
+/// canceled (409) and is billed as usual.
+async fn cancel_cast(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let cast_id = path.into_inner();
+
+    // started_at is only set once a worker claims the cast
+    let canceled: Option<(Option<chrono::DateTime<chrono::Utc>>, Option<String>)> = sqlx::query_as(
+        r#"
+        UPDATE casts
+        SET status = 'CANCELED', error_code = 'CAST_CANCELED',
+            error_message = 'Canceled by caller', finished_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND status IN ('QUEUED', 'RUNNING')
+        RETURNING started_at, callback_url
+        "#,
+    )
+    .bind(cast_id)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    let Some((started_at, callback_url)) = canceled else {
+        return match fetch_cast(&state, cast_id, user_id).await? {
+            Some(cast) => Err(ApiError::Conflict(format!(
+                "Cast is already {} and can't be canceled",
+                cast.status
+            ))),
+            None => Err(ApiError::NotFound("Cast not found".to_string())),
+        };
+    };
+
+    if started_at.is_none() {
+        if let Err(e) = CastQueue::remove(&state.redis, &cast_id).await {
+            log::warn!("Failed to drop canceled cast {cast_id} from the queue: {e}");
+        }
+        // Running casts notify their callback when the executor stops; queued ones never will
+        if callback_url.is_some() {
+            if let Err(e) = WebhookService::enqueue_for_cast(&cast_id, &state.db).await {
+                log::error!("Failed to queue webhook for cast {cast_id}: {e}");
+            }
+        }
+    }
+
+    log::info!("Cast {cast_id} canceled by user {user_id}");
+
+    let cast = fetch_cast(&state, cast_id, user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Cast not found".to_string()))?;
+
+    Ok(Codec::negotiate(&http_req).respond(&mut HttpResponse::Ok(), &cast))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_only_include_requested_conditions() {
+        let query = CastHistoryQuery {
+            spell: Some("resize".to_string()),
+            status: None,
+            error_code: None,
+            request_id: None,
+            schedule_id: None,
+            from: None,
+            to: Some(chrono::Utc::now()),
+            cursor: None,
+            limit: None,
+        };
+
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT 1 FROM casts c");
+        push_filters(&mut qb, Uuid::new_v4(), &query, Some("FAILED".to_string()));
+
+        assert_eq!(
+            qb.sql(),
+            "SELECT 1 FROM casts c WHERE c.user_id = $1 AND c.spell_name = $2 \
+             AND c.status = $3 AND c.created_at < $4"
+        );
+    }
+}
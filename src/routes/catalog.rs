@@ -0,0 +1,232 @@
+use actix_web::{web, HttpResponse};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::errors::ApiError;
+use crate::models::spell::{
+    CatalogCursor, CatalogPage, CatalogQuery, CatalogRow, CatalogSort, CatalogSpell,
+    CatalogSpellDetail,
+};
+use crate::utils::cursor::{decode_cursor, encode_cursor};
+use crate::AppState;
+
+// Price a cast would be charged right now, and a still-pending scheduled price
+const EFFECTIVE_PRICE_SQL: &str = "(CASE WHEN s.next_price_effective_at <= NOW() \
+     THEN s.next_price_cents ELSE s.price_cents END)";
+const PENDING_PRICE_SQL: &str = "CASE WHEN s.next_price_effective_at > NOW() \
+     THEN s.next_price_cents END AS next_price_cents, \
+     CASE WHEN s.next_price_effective_at > NOW() \
+     THEN s.next_price_effective_at END AS next_price_effective_at";
+// Health of the version unpinned casts resolve to
+const HEALTH_SQL: &str = "(SELECT v.health_status FROM spell_versions v \
+     WHERE v.spell_id = s.id AND v.status <> 'yanked' \
+     AND (v.sunset_at IS NULL OR v.sunset_at > NOW()) \
+     ORDER BY (v.status = 'active') DESC, v.created_at DESC LIMIT 1) AS health_status";
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/spells").route(web::get().to(list_spells)))
+        .service(web::resource("/spells/{name}").route(web::get().to(get_spell)));
+}
+
+/// Public catalog of active spells
+/// Supports full-text search (q), tag/category filters, sorting and cursor pagination
+async fn list_spells(
+    state: web::Data<AppState>,
+    query: web::Query<CatalogQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let search = query
+        .q
+        .as_deref()
+        .map(str::trim)
+        .filter(|q| !q.is_empty());
+
+    let sort = match (query.sort, search) {
+        (Some(CatalogSort::Relevance), None) => {
+            return Err(ApiError::InvalidRequest(
+                "sort=relevance requires a search query (q)".to_string(),
+            ));
+        }
+        (Some(sort), _) => sort,
+        (None, Some(_)) => CatalogSort::Relevance,
+        (None, None) => CatalogSort::Newest,
+    };
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => {
+            let cursor: CatalogCursor = decode_cursor(raw)
+                .ok_or_else(|| ApiError::InvalidRequest("Invalid cursor".to_string()))?;
+            if cursor.sort != sort {
+                return Err(ApiError::InvalidRequest(
+                    "Cursor was issued for a different sort order".to_string(),
+                ));
+            }
+            Some(cursor)
+        }
+        None => None,
+    };
+
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("SELECT s.id, s.name, s.description, ");
+    qb.push(EFFECTIVE_PRICE_SQL)
+        .push(" AS price_cents, ")
+        .push(PENDING_PRICE_SQL)
+        .push(", s.manifest, s.output_schema, u.github_login AS creator_login, ")
+        .push(HEALTH_SQL)
+        .push(", s.created_at, ");
+
+    match search {
+        Some(q) => {
+            qb.push("ts_rank(s.search_vector, websearch_to_tsquery('english', ")
+                .push_bind(q)
+                .push(")) AS rank");
+        }
+        None => {
+            qb.push("NULL::real AS rank");
+        }
+    }
+
+    qb.push(" FROM spells s INNER JOIN users u ON u.id = s.creator_id WHERE s.is_active = true");
+
+    if let Some(q) = search {
+        qb.push(" AND s.search_vector @@ websearch_to_tsquery('english', ")
+            .push_bind(q)
+            .push(")");
+    }
+
+    if let Some(tag) = &query.tag {
+        qb.push(" AND s.manifest->'metadata'->'tags' ? ")
+            .push_bind(tag);
+    }
+
+    if let Some(category) = &query.category {
+        qb.push(" AND s.manifest->'metadata'->>'category' = ")
+            .push_bind(category);
+    }
+
+    if let Some(c) = &cursor {
+        match sort {
+            CatalogSort::Newest => {
+                qb.push(" AND (s.created_at, s.id) < (")
+                    .push_bind(c.created_at)
+                    .push(", ")
+                    .push_bind(c.id)
+                    .push(")");
+            }
+            CatalogSort::Name => {
+                qb.push(" AND (s.name, s.id) > (")
+                    .push_bind(&c.name)
+                    .push(", ")
+                    .push_bind(c.id)
+                    .push(")");
+            }
+            CatalogSort::PriceAsc => {
+                qb.push(" AND (")
+                    .push(EFFECTIVE_PRICE_SQL)
+                    .push(", s.id) > (")
+                    .push_bind(c.price_cents)
+                    .push(", ")
+                    .push_bind(c.id)
+                    .push(")");
+            }
+            CatalogSort::PriceDesc => {
+                qb.push(" AND (")
+                    .push(EFFECTIVE_PRICE_SQL)
+                    .push(", s.id) < (")
+                    .push_bind(c.price_cents)
+                    .push(", ")
+                    .push_bind(c.id)
+                    .push(")");
+            }
+            CatalogSort::Relevance => {
+                // search is guaranteed to be present for relevance ordering
+                qb.push(" AND (ts_rank(s.search_vector, websearch_to_tsquery('english', ")
+                    .push_bind(search.unwrap_or_default())
+                    .push(")), s.id) < (")
+                    .push_bind(c.rank.unwrap_or(f32::MAX))
+                    .push(", ")
+                    .push_bind(c.id)
+                    .push(")");
+            }
+        }
+    }
+
+    qb.push(match sort {
+        CatalogSort::Newest => " ORDER BY s.created_at DESC, s.id DESC",
+        CatalogSort::Name => " ORDER BY s.name ASC, s.id ASC",
+        CatalogSort::PriceAsc => " ORDER BY price_cents ASC, s.id ASC",
+        CatalogSort::PriceDesc => " ORDER BY price_cents DESC, s.id DESC",
+        CatalogSort::Relevance => " ORDER BY rank DESC, s.id DESC",
+    });
+
+    // Fetch one extra row to know whether another page exists
+    qb.push(" LIMIT ").push_bind(limit + 1);
+
+    let mut rows: Vec<CatalogRow> = qb
+        .build_query_as()
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to query spell catalog: {e}");
+            ApiError::Internal("Database error".to_string())
+        })?;
+
+    let next_cursor = if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        rows.last().map(|last| {
+            encode_cursor(&CatalogCursor {
+                sort,
+                id: last.id,
+                created_at: last.created_at,
+                name: last.name.clone(),
+                price_cents: last.price_cents,
+                rank: last.rank,
+            })
+        })
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(CatalogPage {
+        spells: rows.iter().map(CatalogSpell::from).collect(),
+        next_cursor,
+    }))
+}
+
+/// Public detail view of a single active spell
+async fn get_spell(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let spell_name = path.into_inner();
+
+    let sql = format!(
+        "SELECT s.id, s.name, s.description, {EFFECTIVE_PRICE_SQL} AS price_cents, \
+         {PENDING_PRICE_SQL}, s.manifest, s.output_schema, \
+         u.github_login AS creator_login, {HEALTH_SQL}, s.created_at, NULL::real AS rank \
+         FROM spells s \
+         INNER JOIN users u ON u.id = s.creator_id \
+         WHERE s.name = $1 AND s.is_active = true"
+    );
+
+    let row: Option<CatalogRow> = sqlx::query_as(&sql)
+        .bind(&spell_name)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch spell: {e}");
+            ApiError::Internal("Database error".to_string())
+        })?;
+
+    let row = row.ok_or_else(|| ApiError::SpellNotFound(spell_name))?;
+
+    Ok(HttpResponse::Ok().json(CatalogSpellDetail {
+        spell: CatalogSpell::from(&row),
+        output_schema: row.output_schema,
+    }))
+}
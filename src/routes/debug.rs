@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use stripe::{Account, Client, Customer, CustomerId};
 use uuid::Uuid;
 
+use crate::errors::ApiError;
 use crate::middleware::auth::authenticate_from_cookie;
 use crate::services::stripe_service::SetupIntentFailure;
 use crate::AppState;
@@ -60,23 +61,23 @@ async fn get_self(
     state: web::Data<AppState>,
     req: HttpRequest,
     query: web::Query<StripeSelfQuery>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let _user = authenticate_from_cookie(&req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     let client = stripe_service
         .clone_client()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Stripe client unavailable"))?;
+        .ok_or_else(|| ApiError::Unavailable("Stripe client unavailable".to_string()))?;
 
     let account = retrieve_current_account(&client).await.map_err(|err| {
         log::error!("stripe_self_account_failed source={err}");
-        actix_web::error::ErrorInternalServerError("Failed to fetch Stripe account details")
+        ApiError::Internal("Failed to fetch Stripe account details".to_string())
     })?;
 
     let mut customer_in_db = None;
@@ -106,30 +107,30 @@ async fn get_doctor(
     state: web::Data<AppState>,
     req: HttpRequest,
     query: web::Query<StripeDoctorQuery>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let auth_user = authenticate_from_cookie(&req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let target_user = query.user_id.unwrap_or(auth_user.id);
     if target_user != auth_user.id {
-        return Err(actix_web::error::ErrorForbidden(
-            "Cannot inspect other user's billing state",
+        return Err(ApiError::Forbidden(
+            "Cannot inspect other user's billing state".to_string(),
         ));
     }
 
     let stripe_service = state
         .stripe
         .as_ref()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Billing not configured"))?;
+        .ok_or_else(|| ApiError::BillingUnavailable("Billing not configured".to_string()))?;
 
     let client = stripe_service
         .clone_client()
-        .ok_or_else(|| actix_web::error::ErrorServiceUnavailable("Stripe client unavailable"))?;
+        .ok_or_else(|| ApiError::Unavailable("Stripe client unavailable".to_string()))?;
 
     let account = retrieve_current_account(&client).await.map_err(|err| {
         log::error!("stripe_doctor_account_failed source={err}");
-        actix_web::error::ErrorInternalServerError("Failed to fetch Stripe account details")
+        ApiError::Internal("Failed to fetch Stripe account details".to_string())
     })?;
 
     let before_customer = fetch_customer_id(&state, target_user).await?;
@@ -139,7 +140,7 @@ async fn get_doctor(
         .await
         .map_err(|err| {
             log::error!("stripe_doctor_customer_sync_failed user_id={target_user} source={err:?}");
-            actix_web::error::ErrorInternalServerError("Failed to synchronize Stripe customer")
+            ApiError::Internal("Failed to synchronize Stripe customer".to_string())
         })?;
 
     let customer_verified = check_customer_exists(&client, &current_customer_id).await;
@@ -184,7 +185,7 @@ async fn get_doctor(
 async fn fetch_customer_id(
     state: &web::Data<AppState>,
     user_id: Uuid,
-) -> Result<Option<String>, actix_web::Error> {
+) -> Result<Option<String>, ApiError> {
     let record: Option<(String,)> = sqlx::query_as(
         r#"
         SELECT stripe_customer_id
@@ -197,7 +198,7 @@ async fn fetch_customer_id(
     .await
     .map_err(|err| {
         log::error!("stripe_debug_fetch_customer_failed user_id={user_id} source={err:?}");
-        actix_web::error::ErrorInternalServerError("Failed to fetch billing account")
+        ApiError::Internal("Failed to fetch billing account".to_string())
     })?;
 
     Ok(record.map(|(cid,)| cid))
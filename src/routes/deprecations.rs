@@ -0,0 +1,81 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use chrono::{Duration, Utc};
+
+use crate::errors::ApiError;
+use crate::models::spell::{DeprecationFeedQuery, DeprecationNotice};
+use crate::models::User;
+use crate::AppState;
+
+// The feed covers versions cast within the last 30 days by default, at most 90
+const DEFAULT_FEED_DAYS: i32 = 30;
+const MAX_FEED_DAYS: i32 = 90;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(crate::middleware::auth::validator);
+
+    cfg.service(
+        web::resource("/deprecations")
+            .wrap(auth)
+            .route(web::get().to(get_deprecation_feed)),
+    );
+}
+
+/// Deprecated or yanked spell versions the caller has cast recently
+async fn get_deprecation_feed(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<DeprecationFeedQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = {
+        let ext = http_req.extensions();
+        ext.get::<User>()
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+            .id
+    };
+
+    let days = query
+        .days
+        .unwrap_or(DEFAULT_FEED_DAYS)
+        .clamp(1, MAX_FEED_DAYS);
+    let since = Utc::now() - Duration::days(days as i64);
+
+    let notices: Vec<DeprecationNotice> = sqlx::query_as(
+        r#"
+        SELECT
+            s.name AS spell_name,
+            v.version,
+            v.status,
+            v.status_reason,
+            v.deprecated_at,
+            v.sunset_at,
+            v.yanked_at,
+            (
+                SELECT r.version FROM spell_versions r
+                WHERE r.spell_id = v.spell_id AND r.status = 'active'
+                ORDER BY r.created_at DESC
+                LIMIT 1
+            ) AS replacement_version,
+            COUNT(c.id) AS recent_casts,
+            MAX(c.created_at) AS last_cast_at
+        FROM casts c
+        INNER JOIN spell_versions v ON v.id = c.spell_version_id
+        INNER JOIN spells s ON s.id = v.spell_id
+        WHERE c.user_id = $1
+          AND c.created_at >= $2
+          AND v.status <> 'active'
+        GROUP BY s.name, v.id
+        ORDER BY MAX(c.created_at) DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(since)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to build deprecation feed: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    Ok(HttpResponse::Ok().json(notices))
+}
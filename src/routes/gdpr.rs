@@ -6,6 +6,7 @@ use serde::Serialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::errors::ApiError;
 use crate::models::User;
 
 // ============================================================================
@@ -27,12 +28,15 @@ use crate::models::User;
 /// - GDPR Article 17 (Right to Erasure)
 /// - CCPA §1798.105 (Right to Delete)
 /// - Japanese APPI Article 30 (Erasure)
-pub async fn delete_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Result<HttpResponse> {
+pub async fn delete_user_data(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
     // Get authenticated user from request extensions (set by auth middleware)
     let user_id = {
         let ext = req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
@@ -40,13 +44,10 @@ pub async fn delete_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Resu
     let result = sqlx::query!("DELETE FROM users WHERE id = $1 RETURNING id", user_id)
         .fetch_optional(pool.get_ref())
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
+        .map_err(ApiError::from)?;
 
     if result.is_none() {
-        return Ok(HttpResponse::NotFound().json(serde_json::json!({
-            "error": "user_not_found",
-            "message": "User not found"
-        })));
+        return Err(ApiError::NotFound("User not found".to_string()));
     }
 
     Ok(HttpResponse::NoContent().finish())
@@ -126,12 +127,15 @@ pub struct CastExport {
 /// - GDPR Article 20 (Right to Data Portability)
 /// - CCPA §1798.110 (Right to Know)
 /// - Japanese APPI Article 28 (Disclosure)
-pub async fn export_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Result<HttpResponse> {
+pub async fn export_user_data(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
     // Get authenticated user from request extensions (set by auth middleware)
     let user_id = {
         let ext = req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
@@ -153,8 +157,8 @@ pub async fn export_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Resu
     )
     .fetch_optional(pool.get_ref())
     .await
-    .map_err(actix_web::error::ErrorInternalServerError)?
-    .ok_or_else(|| actix_web::error::ErrorNotFound("User not found"))?;
+    .map_err(ApiError::from)?
+    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
 
     // Fetch API keys (without hash for security)
     let api_keys = sqlx::query_as!(
@@ -169,7 +173,7 @@ pub async fn export_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Resu
     )
     .fetch_all(pool.get_ref())
     .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+    .map_err(ApiError::from)?;
 
     // Fetch billing info
     let billing = sqlx::query!(
@@ -182,7 +186,7 @@ pub async fn export_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Resu
     )
     .fetch_optional(pool.get_ref())
     .await
-    .map_err(actix_web::error::ErrorInternalServerError)?
+    .map_err(ApiError::from)?
     .map(|row| BillingExport {
         stripe_customer_id: row.stripe_customer_id,
         plan: row.plan,
@@ -201,7 +205,7 @@ pub async fn export_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Resu
     )
     .fetch_optional(pool.get_ref())
     .await
-    .map_err(actix_web::error::ErrorInternalServerError)?
+    .map_err(ApiError::from)?
     .map(|row| BudgetExport {
         period: row.period,
         soft_limit_cents: row.soft_limit_cents,
@@ -231,7 +235,7 @@ pub async fn export_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Resu
     )
     .fetch_all(pool.get_ref())
     .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+    .map_err(ApiError::from)?;
 
     // Fetch casts (limited to last 1000 for performance)
     let casts = sqlx::query_as!(
@@ -247,7 +251,7 @@ pub async fn export_user_data(req: HttpRequest, pool: web::Data<PgPool>) -> Resu
     )
     .fetch_all(pool.get_ref())
     .await
-    .map_err(actix_web::error::ErrorInternalServerError)?;
+    .map_err(ApiError::from)?;
 
     let export = UserDataExport {
         user,
@@ -1,5 +1,10 @@
+use crate::errors::ApiError;
 use crate::middleware::auth::authenticate_from_cookie;
-use crate::models::{ApiKey, CreateApiKeyRequest, CreateApiKeyResponse, ListApiKeyResponse, User};
+use crate::models::{
+    ApiKey, CreateApiKeyRequest, CreateApiKeyResponse, ListApiKeyResponse, SetCallbackUrlRequest,
+    User,
+};
+use crate::services::webhook_service::WebhookService;
 use crate::utils::apikey::{extract_prefix, generate_api_key};
 use crate::AppState;
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
@@ -18,9 +23,14 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     )
     .service(
         web::resource("/keys/{id}")
-            .wrap(auth)
+            .wrap(auth.clone())
             .route(web::delete().to(delete_api_key)),
     )
+    .service(
+        web::resource("/keys/{id}/callback")
+            .wrap(auth)
+            .route(web::put().to(set_callback_url)),
+    )
     .service(
         web::resource("/api-keys")
             .route(web::post().to(create_api_key_cookie))
@@ -33,25 +43,24 @@ async fn create_api_key(
     state: web::Data<AppState>,
     req: HttpRequest,
     payload: web::Json<CreateApiKeyRequest>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     // Get user from request extensions (set by auth middleware)
     let user_id = {
         let ext = req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
     let prefix = env::var("API_KEY_PREFIX").unwrap_or_else(|_| "sk_live_".to_string());
 
     // Generate API key
-    let (api_key, hash) = generate_api_key(&prefix).map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to generate API key: {e}"))
-    })?;
+    let (api_key, hash) = generate_api_key(&prefix)
+        .map_err(|e| ApiError::Internal(format!("Failed to generate API key: {e}")))?;
 
     // Extract prefix for storage
     let stored_prefix = extract_prefix(&api_key)
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Failed to extract prefix"))?;
+        .ok_or_else(|| ApiError::Internal("Failed to extract prefix".to_string()))?;
 
     // Insert into database
     let api_key_id: Uuid = sqlx::query_scalar(
@@ -67,9 +76,7 @@ async fn create_api_key(
     .bind(&hash)
     .fetch_one(&state.db)
     .await
-    .map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to create API key: {e}"))
-    })?;
+    .map_err(|e| ApiError::Internal(format!("Failed to create API key: {e}")))?;
 
     log::info!("API key {api_key_id} created for user {user_id}");
 
@@ -83,17 +90,17 @@ async fn create_api_key(
 async fn list_api_keys(
     state: web::Data<AppState>,
     req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user_id = {
         let ext = req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
     let keys: Vec<ApiKey> = sqlx::query_as::<_, ApiKey>(
         r#"
-        SELECT id, user_id, name, prefix, hash, created_at, last_used_at
+        SELECT id, user_id, name, prefix, hash, created_at, last_used_at, default_callback_url
         FROM api_keys
         WHERE user_id = $1
         ORDER BY created_at DESC
@@ -102,9 +109,7 @@ async fn list_api_keys(
     .bind(user_id)
     .fetch_all(&state.db)
     .await
-    .map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to list API keys: {e}"))
-    })?;
+    .map_err(|e| ApiError::Internal(format!("Failed to list API keys: {e}")))?;
 
     let response: Vec<ListApiKeyResponse> = keys
         .into_iter()
@@ -114,6 +119,7 @@ async fn list_api_keys(
             prefix: k.prefix,
             created_at: k.created_at,
             last_used_at: k.last_used_at,
+            default_callback_url: k.default_callback_url,
         })
         .collect();
 
@@ -124,11 +130,11 @@ async fn delete_api_key(
     state: web::Data<AppState>,
     req: HttpRequest,
     path: web::Path<Uuid>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user_id = {
         let ext = req.extensions();
         ext.get::<User>()
-            .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not authenticated"))?
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
             .id
     };
 
@@ -144,12 +150,10 @@ async fn delete_api_key(
     .bind(user_id)
     .execute(&state.db)
     .await
-    .map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to delete API key: {e}"))
-    })?;
+    .map_err(|e| ApiError::Internal(format!("Failed to delete API key: {e}")))?;
 
     if result.rows_affected() == 0 {
-        return Err(actix_web::error::ErrorNotFound("API key not found"));
+        return Err(ApiError::NotFound("API key not found".to_string()));
     }
 
     log::info!("API key {key_id} deleted by user {user_id}");
@@ -157,27 +161,69 @@ async fn delete_api_key(
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Set or clear the default webhook URL for casts made with this key
+async fn set_callback_url(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    payload: web::Json<SetCallbackUrlRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = {
+        let ext = req.extensions();
+        ext.get::<User>()
+            .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+            .id
+    };
+
+    let key_id = path.into_inner();
+
+    if let Some(url) = &payload.url {
+        WebhookService::validate_callback_url(url).map_err(ApiError::InvalidRequest)?;
+    }
+
+    let result = sqlx::query(
+        r#"
+        UPDATE api_keys
+        SET default_callback_url = $3
+        WHERE id = $1 AND user_id = $2
+        "#,
+    )
+    .bind(key_id)
+    .bind(user_id)
+    .bind(&payload.url)
+    .execute(&state.db)
+    .await
+    .map_err(|e| ApiError::Internal(format!("Failed to update API key: {e}")))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("API key not found".to_string()));
+    }
+
+    log::info!("Default callback URL updated for API key {key_id} by user {user_id}");
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
 // Cookie-based authentication endpoints
 
 async fn create_api_key_cookie(
     state: web::Data<AppState>,
     http_req: HttpRequest,
     payload: web::Json<CreateApiKeyRequest>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let prefix = env::var("API_KEY_PREFIX").unwrap_or_else(|_| "sk_live_".to_string());
 
     // Generate API key
-    let (api_key, hash) = generate_api_key(&prefix).map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to generate API key: {e}"))
-    })?;
+    let (api_key, hash) = generate_api_key(&prefix)
+        .map_err(|e| ApiError::Internal(format!("Failed to generate API key: {e}")))?;
 
     // Extract prefix for storage
     let stored_prefix = extract_prefix(&api_key)
-        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Failed to extract prefix"))?;
+        .ok_or_else(|| ApiError::Internal("Failed to extract prefix".to_string()))?;
 
     // Insert into database
     let api_key_id: Uuid = sqlx::query_scalar(
@@ -193,9 +239,7 @@ async fn create_api_key_cookie(
     .bind(&hash)
     .fetch_one(&state.db)
     .await
-    .map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to create API key: {e}"))
-    })?;
+    .map_err(|e| ApiError::Internal(format!("Failed to create API key: {e}")))?;
 
     log::info!(
         "API key {api_key_id} created for user {} ({})",
@@ -213,14 +257,14 @@ async fn create_api_key_cookie(
 async fn list_api_keys_cookie(
     state: web::Data<AppState>,
     http_req: HttpRequest,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let keys: Vec<ApiKey> = sqlx::query_as::<_, ApiKey>(
         r#"
-        SELECT id, user_id, name, prefix, hash, created_at, last_used_at
+        SELECT id, user_id, name, prefix, hash, created_at, last_used_at, default_callback_url
         FROM api_keys
         WHERE user_id = $1
         ORDER BY created_at DESC
@@ -229,9 +273,7 @@ async fn list_api_keys_cookie(
     .bind(user.id)
     .fetch_all(&state.db)
     .await
-    .map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to list API keys: {e}"))
-    })?;
+    .map_err(|e| ApiError::Internal(format!("Failed to list API keys: {e}")))?;
 
     let response: Vec<ListApiKeyResponse> = keys
         .into_iter()
@@ -241,6 +283,7 @@ async fn list_api_keys_cookie(
             prefix: k.prefix,
             created_at: k.created_at,
             last_used_at: k.last_used_at,
+            default_callback_url: k.default_callback_url,
         })
         .collect();
 
@@ -251,10 +294,10 @@ async fn delete_api_key_cookie(
     state: web::Data<AppState>,
     http_req: HttpRequest,
     path: web::Path<Uuid>,
-) -> Result<HttpResponse, actix_web::Error> {
+) -> Result<HttpResponse, ApiError> {
     let user = authenticate_from_cookie(&http_req, &state.db)
         .await
-        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Not authenticated"))?;
+        .ok_or_else(|| ApiError::Unauthorized("Not authenticated".to_string()))?;
 
     let key_id = path.into_inner();
 
@@ -268,12 +311,10 @@ async fn delete_api_key_cookie(
     .bind(user.id)
     .execute(&state.db)
     .await
-    .map_err(|e| {
-        actix_web::error::ErrorInternalServerError(format!("Failed to delete API key: {e}"))
-    })?;
+    .map_err(|e| ApiError::Internal(format!("Failed to delete API key: {e}")))?;
 
     if result.rows_affected() == 0 {
-        return Err(actix_web::error::ErrorNotFound("API key not found"));
+        return Err(ApiError::NotFound("API key not found".to_string()));
     }
 
     log::info!(
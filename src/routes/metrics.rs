@@ -3,6 +3,7 @@ use parking_lot::Mutex;
 use prometheus::{opts, Counter, Encoder, Gauge, Histogram, HistogramOpts, Registry, TextEncoder};
 use std::sync::Arc;
 
+use crate::errors::ApiError;
 // Metrics fields are registered in Prometheus registry and accessed via registry.gather()
 #[allow(dead_code)]
 pub struct Metrics {
@@ -100,9 +101,7 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.route("/metrics", web::get().to(get_metrics));
 }
 
-async fn get_metrics(
-    metrics: web::Data<Arc<Mutex<Metrics>>>,
-) -> Result<HttpResponse, actix_web::Error> {
+async fn get_metrics(metrics: web::Data<Arc<Mutex<Metrics>>>) -> Result<HttpResponse, ApiError> {
     let metrics = metrics.lock();
     let encoder = TextEncoder::new();
     let metric_families = metrics.registry.gather();
@@ -110,7 +109,7 @@ async fn get_metrics(
     let mut buffer = Vec::new();
     encoder.encode(&metric_families, &mut buffer).map_err(|e| {
         log::error!("Failed to encode metrics: {e}");
-        actix_web::error::ErrorInternalServerError("Failed to encode metrics")
+        ApiError::Internal("Failed to encode metrics".to_string())
     })?;
 
     Ok(HttpResponse::Ok()
@@ -3,7 +3,15 @@ pub mod auth;
 pub mod billing;
 pub mod budgets;
 pub mod cast;
+pub mod casts;
+pub mod catalog;
 pub mod debug;
+pub mod deprecations;
 pub mod gdpr;
 pub mod keys;
 pub mod metrics;
+pub mod pipelines;
+pub mod schedules;
+pub mod sessions;
+pub mod spells;
+pub mod webhooks;
@@ -0,0 +1,238 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+use crate::models::pipeline::{
+    Pipeline, PipelineRun, RunInlinePipelineRequest, RunPipelineRequest, SavePipelineRequest,
+    UpdatePipelineRequest,
+};
+use crate::models::User;
+use crate::services::pipeline_service::PipelineService;
+use crate::AppState;
+
+const MAX_NAME_LEN: usize = 64;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(crate::middleware::auth::validator);
+    cfg.service(
+        web::scope("/pipelines")
+            .wrap(auth.clone())
+            .route("", web::get().to(list_pipelines))
+            .route("", web::post().to(create_pipeline))
+            .route("/{name}", web::get().to(get_pipeline))
+            .route("/{name}", web::put().to(update_pipeline))
+            .route("/{name}", web::delete().to(delete_pipeline))
+            .route("/{name}/runs", web::post().to(run_pipeline)),
+    )
+    .service(
+        web::scope("/pipeline-runs")
+            .wrap(auth)
+            .route("", web::post().to(run_inline))
+            .route("/{id}", web::get().to(get_run)),
+    );
+}
+
+fn authenticated_user_id(http_req: &HttpRequest) -> Result<Uuid, ApiError> {
+    let ext = http_req.extensions();
+    Ok(ext
+        .get::<User>()
+        .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+        .id)
+}
+
+fn db_error(e: sqlx::Error) -> ApiError {
+    ApiError::database("Pipeline query failed", e)
+}
+
+async fn fetch_pipeline(state: &AppState, user_id: Uuid, name: &str) -> Result<Pipeline, ApiError> {
+    sqlx::query_as("SELECT * FROM pipelines WHERE user_id = $1 AND name = $2")
+        .bind(user_id)
+        .bind(name)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(db_error)?
+        .ok_or_else(|| ApiError::NotFound(format!("Pipeline '{name}' not found")))
+}
+
+async fn list_pipelines(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let pipelines: Vec<Pipeline> =
+        sqlx::query_as("SELECT * FROM pipelines WHERE user_id = $1 ORDER BY name")
+            .bind(user_id)
+            .fetch_all(&state.db)
+            .await
+            .map_err(db_error)?;
+    Ok(HttpResponse::Ok().json(pipelines))
+}
+
+/// Save a pipeline definition under a name; the definition is checked but not run
+async fn create_pipeline(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<SavePipelineRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let req = req.into_inner();
+
+    if req.name.is_empty()
+        || req.name.len() > MAX_NAME_LEN
+        || !req
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+    {
+        return Err(ApiError::InvalidRequest(
+            "Pipeline name must be 1-64 characters of [A-Za-z0-9_-]".to_string(),
+        ));
+    }
+    PipelineService::plan(&req.definition).map_err(ApiError::InvalidRequest)?;
+
+    let pipeline: Option<Pipeline> = sqlx::query_as(
+        r#"
+        INSERT INTO pipelines (id, user_id, name, description, definition)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (user_id, name) DO NOTHING
+        RETURNING *
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(&req.name)
+    .bind(&req.description)
+    .bind(sqlx::types::Json(&req.definition))
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    let pipeline = pipeline.ok_or_else(|| {
+        ApiError::Conflict(format!("A pipeline named '{}' already exists", req.name))
+    })?;
+
+    log::info!("Pipeline {} saved by user {user_id}", pipeline.name);
+
+    Ok(HttpResponse::Created().json(pipeline))
+}
+
+async fn get_pipeline(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let pipeline = fetch_pipeline(&state, user_id, &path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(pipeline))
+}
+
+/// Replace a saved pipeline's definition; runs already made keep the definition they ran
+async fn update_pipeline(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<UpdatePipelineRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let existing = fetch_pipeline(&state, user_id, &path.into_inner()).await?;
+    let req = req.into_inner();
+    PipelineService::plan(&req.definition).map_err(ApiError::InvalidRequest)?;
+
+    let pipeline: Pipeline = sqlx::query_as(
+        r#"
+        UPDATE pipelines
+        SET description = COALESCE($2, description), definition = $3, updated_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(existing.id)
+    .bind(&req.description)
+    .bind(sqlx::types::Json(&req.definition))
+    .fetch_one(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    Ok(HttpResponse::Ok().json(pipeline))
+}
+
+async fn delete_pipeline(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let pipeline = fetch_pipeline(&state, user_id, &path.into_inner()).await?;
+
+    sqlx::query("DELETE FROM pipelines WHERE id = $1")
+        .bind(pipeline.id)
+        .execute(&state.db)
+        .await
+        .map_err(db_error)?;
+
+    log::info!("Pipeline {} deleted by user {user_id}", pipeline.name);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Run a saved pipeline and wait for every step
+async fn run_pipeline(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<RunPipelineRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let pipeline = fetch_pipeline(&state, user_id, &path.into_inner()).await?;
+    let req = req.into_inner();
+
+    let run = PipelineService::run(
+        state,
+        user_id,
+        Some(pipeline.id),
+        pipeline.definition.0,
+        req.input,
+        req.bypass_cache,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(run))
+}
+
+/// Run a pipeline defined in the request body, without saving it
+async fn run_inline(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<RunInlinePipelineRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let req = req.into_inner();
+
+    let run = PipelineService::run(
+        state,
+        user_id,
+        None,
+        req.definition,
+        req.input,
+        req.bypass_cache,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(run))
+}
+
+async fn get_run(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let run: PipelineRun =
+        sqlx::query_as("SELECT * FROM pipeline_runs WHERE id = $1 AND user_id = $2")
+            .bind(path.into_inner())
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(db_error)?
+            .ok_or_else(|| ApiError::NotFound("Pipeline run not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(run))
+}
@@ -0,0 +1,301 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use chrono::Utc;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+use crate::models::schedule::{
+    CastSchedule, CreateScheduleRequest, UpdateScheduleRequest, SCHEDULE_ACTIVE, SCHEDULE_PAUSED,
+};
+use crate::models::User;
+use crate::services::cast_service::CastService;
+use crate::services::schedule_service::ScheduleService;
+use crate::AppState;
+
+const MAX_NAME_LEN: usize = 64;
+
+// The last run's status comes from its cast once there is one
+const SELECT_SCHEDULE: &str = r#"
+    SELECT s.id, s.name, s.spell_name, s.version, s.payload, s.cron, s.status,
+           s.next_run_at, s.last_run_at, s.last_cast_id,
+           COALESCE(c.status, s.last_status) AS last_status,
+           COALESCE(c.error_code, s.last_error_code) AS last_error_code,
+           COALESCE(c.error_message, s.last_error) AS last_error,
+           s.created_at, s.updated_at
+    FROM cast_schedules s
+    LEFT JOIN casts c ON c.id = s.last_cast_id
+"#;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/schedules")
+            .wrap(HttpAuthentication::bearer(
+                crate::middleware::auth::validator,
+            ))
+            .route("", web::get().to(list_schedules))
+            .route("", web::post().to(create_schedule))
+            .route("/{name}", web::get().to(get_schedule))
+            .route("/{name}", web::put().to(update_schedule))
+            .route("/{name}", web::delete().to(delete_schedule))
+            .route("/{name}/pause", web::post().to(pause_schedule))
+            .route("/{name}/resume", web::post().to(resume_schedule)),
+    );
+}
+
+fn authenticated_user_id(http_req: &HttpRequest) -> Result<Uuid, ApiError> {
+    let ext = http_req.extensions();
+    Ok(ext
+        .get::<User>()
+        .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+        .id)
+}
+
+fn db_error(e: sqlx::Error) -> ApiError {
+    ApiError::database("Schedule query failed", e)
+}
+
+async fn fetch_schedule(
+    state: &AppState,
+    user_id: Uuid,
+    name: &str,
+) -> Result<CastSchedule, ApiError> {
+    sqlx::query_as(&format!(
+        "{SELECT_SCHEDULE} WHERE s.user_id = $1 AND s.name = $2"
+    ))
+    .bind(user_id)
+    .bind(name)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?
+    .ok_or_else(|| ApiError::NotFound(format!("Schedule '{name}' not found")))
+}
+
+/// Check what a schedule would cast, so mistakes surface now rather than at its first run
+async fn check_cast(
+    state: &AppState,
+    spell_name: &str,
+    version: Option<&str>,
+    payload: &Value,
+) -> Result<(), ApiError> {
+    let (spell, version) = CastService::resolve(spell_name, version, &state.db).await?;
+    CastService::check_payload(&spell, &version, payload, &[])?;
+    Ok(())
+}
+
+async fn list_schedules(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let schedules: Vec<CastSchedule> = sqlx::query_as(&format!(
+        "{SELECT_SCHEDULE} WHERE s.user_id = $1 ORDER BY s.name"
+    ))
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_error)?;
+    Ok(HttpResponse::Ok().json(schedules))
+}
+
+async fn create_schedule(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<CreateScheduleRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let req = req.into_inner();
+
+    if req.name.is_empty()
+        || req.name.len() > MAX_NAME_LEN
+        || !req
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+    {
+        return Err(ApiError::InvalidRequest(
+            "Schedule name must be 1-64 characters of [A-Za-z0-9_-]".to_string(),
+        ));
+    }
+    let next_run_at =
+        ScheduleService::next_run(&req.cron, Utc::now()).map_err(ApiError::InvalidRequest)?;
+    check_cast(
+        &state,
+        &req.spell_name,
+        req.version.as_deref(),
+        &req.payload,
+    )
+    .await?;
+
+    let (status, next_run_at) = if req.paused {
+        (SCHEDULE_PAUSED, None)
+    } else {
+        (SCHEDULE_ACTIVE, Some(next_run_at))
+    };
+
+    let created: Option<(Uuid,)> = sqlx::query_as(
+        r#"
+        INSERT INTO cast_schedules (id, user_id, name, spell_name, version, payload, cron, status, next_run_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (user_id, name) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(&req.name)
+    .bind(&req.spell_name)
+    .bind(&req.version)
+    .bind(&req.payload)
+    .bind(req.cron.trim())
+    .bind(status)
+    .bind(next_run_at)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    if created.is_none() {
+        return Err(ApiError::Conflict(format!(
+            "A schedule named '{}' already exists",
+            req.name
+        )));
+    }
+
+    log::info!("Schedule {} created by user {user_id}", req.name);
+
+    let schedule = fetch_schedule(&state, user_id, &req.name).await?;
+    Ok(HttpResponse::Created().json(schedule))
+}
+
+/// A schedule with its next run and the outcome of its last one
+async fn get_schedule(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let schedule = fetch_schedule(&state, user_id, &path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+/// Replace what a schedule casts and when; a paused schedule stays paused
+async fn update_schedule(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<UpdateScheduleRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let existing = fetch_schedule(&state, user_id, &path.into_inner()).await?;
+    let req = req.into_inner();
+
+    let next_run_at =
+        ScheduleService::next_run(&req.cron, Utc::now()).map_err(ApiError::InvalidRequest)?;
+    check_cast(
+        &state,
+        &req.spell_name,
+        req.version.as_deref(),
+        &req.payload,
+    )
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE cast_schedules
+        SET spell_name = $2, version = $3, payload = $4, cron = $5,
+            next_run_at = CASE WHEN status = 'ACTIVE' THEN $6 END, updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(existing.id)
+    .bind(&req.spell_name)
+    .bind(&req.version)
+    .bind(&req.payload)
+    .bind(req.cron.trim())
+    .bind(next_run_at)
+    .execute(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    let schedule = fetch_schedule(&state, user_id, &existing.name).await?;
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+/// Delete a schedule; casts it already made are kept
+async fn delete_schedule(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let schedule = fetch_schedule(&state, user_id, &path.into_inner()).await?;
+
+    sqlx::query("DELETE FROM cast_schedules WHERE id = $1")
+        .bind(schedule.id)
+        .execute(&state.db)
+        .await
+        .map_err(db_error)?;
+
+    log::info!("Schedule {} deleted by user {user_id}", schedule.name);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Stop a schedule's runs; a run already cast is not canceled
+async fn pause_schedule(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let schedule = fetch_schedule(&state, user_id, &path.into_inner()).await?;
+
+    sqlx::query(
+        r#"
+        UPDATE cast_schedules
+        SET status = 'PAUSED', next_run_at = NULL, updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(schedule.id)
+    .execute(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    log::info!("Schedule {} paused by user {user_id}", schedule.name);
+
+    let schedule = fetch_schedule(&state, user_id, &schedule.name).await?;
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+/// Restart a paused schedule from its next occurrence; runs missed while paused are not made up
+async fn resume_schedule(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let schedule = fetch_schedule(&state, user_id, &path.into_inner()).await?;
+
+    if schedule.status != SCHEDULE_ACTIVE {
+        let next_run_at = ScheduleService::next_run(&schedule.cron, Utc::now())
+            .map_err(ApiError::InvalidRequest)?;
+        sqlx::query(
+            r#"
+            UPDATE cast_schedules
+            SET status = 'ACTIVE', next_run_at = $2, updated_at = NOW()
+            WHERE id = $1 AND status = 'PAUSED'
+            "#,
+        )
+        .bind(schedule.id)
+        .bind(next_run_at)
+        .execute(&state.db)
+        .await
+        .map_err(db_error)?;
+
+        log::info!("Schedule {} resumed by user {user_id}", schedule.name);
+    }
+
+    let schedule = fetch_schedule(&state, user_id, &schedule.name).await?;
+    Ok(HttpResponse::Ok().json(schedule))
+}
@@ -0,0 +1,92 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use serde::Deserialize;
+
+use crate::errors::{ApiError, CastError};
+use crate::middleware::request_id;
+use crate::models::ApiKey;
+use crate::services::budget_service::BudgetService;
+use crate::services::cast_service::CastService;
+use crate::services::circuit_breaker::CircuitBreaker;
+use crate::services::session_service::{
+    SessionContext, SessionService, MAX_MESSAGE_BYTES, MAX_OPEN_SESSIONS,
+};
+use crate::AppState;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(crate::middleware::auth::validator);
+    cfg.service(
+        web::resource("/sessions/{spell_name}")
+            .wrap(auth)
+            .route(web::get().to(open_session)),
+    );
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionQuery {
+    version: Option<String>,
+}
+
+/// Upgrade to a WebSocket session on one warm instance of a spell
+/// Only API keys may open sessions. Each text frame is a JSON payload cast on the same
+/// instance; replies are the cast event objects also used by `/cast/stream`.
+async fn open_session(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<SessionQuery>,
+    body: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    let api_key = http_req
+        .extensions()
+        .get::<ApiKey>()
+        .cloned()
+        .ok_or_else(|| ApiError::Unauthorized("Spell sessions require an API key".to_string()))?;
+    let user_id = api_key.user_id;
+
+    // Refuse up front rather than opening a session that can't run a single message
+    BudgetService::check_hard_limit(&user_id, &state.db)
+        .await
+        .map_err(CastError::BudgetExceeded)?;
+
+    let spell_name = path.into_inner();
+    let (spell, version) =
+        CastService::resolve(&spell_name, query.version.as_deref(), &state.db).await?;
+    let admission = CircuitBreaker::admit(&state.redis, &spell, &version).await?;
+    let module = state.wasm.load_module(&spell.name, &version).await?;
+
+    let (response, ws, messages) = actix_ws::handle(&http_req, body)
+        .map_err(|e| ApiError::InvalidRequest(format!("WebSocket handshake failed: {e}")))?;
+    let messages = messages
+        .max_frame_size(MAX_MESSAGE_BYTES)
+        .aggregate_continuations()
+        .max_continuation_size(MAX_MESSAGE_BYTES);
+
+    let session_id = SessionService::open(&user_id, &api_key.id, &spell, &version, &state.db)
+        .await?
+        .ok_or_else(|| {
+            log::warn!("User {user_id} already holds {MAX_OPEN_SESSIONS} open sessions");
+            ApiError::RateLimited {
+                retry_after_secs: 30,
+            }
+        })?;
+    log::info!(
+        "Session {session_id} opened on {spell_name}@{} by user {user_id}",
+        version.version
+    );
+
+    let ctx = SessionContext {
+        id: session_id,
+        user_id,
+        spell,
+        version,
+        module,
+        admission,
+    };
+    actix_web::rt::spawn(request_id::scope(
+        request_id::current(),
+        SessionService::run(state, ws, messages, ctx),
+    ));
+
+    Ok(response)
+}
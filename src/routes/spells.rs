@@ -0,0 +1,885 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+use crate::models::replay::ReplayRequest;
+use crate::models::spell::manifest_examples;
+use crate::models::spell::{
+    ChangePriceRequest, CreatorSpellResponse, DeprecateVersionRequest, SetVersionPurityRequest,
+    SpellCircuitEvent, SpellCircuitReport, SpellExample, SpellHealthCheck, SpellHealthReport,
+    SpellHealthStats, SpellStatsQuery, SpellVersion, SpellVersionResponse, UpdateSpellRequest,
+    YankVersionRequest, MAX_EXAMPLES, PRICING_PER_CALL, PRICING_PER_SESSION,
+};
+use crate::models::{Spell, User};
+use crate::services::circuit_breaker::CircuitBreaker;
+use crate::services::contract_service::ContractService;
+use crate::services::replay_service::ReplayService;
+use crate::services::webhook_service::WebhookService;
+use crate::storage::put_blob;
+use crate::utils::codec::Codec;
+use crate::AppState;
+
+// Health stats look back 30 days by default, at most one year
+const DEFAULT_STATS_DAYS: i32 = 30;
+const MAX_STATS_DAYS: i32 = 365;
+
+// Price increases must be announced at least a week ahead; decreases may apply immediately
+const PRICE_INCREASE_NOTICE_DAYS: i64 = 7;
+const MAX_PRICE_CENTS: i32 = 100_000; // $1,000 per cast
+
+// Published modules are capped at 50MB; version labels at 64 characters
+const MAX_MODULE_BYTES: usize = 50 * 1024 * 1024;
+const MAX_VERSION_LEN: usize = 64;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(crate::middleware::auth::validator);
+
+    cfg.service(
+        web::resource("/spells/{name}/stats")
+            .wrap(auth.clone())
+            .route(web::get().to(get_spell_stats)),
+    )
+    .service(
+        web::scope("/creator/spells")
+            .wrap(auth)
+            .route("", web::get().to(list_my_spells))
+            .route("/{name}", web::patch().to(update_spell))
+            .route("/{name}/price", web::put().to(change_price))
+            .route("/{name}/activate", web::post().to(activate_spell))
+            .route("/{name}/deactivate", web::post().to(deactivate_spell))
+            .route("/{name}/circuit", web::get().to(get_spell_circuit))
+            .route("/{name}/health", web::get().to(get_spell_health))
+            .service(
+                web::resource("/{name}/versions/{version}")
+                    .app_data(web::PayloadConfig::new(MAX_MODULE_BYTES))
+                    .route(web::post().to(publish_version)),
+            )
+            .route(
+                "/{name}/versions/{version}/deprecate",
+                web::post().to(deprecate_version),
+            )
+            .route(
+                "/{name}/versions/{version}/yank",
+                web::post().to(yank_version),
+            )
+            .route(
+                "/{name}/versions/{version}/restore",
+                web::post().to(restore_version),
+            )
+            .route(
+                "/{name}/versions/{version}/purity",
+                web::put().to(set_version_purity),
+            )
+            .route(
+                "/{name}/versions/{version}/replay",
+                web::post().to(replay_casts),
+            ),
+    );
+}
+
+fn authenticated_user_id(http_req: &HttpRequest) -> Result<Uuid, ApiError> {
+    let ext = http_req.extensions();
+    Ok(ext
+        .get::<User>()
+        .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+        .id)
+}
+
+/// Fetch a spell by name, only if it belongs to the given creator
+async fn fetch_owned_spell(
+    state: &web::Data<AppState>,
+    user_id: Uuid,
+    spell_name: &str,
+) -> Result<Spell, ApiError> {
+    let spell: Option<Spell> = sqlx::query_as(
+        r#"
+        SELECT * FROM spells WHERE name = $1
+        "#,
+    )
+    .bind(spell_name)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to fetch spell: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    // Non-owners get the same answer as a missing spell
+    spell
+        .filter(|s| s.creator_id == user_id)
+        .ok_or_else(|| ApiError::SpellNotFound(spell_name.to_string()))
+}
+
+async fn fetch_versions(
+    state: &web::Data<AppState>,
+    spell_ids: &[Uuid],
+) -> Result<Vec<SpellVersion>, ApiError> {
+    sqlx::query_as(
+        r#"
+        SELECT * FROM spell_versions
+        WHERE spell_id = ANY($1)
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(spell_ids)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to fetch spell versions: {e}");
+        ApiError::Internal("Database error".to_string())
+    })
+}
+
+async fn creator_response(
+    state: &web::Data<AppState>,
+    spell: Spell,
+) -> Result<CreatorSpellResponse, ApiError> {
+    let versions = fetch_versions(state, &[spell.id]).await?;
+    Ok(CreatorSpellResponse::new(spell, versions))
+}
+
+/// List the authenticated creator's spells (active or not) with their versions
+async fn list_my_spells(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+
+    let spells: Vec<Spell> = sqlx::query_as(
+        r#"
+        SELECT * FROM spells WHERE creator_id = $1 ORDER BY created_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to list spells: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    let spell_ids: Vec<Uuid> = spells.iter().map(|s| s.id).collect();
+    let mut versions_by_spell: HashMap<Uuid, Vec<SpellVersion>> = HashMap::new();
+    for version in fetch_versions(&state, &spell_ids).await? {
+        versions_by_spell
+            .entry(version.spell_id)
+            .or_default()
+            .push(version);
+    }
+
+    let response: Vec<CreatorSpellResponse> = spells
+        .into_iter()
+        .map(|spell| {
+            let versions = versions_by_spell.remove(&spell.id).unwrap_or_default();
+            CreatorSpellResponse::new(spell, versions)
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Update description, catalog tags/category, the output schema and retry/cache policies
+async fn update_spell(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<UpdateSpellRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let spell = fetch_owned_spell(&state, user_id, &path.into_inner()).await?;
+    let req = req.into_inner();
+
+    if let Some(Some(schema)) = &req.output_schema {
+        ContractService::check_schema(schema).map_err(ApiError::SchemaInvalid)?;
+    }
+
+    if let Some(policy) = &req.retry_policy {
+        policy.validate().map_err(ApiError::InvalidRequest)?;
+    }
+    let retry_policy = req.retry_policy.unwrap_or_else(|| spell.retry_policy());
+    if let Some(policy) = &req.cache_policy {
+        policy.validate().map_err(ApiError::InvalidRequest)?;
+    }
+    let cache_policy = req.cache_policy.unwrap_or_else(|| spell.cache_policy());
+    if let Some(model) = &req.pricing_model {
+        if ![PRICING_PER_CALL, PRICING_PER_SESSION].contains(&model.as_str()) {
+            return Err(ApiError::InvalidRequest(
+                "pricing_model must be one of per_call, per_session".to_string(),
+            ));
+        }
+    }
+    let pricing_model = req.pricing_model.unwrap_or(spell.pricing_model);
+    if let Some(limit) = req.max_input_bytes {
+        if !(Spell::MIN_INPUT_BYTES..=Spell::MAX_INPUT_BYTES).contains(&limit) {
+            return Err(ApiError::InvalidRequest(format!(
+                "max_input_bytes must be between {} and {}",
+                Spell::MIN_INPUT_BYTES,
+                Spell::MAX_INPUT_BYTES
+            )));
+        }
+    }
+    let max_input_bytes = req.max_input_bytes.unwrap_or(spell.max_input_bytes);
+    for format in [&req.input_format, &req.output_format]
+        .into_iter()
+        .flatten()
+    {
+        if Codec::from_name(format).is_none() {
+            return Err(ApiError::InvalidRequest(
+                "input_format and output_format must be one of json, msgpack, cbor".to_string(),
+            ));
+        }
+    }
+
+    if let Some(Some(url)) = &req.alert_url {
+        WebhookService::validate_callback_url(url)
+            .map_err(|e| ApiError::InvalidRequest(e.replace("callback_url", "alert_url")))?;
+    }
+    let alert_url = req.alert_url.unwrap_or(spell.alert_url);
+    if let Some(examples) = &req.examples {
+        validate_examples(examples).map_err(ApiError::InvalidRequest)?;
+    }
+
+    let description = req.description.unwrap_or(spell.description);
+    let output_schema = req.output_schema.unwrap_or(spell.output_schema);
+
+    // Catalog metadata lives under the manifest's [metadata] table
+    let mut manifest = spell.manifest;
+    if !manifest.is_object() {
+        manifest = serde_json::json!({});
+    }
+    if !manifest["metadata"].is_object() {
+        manifest["metadata"] = serde_json::json!({});
+    }
+    if let Some(tags) = req.tags {
+        manifest["metadata"]["tags"] = serde_json::json!(tags);
+    }
+    if let Some(category) = req.category {
+        manifest["metadata"]["category"] = serde_json::json!(category);
+    }
+    if let Some(format) = req.input_format {
+        manifest["input_format"] = serde_json::json!(format.to_ascii_lowercase());
+    }
+    if let Some(format) = req.output_format {
+        manifest["output_format"] = serde_json::json!(format.to_ascii_lowercase());
+    }
+    if let (Some(examples), Some(fields)) = (req.examples, manifest.as_object_mut()) {
+        if examples.is_empty() {
+            fields.remove("examples");
+        } else {
+            fields.insert("examples".to_string(), serde_json::json!(examples));
+        }
+    }
+
+    let updated: Spell = sqlx::query_as(
+        r#"
+        UPDATE spells
+        SET description = $2, manifest = $3, output_schema = $4,
+            retry_max_attempts = $5, retry_backoff_ms = $6,
+            cache_ttl_secs = $7, cache_discount_percent = $8, pricing_model = $9,
+            max_input_bytes = $10, alert_url = $11
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(spell.id)
+    .bind(&description)
+    .bind(&manifest)
+    .bind(&output_schema)
+    .bind(retry_policy.max_attempts)
+    .bind(retry_policy.backoff_ms)
+    .bind(cache_policy.ttl_secs)
+    .bind(cache_policy.discount_percent)
+    .bind(&pricing_model)
+    .bind(max_input_bytes)
+    .bind(&alert_url)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to update spell: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    log::info!("Spell {} metadata updated by user {user_id}", updated.name);
+
+    Ok(HttpResponse::Ok().json(creator_response(&state, updated).await?))
+}
+
+/// Check health check examples: a bounded number with distinct, non-empty names
+fn validate_examples(examples: &[SpellExample]) -> Result<(), String> {
+    if examples.len() > MAX_EXAMPLES {
+        return Err(format!("A spell declares at most {MAX_EXAMPLES} examples"));
+    }
+    for (i, example) in examples.iter().enumerate() {
+        if example.name.trim().is_empty() {
+            return Err("Example names must not be empty".to_string());
+        }
+        if examples[..i].iter().any(|e| e.name == example.name) {
+            return Err(format!("Duplicate example name {}", example.name));
+        }
+    }
+    Ok(())
+}
+
+/// Decide when a price change may take effect
+/// Returns the effective time, or an error message if the notice period is too short
+fn schedule_price_change(
+    current_cents: i32,
+    new_cents: i32,
+    effective_from: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    if !(0..=MAX_PRICE_CENTS).contains(&new_cents) {
+        return Err(format!(
+            "Price must be between 0 and {MAX_PRICE_CENTS} cents"
+        ));
+    }
+
+    let effective_from = effective_from.unwrap_or(now).max(now);
+
+    if new_cents > current_cents {
+        let earliest = now + Duration::days(PRICE_INCREASE_NOTICE_DAYS);
+        if effective_from < earliest {
+            return Err(format!(
+                "Price increases require at least {PRICE_INCREASE_NOTICE_DAYS} days notice (earliest: {})",
+                earliest.to_rfc3339()
+            ));
+        }
+    }
+
+    Ok(effective_from)
+}
+
+/// Change the price, either immediately (decreases) or from a future date
+async fn change_price(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<ChangePriceRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let spell = fetch_owned_spell(&state, user_id, &path.into_inner()).await?;
+
+    let now = Utc::now();
+    let current_cents = spell.effective_price_cents(now);
+
+    let effective_from =
+        schedule_price_change(current_cents, req.price_cents, req.effective_from, now)
+            .map_err(ApiError::InvalidRequest)?;
+
+    // Fold any already-effective scheduled change into price_cents, replacing pending ones
+    let (price_cents, next_price_cents, next_price_effective_at) = if effective_from <= now {
+        (req.price_cents, None, None)
+    } else {
+        (current_cents, Some(req.price_cents), Some(effective_from))
+    };
+
+    let updated: Spell = sqlx::query_as(
+        r#"
+        UPDATE spells
+        SET price_cents = $2, next_price_cents = $3, next_price_effective_at = $4
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(spell.id)
+    .bind(price_cents)
+    .bind(next_price_cents)
+    .bind(next_price_effective_at)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to change spell price: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    log::info!(
+        "Spell {} price set to {} cents effective {} by user {user_id}",
+        updated.name,
+        req.price_cents,
+        effective_from.to_rfc3339()
+    );
+
+    Ok(HttpResponse::Ok().json(creator_response(&state, updated).await?))
+}
+
+async fn activate_spell(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    set_active(state, http_req, path.into_inner(), true).await
+}
+
+async fn deactivate_spell(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    set_active(state, http_req, path.into_inner(), false).await
+}
+
+async fn set_active(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    spell_name: String,
+    is_active: bool,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let spell = fetch_owned_spell(&state, user_id, &spell_name).await?;
+
+    let updated: Spell = sqlx::query_as(
+        r#"
+        UPDATE spells SET is_active = $2 WHERE id = $1 RETURNING *
+        "#,
+    )
+    .bind(spell.id)
+    .bind(is_active)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to update spell activation: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    log::info!(
+        "Spell {} {} by user {user_id}",
+        updated.name,
+        if is_active {
+            "activated"
+        } else {
+            "deactivated"
+        }
+    );
+
+    Ok(HttpResponse::Ok().json(creator_response(&state, updated).await?))
+}
+
+/// Publish a new version from a raw WASM module body
+/// The module is stored content-addressed in the blob store; versions are immutable
+async fn publish_version(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let (spell_name, version) = path.into_inner();
+    let spell = fetch_owned_spell(&state, user_id, &spell_name).await?;
+
+    if version.is_empty()
+        || version.len() > MAX_VERSION_LEN
+        || !version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+' | '_'))
+    {
+        return Err(ApiError::InvalidRequest(
+            "Version must be 1-64 characters of [A-Za-z0-9.+-_]".to_string(),
+        ));
+    }
+
+    state
+        .wasm
+        .validate_module(&body)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid WASM module: {e}")))?;
+
+    let digest = put_blob(state.storage.as_ref(), &body).await.map_err(|e| {
+        log::error!("Failed to store module for {spell_name}@{version}: {e}");
+        ApiError::Unavailable("Failed to store module".to_string())
+    })?;
+
+    let mut manifest = spell.manifest.clone();
+    if manifest.is_object() {
+        if !manifest["spell"].is_object() {
+            manifest["spell"] = serde_json::json!({});
+        }
+        manifest["spell"]["version"] = serde_json::json!(version);
+    }
+
+    let created: Option<SpellVersion> = sqlx::query_as(
+        r#"
+        INSERT INTO spell_versions (spell_id, version, wasm_path, manifest, digest, size_bytes)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (spell_id, version) DO NOTHING
+        RETURNING *
+        "#,
+    )
+    .bind(spell.id)
+    .bind(&version)
+    .bind(&digest)
+    .bind(&manifest)
+    .bind(&digest)
+    .bind(body.len() as i64)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to create spell version: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    let created = created
+        .ok_or_else(|| ApiError::Conflict(format!("{spell_name}@{version} already exists")))?;
+
+    log::info!("Spell {spell_name}@{version} published as {digest} by user {user_id}");
+
+    Ok(HttpResponse::Created().json(SpellVersionResponse::from(created)))
+}
+
+/// Deprecate a version: still castable, but casters get Deprecation/Sunset headers and a warning
+async fn deprecate_version(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    req: web::Json<DeprecateVersionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let (spell_name, version) = path.into_inner();
+    let spell = fetch_owned_spell(&state, user_id, &spell_name).await?;
+
+    if let Some(sunset) = req.sunset_at {
+        if sunset <= Utc::now() {
+            return Err(ApiError::InvalidRequest(
+                "sunset_at must be in the future".to_string(),
+            ));
+        }
+    }
+
+    let updated: Option<SpellVersion> = sqlx::query_as(
+        r#"
+        UPDATE spell_versions
+        SET status = 'deprecated', status_reason = $3, deprecated_at = NOW(),
+            sunset_at = $4, yanked_at = NULL
+        WHERE spell_id = $1 AND version = $2
+        RETURNING *
+        "#,
+    )
+    .bind(spell.id)
+    .bind(&version)
+    .bind(&req.reason)
+    .bind(req.sunset_at)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to deprecate spell version: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    version_status_response(updated, &spell_name, &version, "deprecated", user_id)
+}
+
+/// Yank a version: unpinned casts no longer resolve to it
+async fn yank_version(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    req: web::Json<YankVersionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let (spell_name, version) = path.into_inner();
+    let spell = fetch_owned_spell(&state, user_id, &spell_name).await?;
+
+    let updated: Option<SpellVersion> = sqlx::query_as(
+        r#"
+        UPDATE spell_versions
+        SET status = 'yanked', status_reason = $3, yanked_at = NOW()
+        WHERE spell_id = $1 AND version = $2
+        RETURNING *
+        "#,
+    )
+    .bind(spell.id)
+    .bind(&version)
+    .bind(&req.reason)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to yank spell version: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    version_status_response(updated, &spell_name, &version, "yanked", user_id)
+}
+
+/// Return a deprecated or yanked version to active
+async fn restore_version(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let (spell_name, version) = path.into_inner();
+    let spell = fetch_owned_spell(&state, user_id, &spell_name).await?;
+
+    let updated: Option<SpellVersion> = sqlx::query_as(
+        r#"
+        UPDATE spell_versions
+        SET status = 'active', status_reason = NULL, deprecated_at = NULL,
+            sunset_at = NULL, yanked_at = NULL
+        WHERE spell_id = $1 AND version = $2
+        RETURNING *
+        "#,
+    )
+    .bind(spell.id)
+    .bind(&version)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to restore spell version: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    version_status_response(updated, &spell_name, &version, "restored", user_id)
+}
+
+/// Mark a version pure (results cacheable) or not
+/// Marking it impure stops new results from being cached or served; existing entries expire.
+async fn set_version_purity(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    req: web::Json<SetVersionPurityRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let (spell_name, version) = path.into_inner();
+    let spell = fetch_owned_spell(&state, user_id, &spell_name).await?;
+
+    let updated: Option<SpellVersion> = sqlx::query_as(
+        r#"
+        UPDATE spell_versions
+        SET pure = $3
+        WHERE spell_id = $1 AND version = $2
+        RETURNING *
+        "#,
+    )
+    .bind(spell.id)
+    .bind(&version)
+    .bind(req.pure)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to set spell version purity: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    let action = if req.pure {
+        "marked pure"
+    } else {
+        "marked impure"
+    };
+    version_status_response(updated, &spell_name, &version, action, user_id)
+}
+
+fn version_status_response(
+    updated: Option<SpellVersion>,
+    spell_name: &str,
+    version: &str,
+    action: &str,
+    user_id: Uuid,
+) -> Result<HttpResponse, ApiError> {
+    let updated =
+        updated.ok_or_else(|| ApiError::NotFound("Spell version not found".to_string()))?;
+
+    log::info!("Spell {spell_name}@{version} {action} by user {user_id}");
+
+    Ok(HttpResponse::Ok().json(SpellVersionResponse::from(updated)))
+}
+
+/// Replay past casts of the creator's spell against one of its versions and diff the outputs
+/// Any version may be targeted, including yanked ones; replays are not recorded or billed.
+async fn replay_casts(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+    req: web::Json<ReplayRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let (spell_name, version) = path.into_inner();
+    let spell = fetch_owned_spell(&state, user_id, &spell_name).await?;
+
+    let version: SpellVersion =
+        sqlx::query_as("SELECT * FROM spell_versions WHERE spell_id = $1 AND version = $2")
+            .bind(spell.id)
+            .bind(&version)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to fetch spell version: {e}");
+                ApiError::Internal("Database error".to_string())
+            })?
+            .ok_or_else(|| ApiError::NotFound("Spell version not found".to_string()))?;
+
+    let report = ReplayService::replay(&state, &spell, &version, &req).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Circuit breaker state of each version of a spell and its recent transitions
+async fn get_spell_circuit(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let spell = fetch_owned_spell(&state, user_id, &path.into_inner()).await?;
+
+    let versions = fetch_versions(&state, &[spell.id]).await?;
+    let mut statuses = Vec::with_capacity(versions.len());
+    for version in &versions {
+        statuses.push(CircuitBreaker::status(&state.redis, version).await);
+    }
+
+    let events: Vec<SpellCircuitEvent> = sqlx::query_as(
+        r#"
+        SELECT v.version, e.event, e.reason, e.created_at
+        FROM spell_circuit_events e
+        JOIN spell_versions v ON v.id = e.spell_version_id
+        WHERE e.spell_id = $1
+        ORDER BY e.created_at DESC
+        LIMIT 50
+        "#,
+    )
+    .bind(spell.id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to fetch circuit events: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    Ok(HttpResponse::Ok().json(SpellCircuitReport {
+        spell_name: spell.name,
+        versions: statuses,
+        events,
+    }))
+}
+
+/// The spell's health check examples and the latest checks of its versions
+async fn get_spell_health(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let spell = fetch_owned_spell(&state, user_id, &path.into_inner()).await?;
+
+    let checks: Vec<SpellHealthCheck> = sqlx::query_as(
+        r#"
+        SELECT v.version, h.triggered_by, h.status, h.passed, h.failed, h.results, h.created_at
+        FROM spell_health_checks h
+        JOIN spell_versions v ON v.id = h.spell_version_id
+        WHERE h.spell_id = $1
+        ORDER BY h.created_at DESC
+        LIMIT 20
+        "#,
+    )
+    .bind(spell.id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to fetch health checks: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    Ok(HttpResponse::Ok().json(SpellHealthReport {
+        examples: manifest_examples(&spell.manifest),
+        spell_name: spell.name,
+        checks,
+    }))
+}
+
+/// Health stats for a spell, visible to its creator only
+async fn get_spell_stats(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<SpellStatsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let spell = fetch_owned_spell(&state, user_id, &path.into_inner()).await?;
+
+    let days = query
+        .days
+        .unwrap_or(DEFAULT_STATS_DAYS)
+        .clamp(1, MAX_STATS_DAYS);
+    let since = Utc::now() - Duration::days(days as i64);
+
+    let (total_casts, completed, failed, contract_violations, last_contract_violation_at): (
+        i64,
+        i64,
+        i64,
+        i64,
+        Option<DateTime<Utc>>,
+    ) = sqlx::query_as(
+        r#"
+        SELECT
+            COUNT(*) as total_casts,
+            COUNT(*) FILTER (WHERE status = 'COMPLETED') as completed,
+            COUNT(*) FILTER (WHERE status = 'FAILED') as failed,
+            COUNT(*) FILTER (WHERE error_code = 'OUTPUT_CONTRACT_VIOLATION') as contract_violations,
+            MAX(created_at) FILTER (WHERE error_code = 'OUTPUT_CONTRACT_VIOLATION') as last_contract_violation_at
+        FROM casts
+        WHERE spell_id = $1 AND created_at >= $2
+        "#,
+    )
+    .bind(spell.id)
+    .bind(since)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| {
+        log::error!("Failed to aggregate spell stats: {e}");
+        ApiError::Internal("Database error".to_string())
+    })?;
+
+    Ok(HttpResponse::Ok().json(SpellHealthStats {
+        spell_id: spell.id,
+        spell_name: spell.name,
+        window_days: days,
+        total_casts,
+        completed,
+        failed,
+        contract_violations,
+        last_contract_violation_at,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn price_decrease_applies_immediately() {
+        let now = Utc::now();
+        assert_eq!(schedule_price_change(100, 50, None, now), Ok(now));
+    }
+
+    #[test]
+    fn price_increase_requires_notice() {
+        let now = Utc::now();
+        assert!(schedule_price_change(100, 150, None, now).is_err());
+        assert!(schedule_price_change(100, 150, Some(now + Duration::days(1)), now).is_err());
+
+        let later = now + Duration::days(PRICE_INCREASE_NOTICE_DAYS);
+        assert_eq!(schedule_price_change(100, 150, Some(later), now), Ok(later));
+    }
+
+    #[test]
+    fn past_effective_dates_are_clamped_to_now() {
+        let now = Utc::now();
+        let past = now - Duration::days(3);
+        assert_eq!(schedule_price_change(100, 80, Some(past), now), Ok(now));
+        assert!(schedule_price_change(100, -1, None, now).is_err());
+    }
+
+    #[test]
+    fn examples_need_distinct_names() {
+        let example = |name: &str| SpellExample {
+            name: name.to_string(),
+            payload: serde_json::json!({}),
+            expected: None,
+        };
+        assert!(validate_examples(&[example("a"), example("b")]).is_ok());
+        assert!(validate_examples(&[example("a"), example("a")]).is_err());
+        assert!(validate_examples(&[example(" ")]).is_err());
+        assert!(validate_examples(&vec![example("a"); MAX_EXAMPLES + 1]).is_err());
+    }
+}
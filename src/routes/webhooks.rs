@@ -0,0 +1,205 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use actix_web_httpauth::middleware::HttpAuthentication;
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+use crate::models::webhook::{
+    DeliveryLogCursor, DeliveryLogPage, DeliveryLogQuery, WebhookDelivery, WebhookDeliveryDetail,
+    DELIVERY_DELIVERED, DELIVERY_FAILED, DELIVERY_PENDING,
+};
+use crate::models::User;
+use crate::services::webhook_service::WebhookService;
+use crate::utils::cursor::{decode_cursor, encode_cursor};
+use crate::AppState;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let auth = HttpAuthentication::bearer(crate::middleware::auth::validator);
+    cfg.service(
+        web::scope("/webhooks")
+            .wrap(auth)
+            .route("/secret", web::get().to(get_secret))
+            .route("/secret/rotate", web::post().to(rotate_secret))
+            .route("/deliveries", web::get().to(list_deliveries))
+            .route("/deliveries/{id}", web::get().to(get_delivery))
+            .route("/deliveries/{id}/redeliver", web::post().to(redeliver)),
+    );
+}
+
+fn authenticated_user_id(http_req: &HttpRequest) -> Result<Uuid, ApiError> {
+    let ext = http_req.extensions();
+    Ok(ext
+        .get::<User>()
+        .ok_or_else(|| ApiError::Unauthorized("User not authenticated".to_string()))?
+        .id)
+}
+
+fn db_error(e: sqlx::Error) -> ApiError {
+    log::error!("Webhook query failed: {e}");
+    ApiError::Internal("Database error".to_string())
+}
+
+/// The secret used to sign this user's webhooks (created on first request)
+async fn get_secret(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let secret = WebhookService::signing_secret(&user_id, &state.db)
+        .await
+        .map_err(db_error)?;
+    Ok(HttpResponse::Ok().json(secret))
+}
+
+async fn rotate_secret(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let secret = WebhookService::rotate_secret(&user_id, &state.db)
+        .await
+        .map_err(db_error)?;
+
+    log::info!("Webhook signing secret rotated for user {user_id}");
+
+    Ok(HttpResponse::Ok().json(secret))
+}
+
+/// Delivery log, newest first, optionally for a single cast or status
+async fn list_deliveries(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<DeliveryLogQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+
+    if let Some(status) = &query.status {
+        if ![DELIVERY_PENDING, DELIVERY_DELIVERED, DELIVERY_FAILED].contains(&status.as_str()) {
+            return Err(ApiError::InvalidRequest(
+                "status must be one of pending, delivered, failed".to_string(),
+            ));
+        }
+    }
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let cursor = match query.cursor.as_deref() {
+        Some(raw) => Some(
+            decode_cursor::<DeliveryLogCursor>(raw)
+                .ok_or_else(|| ApiError::InvalidRequest("Invalid cursor".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let mut qb: QueryBuilder<Postgres> =
+        QueryBuilder::new("SELECT * FROM webhook_deliveries WHERE user_id = ");
+    qb.push_bind(user_id);
+
+    if let Some(cast_id) = query.cast_id {
+        qb.push(" AND cast_id = ").push_bind(cast_id);
+    }
+    if let Some(status) = &query.status {
+        qb.push(" AND status = ").push_bind(status);
+    }
+    if let Some(c) = &cursor {
+        qb.push(" AND (created_at, id) < (")
+            .push_bind(c.created_at)
+            .push(", ")
+            .push_bind(c.id)
+            .push(")");
+    }
+
+    // Fetch one extra row to know whether another page exists
+    qb.push(" ORDER BY created_at DESC, id DESC LIMIT ")
+        .push_bind(limit + 1);
+
+    let mut deliveries: Vec<WebhookDelivery> = qb
+        .build_query_as()
+        .fetch_all(&state.db)
+        .await
+        .map_err(db_error)?;
+
+    let next_cursor = if deliveries.len() as i64 > limit {
+        deliveries.truncate(limit as usize);
+        deliveries.last().map(|last| {
+            encode_cursor(&DeliveryLogCursor {
+                created_at: last.created_at,
+                id: last.id,
+            })
+        })
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(DeliveryLogPage {
+        deliveries,
+        next_cursor,
+    }))
+}
+
+/// A single delivery with its payload and every attempt made
+async fn get_delivery(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+    let delivery_id = path.into_inner();
+
+    let detail: Option<WebhookDeliveryDetail> =
+        sqlx::query_as("SELECT * FROM webhook_deliveries WHERE id = $1 AND user_id = $2")
+            .bind(delivery_id)
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(db_error)?;
+    let mut detail = detail.ok_or_else(|| ApiError::NotFound("Delivery not found".to_string()))?;
+
+    detail.attempts_log = sqlx::query_as(
+        r#"
+        SELECT attempted_at, status_code, error, duration_ms
+        FROM webhook_delivery_attempts
+        WHERE delivery_id = $1
+        ORDER BY attempted_at
+        "#,
+    )
+    .bind(delivery_id)
+    .fetch_all(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    Ok(HttpResponse::Ok().json(detail))
+}
+
+/// Schedule a delivery to be sent again now, e.g. after fixing the receiving endpoint
+async fn redeliver(
+    state: web::Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let user_id = authenticated_user_id(&http_req)?;
+
+    let delivery: Option<WebhookDelivery> = sqlx::query_as(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = 'pending', attempts = 0, next_attempt_at = NOW()
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#,
+    )
+    .bind(path.into_inner())
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?;
+
+    let delivery = delivery.ok_or_else(|| ApiError::NotFound("Delivery not found".to_string()))?;
+
+    Ok(HttpResponse::Accepted().json(delivery))
+}
@@ -1,7 +1,8 @@
 use chrono::{DateTime, Datelike, Duration, Utc};
 use uuid::Uuid;
 
-use crate::models::billing::{Budget, BudgetExceededError};
+use crate::errors::CastError;
+use crate::models::billing::{Budget, BudgetExceededError, HardLimitStatus};
 
 pub struct BudgetService;
 
@@ -12,6 +13,38 @@ impl BudgetService {
         user_id: &Uuid,
         db: &sqlx::PgPool,
     ) -> Result<(), BudgetExceededError> {
+        let status = Self::hard_limit_status(user_id, db).await.map_err(|e| {
+            log::error!("Failed to fetch budget status: {e}");
+            BudgetExceededError::new("monthly".to_string(), 0, 0)
+        })?;
+        let Some(status) = status else {
+            return Ok(());
+        };
+
+        if status.spent_cents >= status.hard_limit_cents {
+            log::warn!(
+                "User {} exceeded hard limit: {} >= {} (period: {})",
+                user_id,
+                status.spent_cents,
+                status.hard_limit_cents,
+                status.period
+            );
+            return Err(BudgetExceededError::new(
+                status.period,
+                status.hard_limit_cents,
+                status.spent_cents,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Spend so far against the user's hard limit this period, including active reservations
+    /// Returns None when the user has no budget or no hard limit.
+    pub async fn hard_limit_status(
+        user_id: &Uuid,
+        db: &sqlx::PgPool,
+    ) -> Result<Option<HardLimitStatus>, sqlx::Error> {
         // Get user's budget
         let budget: Option<Budget> = sqlx::query_as(
             r#"
@@ -20,22 +53,18 @@ impl BudgetService {
         )
         .bind(user_id)
         .fetch_optional(db)
-        .await
-        .map_err(|e| {
-            log::error!("Failed to fetch budget: {e}");
-            BudgetExceededError::new("monthly".to_string(), 0, 0)
-        })?;
+        .await?;
 
         // If no budget set, allow
         let budget = match budget {
             Some(b) => b,
-            None => return Ok(()),
+            None => return Ok(None),
         };
 
         // If no hard limit set, allow
         let hard_limit = match budget.hard_limit_cents {
             Some(limit) => limit,
-            None => return Ok(()),
+            None => return Ok(None),
         };
 
         // Calculate current period window
@@ -45,10 +74,14 @@ impl BudgetService {
             _ => Self::monthly_window(),
         };
 
-        // Get current spending in this period
+        // Get current spending in this period, counting spend held by active reservations
         let spent: Option<(i32,)> = sqlx::query_as(
             r#"
-            SELECT COALESCE(SUM(cost_cents), 0) as total
+            SELECT (COALESCE(SUM(cost_cents), 0) + (
+                SELECT COALESCE(SUM(amount_cents), 0)
+                FROM budget_reservations
+                WHERE user_id = $1 AND released_at IS NULL AND expires_at > NOW()
+            ))::INT as total
             FROM usage_counters
             WHERE user_id = $1
               AND window_start >= $2
@@ -59,29 +92,123 @@ impl BudgetService {
         .bind(window_start)
         .bind(window_end)
         .fetch_optional(db)
-        .await
-        .map_err(|e| {
-            log::error!("Failed to fetch usage: {e}");
-            BudgetExceededError::new(budget.period.clone(), hard_limit, 0)
-        })?;
+        .await?;
+
+        Ok(Some(HardLimitStatus {
+            period: budget.period,
+            hard_limit_cents: hard_limit,
+            spent_cents: spent.map(|(total,)| total).unwrap_or(0),
+        }))
+    }
+
+    /// Hold `amount_cents` against the user's hard limit for work spanning many casts
+    /// Returns None when the user has no hard limit (nothing to hold). The reservation
+    /// must be released once the work finishes; actual usage is recorded per cast, with
+    /// the reservation drawn down as it is.
+    pub async fn reserve(
+        user_id: &Uuid,
+        amount_cents: i32,
+        db: &sqlx::PgPool,
+    ) -> Result<Option<Uuid>, CastError> {
+        let mut tx = db.begin().await?;
+
+        // Lock the budget row so concurrent reservations are checked one at a time
+        let budget: Option<Budget> = sqlx::query_as(
+            r#"
+            SELECT * FROM budgets WHERE user_id = $1 FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
 
-        let spent_cents = spent.map(|(total,)| total).unwrap_or(0);
+        let Some(budget) = budget else {
+            return Ok(None);
+        };
+        let Some(hard_limit) = budget.hard_limit_cents else {
+            return Ok(None);
+        };
 
-        if spent_cents >= hard_limit {
+        let (window_start, window_end) = match budget.period.as_str() {
+            "daily" => Self::daily_window(),
+            _ => Self::monthly_window(),
+        };
+
+        let (committed,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT (
+                (SELECT COALESCE(SUM(cost_cents), 0) FROM usage_counters
+                 WHERE user_id = $1 AND window_start >= $2 AND window_end <= $3)
+              + (SELECT COALESCE(SUM(amount_cents), 0) FROM budget_reservations
+                 WHERE user_id = $1 AND released_at IS NULL AND expires_at > NOW())
+            )::BIGINT
+            "#,
+        )
+        .bind(user_id)
+        .bind(window_start)
+        .bind(window_end)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if committed + i64::from(amount_cents) > i64::from(hard_limit) {
             log::warn!(
-                "User {} exceeded hard limit: {} >= {} (period: {})",
-                user_id,
-                spent_cents,
-                hard_limit,
-                budget.period
+                "User {user_id} cannot reserve {amount_cents} cents: {committed} committed of {hard_limit}"
             );
-            return Err(BudgetExceededError::new(
+            return Err(CastError::BudgetExceeded(BudgetExceededError::new(
                 budget.period,
                 hard_limit,
-                spent_cents,
-            ));
+                committed.min(i64::from(i32::MAX)) as i32,
+            )));
         }
 
+        let reservation_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO budget_reservations (user_id, amount_cents, expires_at)
+            VALUES ($1, $2, NOW() + INTERVAL '1 hour')
+            RETURNING id
+            "#,
+        )
+        .bind(user_id)
+        .bind(amount_cents)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(reservation_id))
+    }
+
+    /// Give back `amount_cents` of a reservation once the cast it covered has finished
+    /// By then its usage is recorded (or it failed unbilled), so holding its share any
+    /// longer would count the same spend twice.
+    pub async fn draw_down(
+        reservation_id: &Uuid,
+        amount_cents: i32,
+        db: &sqlx::PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE budget_reservations SET amount_cents = GREATEST(amount_cents - $2, 0)
+            WHERE id = $1 AND released_at IS NULL
+            "#,
+        )
+        .bind(reservation_id)
+        .bind(amount_cents)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Release a reservation made by `reserve`
+    pub async fn release(reservation_id: &Uuid, db: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE budget_reservations SET released_at = NOW() WHERE id = $1
+            "#,
+        )
+        .bind(reservation_id)
+        .execute(db)
+        .await?;
         Ok(())
     }
 
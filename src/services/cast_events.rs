@@ -0,0 +1,240 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use uuid::Uuid;
+
+use crate::errors::Problem;
+
+// Events a slow reader may fall behind by
+const EVENT_BUFFER: usize = 256;
+// Slots log and output may not fill, kept for status and result events
+const RESERVED_SLOTS: usize = 16;
+// Log and output bytes a single cast may stream; the rest is dropped
+const MAX_STREAMED_BYTES: usize = 1024 * 1024;
+
+/// Progress of a single cast, as streamed to the caller over Server-Sent Events
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CastEvent {
+    /// The cast moved to a new status (QUEUED, RUNNING, RETRYING)
+    State {
+        cast_id: Uuid,
+        status: &'static str,
+        attempt: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry_in_ms: Option<u64>,
+    },
+    /// A line the spell wrote with the `spell.log` host function
+    Log { line: String },
+    /// A piece of output the spell emitted with the `spell.emit_output` host function
+    Output { chunk: String },
+    /// The final result and what the cast cost; always the last event of a successful cast
+    Result {
+        cast_id: Uuid,
+        result: Value,
+        attempts: i32,
+        cached: bool,
+        cost_cents: i32,
+    },
+    /// The cast failed or was canceled; always the last event of an unsuccessful cast
+    Error(Problem),
+    /// A WebSocket session opened or closed, with what it has cost so far
+    Session {
+        session_id: Uuid,
+        status: &'static str,
+        messages: i32,
+        cost_cents: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+}
+
+impl CastEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            CastEvent::State { .. } => "state",
+            CastEvent::Log { .. } => "log",
+            CastEvent::Output { .. } => "output",
+            CastEvent::Result { .. } => "result",
+            CastEvent::Error(_) => "error",
+            CastEvent::Session { .. } => "session",
+        }
+    }
+
+    /// Frame the event for a `text/event-stream` response
+    pub fn to_sse(&self) -> String {
+        let data = match self {
+            // Problems keep their RFC 7807 shape; the event name already says what this is
+            CastEvent::Error(problem) => serde_json::to_string(problem),
+            event => serde_json::to_string(event),
+        }
+        .unwrap_or_default();
+        format!("event: {}\ndata: {data}\n\n", self.name())
+    }
+}
+
+/// Where a cast reports its progress; a no-op unless someone is streaming it
+/// Sending never blocks, so it is safe from the WASM thread. The buffer is bounded:
+/// log and output events are dropped when the reader falls behind or the cast has
+/// streamed its budget, and a notice says how many were lost.
+#[derive(Clone, Default)]
+pub struct EventSink(Option<Arc<Stream>>);
+
+struct Stream {
+    tx: Sender<CastEvent>,
+    streamed_bytes: AtomicUsize,
+    exhausted: AtomicBool,
+    dropped: AtomicUsize,
+}
+
+impl EventSink {
+    pub fn channel() -> (Self, Receiver<CastEvent>) {
+        let (tx, rx) = channel(EVENT_BUFFER);
+        let stream = Stream {
+            tx,
+            streamed_bytes: AtomicUsize::new(0),
+            exhausted: AtomicBool::new(false),
+            dropped: AtomicUsize::new(0),
+        };
+        (Self(Some(Arc::new(stream))), rx)
+    }
+
+    /// Start a fresh streaming budget, for sinks that outlive one cast
+    pub fn next_cast(&self) {
+        if let Some(stream) = &self.0 {
+            stream.streamed_bytes.store(0, Ordering::SeqCst);
+            stream.exhausted.store(false, Ordering::SeqCst);
+        }
+    }
+
+    pub fn emit(&self, event: CastEvent) {
+        let Some(stream) = &self.0 else {
+            return;
+        };
+        let size = match &event {
+            CastEvent::Log { line } => line.len(),
+            CastEvent::Output { chunk } => chunk.len(),
+            _ => {
+                // The caller may have disconnected; the cast carries on regardless
+                if let Err(tokio::sync::mpsc::error::TrySendError::Full(event)) =
+                    stream.tx.try_send(event)
+                {
+                    log::warn!("Event stream full; dropped a {} event", event.name());
+                }
+                return;
+            }
+        };
+
+        if stream.tx.capacity() <= RESERVED_SLOTS {
+            stream.dropped.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+        if stream.streamed_bytes.fetch_add(size, Ordering::SeqCst) + size > MAX_STREAMED_BYTES {
+            if !stream.exhausted.swap(true, Ordering::SeqCst) {
+                let _ = stream.tx.try_send(CastEvent::Log {
+                    line: format!(
+                        "[stream limit of {MAX_STREAMED_BYTES} bytes reached; further log and output dropped]"
+                    ),
+                });
+            }
+            return;
+        }
+        let dropped = stream.dropped.swap(0, Ordering::SeqCst);
+        if dropped > 0 {
+            let _ = stream.tx.try_send(CastEvent::Log {
+                line: format!("[{dropped} log or output event(s) dropped: reader too slow]"),
+            });
+        }
+        let _ = stream.tx.try_send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_framed_as_sse() {
+        let framed = CastEvent::Log {
+            line: "warming up".to_string(),
+        }
+        .to_sse();
+        assert_eq!(
+            framed,
+            "event: log\ndata: {\"event\":\"log\",\"line\":\"warming up\"}\n\n"
+        );
+
+        let (sink, mut rx) = EventSink::channel();
+        sink.emit(CastEvent::Output {
+            chunk: "partial".to_string(),
+        });
+        assert!(matches!(rx.try_recv(), Ok(CastEvent::Output { chunk }) if chunk == "partial"));
+
+        drop(rx);
+        sink.emit(CastEvent::Log {
+            line: "caller went away".to_string(),
+        });
+        EventSink::default().emit(CastEvent::Log {
+            line: "nobody listens".to_string(),
+        });
+    }
+
+    fn log(line: &str) -> CastEvent {
+        CastEvent::Log {
+            line: line.to_string(),
+        }
+    }
+
+    #[test]
+    fn slow_readers_lose_logs_but_not_results() {
+        let (sink, mut rx) = EventSink::channel();
+        for i in 0..EVENT_BUFFER {
+            sink.emit(log(&i.to_string()));
+        }
+        sink.emit(CastEvent::Error(
+            crate::errors::CastError::WasmTimeout.problem(),
+        ));
+
+        let mut logs = 0;
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                CastEvent::Log { .. } => logs += 1,
+                CastEvent::Error(_) => break,
+                other => panic!("unexpected {} event", other.name()),
+            }
+        }
+        assert_eq!(logs, EVENT_BUFFER - RESERVED_SLOTS);
+
+        // The next line that fits is preceded by a count of what was lost
+        sink.emit(log("caught up"));
+        assert!(
+            matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line.contains("16 log or output event(s) dropped"))
+        );
+        assert!(matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line == "caught up"));
+    }
+
+    #[test]
+    fn casts_stream_at_most_their_budget() {
+        let (sink, mut rx) = EventSink::channel();
+        let line = "x".repeat(MAX_STREAMED_BYTES / 2);
+        for _ in 0..4 {
+            sink.emit(log(&line));
+        }
+        assert!(
+            matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line.len() == MAX_STREAMED_BYTES / 2)
+        );
+        assert!(
+            matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line.len() == MAX_STREAMED_BYTES / 2)
+        );
+        assert!(
+            matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line.contains("stream limit"))
+        );
+        assert!(rx.try_recv().is_err());
+
+        sink.next_cast();
+        sink.emit(log("next message"));
+        assert!(matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line == "next message"));
+    }
+}
@@ -0,0 +1,306 @@
+use actix_web::web;
+use deadpool_redis::Pool;
+use std::env;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::errors::CastError;
+use crate::middleware::request_id;
+use crate::services::cast_service::CastService;
+use crate::services::webhook_service::WebhookService;
+use crate::AppState;
+
+const QUEUE_KEY: &str = "cast:queue";
+// Jobs a worker has taken but not finished; one list per worker
+const PROCESSING_KEY_PREFIX: &str = "cast:processing";
+// How long a worker blocks on an empty queue before re-polling
+const POLL_TIMEOUT_SECS: u64 = 5;
+// Back-off after a Redis error so an outage doesn't turn into a hot loop
+const ERROR_BACKOFF: Duration = Duration::from_secs(2);
+// Running casts are touched this often by their worker
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+// Async casts untouched for this long were lost with their worker (or never queued)
+const STALE_AFTER_SECS: i64 = 120;
+const REAPER_INTERVAL: Duration = Duration::from_secs(30);
+const REAPER_BATCH_SIZE: i64 = 100;
+// A cast whose worker was lost this many times is failed rather than run again
+const MAX_REQUEUES: i32 = 2;
+
+/// Redis-backed queue of asynchronous casts
+/// Jobs carry only the cast id; everything else is read from the `casts` row. Workers move
+/// each job onto their own processing list and drop it from there once the cast is done,
+/// and a reaper puts casts whose worker died (or that never reached the queue) back on it.
+pub struct CastQueue;
+
+impl CastQueue {
+    pub async fn enqueue(redis: &Pool, cast_id: &Uuid) -> Result<(), anyhow::Error> {
+        let mut conn = redis.get().await?;
+        redis::cmd("LPUSH")
+            .arg(QUEUE_KEY)
+            .arg(cast_id.to_string())
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Drop a queued job, e.g. after its cast was canceled
+    /// Workers skip casts that are no longer QUEUED anyway; this just keeps the queue short.
+    pub async fn remove(redis: &Pool, cast_id: &Uuid) -> Result<(), anyhow::Error> {
+        let mut conn = redis.get().await?;
+        redis::cmd("LREM")
+            .arg(QUEUE_KEY)
+            .arg(0)
+            .arg(cast_id.to_string())
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Start `count` workers consuming the queue, and the reaper, for the lifetime of the process
+    pub fn spawn_workers(state: web::Data<AppState>, count: usize) {
+        // Stable across restarts of the same host, so a restarted worker finds its leftovers
+        let host = env::var("HOSTNAME").unwrap_or_else(|_| Uuid::new_v4().to_string());
+        for worker in 0..count {
+            let state = state.clone();
+            let processing = format!("{PROCESSING_KEY_PREFIX}:{host}:{worker}");
+            tokio::spawn(async move { Self::work(state, worker, processing).await });
+        }
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(REAPER_INTERVAL).await;
+                if let Err(e) = Self::reap(&state).await {
+                    log::error!("Cast reaper failed: {e}");
+                }
+            }
+        });
+    }
+
+    async fn work(state: web::Data<AppState>, worker: usize, processing: String) {
+        log::info!("Cast worker {worker} started");
+
+        // Jobs left by this worker's previous life go back on the queue; casts among them
+        // that were already running are left to the reaper
+        match Self::restore(&state.redis, &processing).await {
+            Ok(0) => {}
+            Ok(n) => log::warn!("Cast worker {worker} requeued {n} unfinished job(s)"),
+            Err(e) => log::error!("Cast worker {worker} failed to requeue unfinished jobs: {e}"),
+        }
+
+        loop {
+            let job = match Self::dequeue(&state.redis, &processing).await {
+                Ok(Some(job)) => job,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::error!("Cast worker {worker} failed to poll queue: {e}");
+                    tokio::time::sleep(ERROR_BACKOFF).await;
+                    continue;
+                }
+            };
+
+            match job.parse() {
+                Ok(cast_id) => Self::process(&state, &cast_id).await,
+                Err(_) => log::warn!("Dropping malformed cast job: {job}"),
+            }
+            if let Err(e) = Self::ack(&state.redis, &processing, &job).await {
+                log::error!("Cast worker {worker} failed to acknowledge job {job}: {e}");
+            }
+        }
+    }
+
+    /// Move the next job onto the worker's processing list
+    async fn dequeue(redis: &Pool, processing: &str) -> Result<Option<String>, anyhow::Error> {
+        let mut conn = redis.get().await?;
+        let job: Option<String> = redis::cmd("BLMOVE")
+            .arg(QUEUE_KEY)
+            .arg(processing)
+            .arg("RIGHT")
+            .arg("LEFT")
+            .arg(POLL_TIMEOUT_SECS)
+            .query_async(&mut *conn)
+            .await?;
+        Ok(job)
+    }
+
+    async fn ack(redis: &Pool, processing: &str, job: &str) -> Result<(), anyhow::Error> {
+        let mut conn = redis.get().await?;
+        redis::cmd("LREM")
+            .arg(processing)
+            .arg(1)
+            .arg(job)
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Put every job on a processing list back on the queue; returns how many were moved
+    async fn restore(redis: &Pool, processing: &str) -> Result<usize, anyhow::Error> {
+        let mut conn = redis.get().await?;
+        let mut moved = 0;
+        loop {
+            let job: Option<String> = redis::cmd("LMOVE")
+                .arg(processing)
+                .arg(QUEUE_KEY)
+                .arg("RIGHT")
+                .arg("RIGHT")
+                .query_async(&mut *conn)
+                .await?;
+            match job {
+                Some(_) => moved += 1,
+                None => return Ok(moved),
+            }
+        }
+    }
+
+    /// Requeue async casts that went stale, and fail those lost too often
+    /// Stale QUEUED casts never reached the queue, were dropped from it, or are still waiting
+    /// in it (those aren't pushed again); stale RUNNING casts lost their worker mid-run.
+    /// Claims are atomic, so any instance may reap, and a cast pushed twice still runs once
+    /// since workers only claim QUEUED casts. A requeue bumps `requeue_count`, so a worker
+    /// that was only slow can no longer complete (and bill) the cast it lost.
+    async fn reap(state: &web::Data<AppState>) -> Result<(), anyhow::Error> {
+        let lost: Vec<(Uuid, i32)> = sqlx::query_as(
+            r#"
+            SELECT id, requeue_count FROM casts
+            WHERE run_async AND status = 'RUNNING' AND requeue_count >= $3
+              AND heartbeat_at < NOW() - make_interval(secs => $2)
+            LIMIT $1
+            "#,
+        )
+        .bind(REAPER_BATCH_SIZE)
+        .bind(STALE_AFTER_SECS as f64)
+        .bind(MAX_REQUEUES)
+        .fetch_all(&state.db)
+        .await?;
+
+        let error = CastError::InternalError("Cast worker was lost".to_string());
+        for (cast_id, requeue_count) in lost {
+            // Fails only if still RUNNING, so each lost cast is failed once
+            if CastService::fail(&cast_id, requeue_count, &error, &state.db)
+                .await
+                .is_ok()
+            {
+                log::error!("Cast {cast_id} failed: its worker was lost {MAX_REQUEUES} times");
+                if let Err(e) = WebhookService::enqueue_for_cast(&cast_id, &state.db).await {
+                    log::error!("Failed to queue webhook for cast {cast_id}: {e}");
+                }
+            }
+        }
+
+        let stale: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            UPDATE casts
+            SET requeue_count = requeue_count + (status = 'RUNNING')::int,
+                status = 'QUEUED', started_at = NULL, heartbeat_at = NOW()
+            WHERE id IN (
+                SELECT id FROM casts
+                WHERE run_async AND status IN ('QUEUED', 'RUNNING') AND requeue_count < $3
+                  AND heartbeat_at < NOW() - make_interval(secs => $2)
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id
+            "#,
+        )
+        .bind(REAPER_BATCH_SIZE)
+        .bind(STALE_AFTER_SECS as f64)
+        .bind(MAX_REQUEUES)
+        .fetch_all(&state.db)
+        .await?;
+
+        // A cast stuck behind a long queue is stale too, but already has its job
+        for (cast_id,) in stale {
+            if Self::is_queued(&state.redis, &cast_id).await? {
+                continue;
+            }
+            log::warn!("Requeuing stale cast {cast_id}");
+            Self::enqueue(&state.redis, &cast_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn is_queued(redis: &Pool, cast_id: &Uuid) -> Result<bool, anyhow::Error> {
+        let mut conn = redis.get().await?;
+        let position: Option<i64> = redis::cmd("LPOS")
+            .arg(QUEUE_KEY)
+            .arg(cast_id.to_string())
+            .query_async(&mut *conn)
+            .await?;
+        Ok(position.is_some())
+    }
+
+    async fn process(state: &web::Data<AppState>, cast_id: &Uuid) {
+        // Loaded before the claim, so the claim is on the requeue_count this run holds
+        let cast = match CastService::load(cast_id, &state.db).await {
+            Ok(Some(cast)) => cast,
+            Ok(None) => {
+                log::error!("Cast {cast_id} references a missing spell or version");
+                Self::fail_orphan(state, cast_id).await;
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to load cast {cast_id}: {e}");
+                Self::fail_orphan(state, cast_id).await;
+                return;
+            }
+        };
+
+        match CastService::mark_running(&cast, &state.db).await {
+            Ok(true) => {}
+            Ok(false) => {
+                log::warn!("Skipping cast {cast_id}: not queued (duplicate or already handled)");
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to claim cast {cast_id}: {e}");
+                return;
+            }
+        }
+
+        // The outcome is recorded on the casts row; errors are already logged
+        let heartbeat = tokio::spawn(Self::heartbeat(state.clone(), *cast_id, cast.requeue_count));
+        let request_id = cast.request_id.clone();
+        let _ = request_id::scope(request_id, CastService::execute(state, &cast)).await;
+        heartbeat.abort();
+    }
+
+    /// Keep touching a running cast so the reaper knows its worker is alive
+    async fn heartbeat(state: web::Data<AppState>, cast_id: Uuid, requeue_count: i32) {
+        let mut ticks = tokio::time::interval(HEARTBEAT_INTERVAL);
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            if let Err(e) = sqlx::query(
+                r#"
+                UPDATE casts SET heartbeat_at = NOW()
+                WHERE id = $1 AND status = 'RUNNING' AND requeue_count = $2
+                "#,
+            )
+            .bind(cast_id)
+            .bind(requeue_count)
+            .execute(&state.db)
+            .await
+            {
+                log::warn!("Failed to record heartbeat of cast {cast_id}: {e}");
+            }
+        }
+    }
+
+    /// Mark a queued cast FAILED when it can't be executed at all
+    async fn fail_orphan(state: &web::Data<AppState>, cast_id: &Uuid) {
+        if let Err(e) = sqlx::query(
+            r#"
+            UPDATE casts
+            SET status = 'FAILED', error_code = 'INTERNAL_ERROR',
+                error_message = 'Cast could not be loaded for execution', finished_at = NOW()
+            WHERE id = $1 AND status = 'QUEUED'
+            "#,
+        )
+        .bind(cast_id)
+        .execute(&state.db)
+        .await
+        {
+            log::error!("Failed to mark cast {cast_id} as failed: {e}");
+        }
+    }
+}
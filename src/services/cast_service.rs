@@ -0,0 +1,720 @@
+use actix_web::web;
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::errors::CastError;
+use crate::middleware::request_id;
+use crate::models::spell::SpellVersion;
+use crate::models::{CastRequest, InputFile, Spell};
+use crate::services::budget_service::BudgetService;
+use crate::services::cast_events::{CastEvent, EventSink};
+use crate::services::cast_queue::CastQueue;
+use crate::services::circuit_breaker::CircuitBreaker;
+use crate::services::contract_service::ContractService;
+use crate::services::result_cache::ResultCache;
+use crate::services::spell_service::SpellService;
+use crate::services::webhook_service::WebhookService;
+use crate::storage::get_verified_blob;
+use crate::wasm::{CancelToken, InputFileData, SpellInput};
+use crate::AppState;
+
+// How often a running cast checks whether it has been canceled
+const CANCEL_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// A cast that has been accepted and recorded, ready to run
+pub struct PreparedCast {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub spell: Spell,
+    pub version: SpellVersion,
+    pub payload: Value,
+    /// Files sent alongside the payload, stored in the blob store
+    pub input_files: Vec<InputFile>,
+    pub callback_url: Option<String>,
+    pub bypass_cache: bool,
+    /// Request that created the cast, restored as the log context when a worker runs it
+    pub request_id: Option<String>,
+    /// Charged if the cast completes; quoted when the cast is recorded or loaded
+    pub cost_cents: i32,
+    /// The row's `requeue_count` when recorded or loaded: the claim this run holds
+    /// A requeue by the reaper bumps the count, so a run it gave up on can't finish the cast.
+    pub requeue_count: i32,
+}
+
+/// Per-cast settings chosen by the caller rather than the spell
+#[derive(Debug, Default)]
+pub struct CastOptions {
+    /// Already-validated webhook target
+    pub callback_url: Option<String>,
+    pub batch_id: Option<Uuid>,
+    /// Always execute, even if a cached result exists for a pure version
+    pub bypass_cache: bool,
+    /// WebSocket session the cast is a message of
+    pub session_id: Option<Uuid>,
+    /// Files already written to the blob store, passed to the spell with the payload
+    pub input_files: Vec<InputFile>,
+    /// Pipeline run the cast is a step of, and which step
+    pub pipeline_run_id: Option<Uuid>,
+    pub pipeline_step: Option<String>,
+    /// Schedule the cast is a run of
+    pub schedule_id: Option<Uuid>,
+    /// Executed by the queue workers rather than within the request
+    pub run_async: bool,
+}
+
+/// Output of a completed cast, how many attempts it took and what it cost
+pub struct Execution {
+    pub output: Value,
+    /// 0 when served from the result cache
+    pub attempts: i32,
+    pub cached: bool,
+    pub cost_cents: i32,
+}
+
+#[derive(sqlx::FromRow)]
+struct CastRow {
+    user_id: Option<Uuid>,
+    spell_id: Option<Uuid>,
+    spell_version_id: Option<Uuid>,
+    payload: Value,
+    input_files: sqlx::types::Json<Vec<InputFile>>,
+    callback_url: Option<String>,
+    bypass_cache: bool,
+    request_id: Option<String>,
+    requeue_count: i32,
+}
+
+pub struct CastService;
+
+impl CastService {
+    /// Validate a cast request and record it as QUEUED
+    /// Enforces the caller's hard budget limit and resolves the version to run.
+    /// The casts row is inserted last, so on error nothing was recorded.
+    pub async fn prepare(
+        user_id: &Uuid,
+        req: &CastRequest,
+        options: CastOptions,
+        db: &PgPool,
+    ) -> Result<PreparedCast, CastError> {
+        // Check budget hard limit BEFORE execution
+        if let Err(budget_err) = BudgetService::check_hard_limit(user_id, db).await {
+            log::warn!("Budget exceeded for user {user_id}: {budget_err:?}");
+            return Err(CastError::BudgetExceeded(budget_err));
+        }
+
+        let (spell, version) = Self::resolve(&req.spell_name, req.version.as_deref(), db).await?;
+
+        Self::record(user_id, &spell, &version, &req.payload, options, db).await
+    }
+
+    /// Look up an active spell and the version a cast of it should run
+    pub async fn resolve(
+        spell_name: &str,
+        pinned: Option<&str>,
+        db: &PgPool,
+    ) -> Result<(Spell, SpellVersion), CastError> {
+        let spell: Option<Spell> = sqlx::query_as(
+            r#"
+            SELECT * FROM spells WHERE name = $1 AND is_active = true
+            "#,
+        )
+        .bind(spell_name)
+        .fetch_optional(db)
+        .await?;
+
+        let spell = spell.ok_or_else(|| CastError::SpellNotFound(spell_name.to_string()))?;
+
+        // Resolve the version to run (pinned, or newest non-yanked)
+        let version = SpellService::resolve_version(&spell.id, pinned, db)
+            .await?
+            .ok_or_else(|| match pinned {
+                Some(v) => CastError::WasmNotFound(format!("{spell_name}@{v}")),
+                None => CastError::WasmNotFound(format!("{spell_name} (no castable version)")),
+            })?;
+
+        Ok((spell, version))
+    }
+
+    /// Check a payload against the version's declared input schema, if any
+    pub fn validate_payload(version: &SpellVersion, payload: &Value) -> Result<(), CastError> {
+        match version.input_schema() {
+            Some(schema) => ContractService::validate_input(schema, payload),
+            None => Ok(()),
+        }
+    }
+
+    /// Check a payload the way every cast is checked before it is recorded
+    pub fn check_payload(
+        spell: &Spell,
+        version: &SpellVersion,
+        payload: &Value,
+        files: &[InputFile],
+    ) -> Result<(), CastError> {
+        Self::check_input_size(spell, payload, files)?;
+        Self::validate_payload(version, payload)
+    }
+
+    /// Enforce the spell's limit on the combined size of the payload and input files
+    pub fn check_input_size(
+        spell: &Spell,
+        payload: &Value,
+        files: &[InputFile],
+    ) -> Result<(), CastError> {
+        let payload_bytes = serde_json::to_vec(payload).map_or(0, |bytes| bytes.len());
+        let file_bytes: usize = files.iter().map(|file| file.size_bytes as usize).sum();
+        let size_bytes = payload_bytes + file_bytes;
+        if size_bytes > spell.max_input_bytes as usize {
+            return Err(CastError::InputTooLarge {
+                size_bytes,
+                limit_bytes: spell.max_input_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Insert the QUEUED casts row for a resolved spell version
+    pub async fn record(
+        user_id: &Uuid,
+        spell: &Spell,
+        version: &SpellVersion,
+        payload: &Value,
+        options: CastOptions,
+        db: &PgPool,
+    ) -> Result<PreparedCast, CastError> {
+        Self::check_payload(spell, version, payload, &options.input_files)?;
+
+        let cast_id = Uuid::new_v4();
+        let request_id = request_id::current();
+
+        // Insert initial record with user_id, spell_id and the resolved version
+        sqlx::query(
+            r#"
+            INSERT INTO casts (id, spell_name, payload, status, user_id, spell_id, spell_version_id, callback_url, batch_id, bypass_cache, request_id, session_id, input_files, pipeline_run_id, pipeline_step, schedule_id, run_async, heartbeat_at, created_at)
+            VALUES ($1, $2, $3, 'QUEUED', $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, NOW(), NOW())
+            "#,
+        )
+        .bind(cast_id)
+        .bind(&spell.name)
+        .bind(payload)
+        .bind(user_id)
+        .bind(spell.id)
+        .bind(version.id)
+        .bind(&options.callback_url)
+        .bind(options.batch_id)
+        .bind(options.bypass_cache)
+        .bind(&request_id)
+        .bind(options.session_id)
+        .bind(sqlx::types::Json(&options.input_files))
+        .bind(options.pipeline_run_id)
+        .bind(&options.pipeline_step)
+        .bind(options.schedule_id)
+        .bind(options.run_async)
+        .execute(db)
+        .await?;
+
+        Ok(PreparedCast {
+            id: cast_id,
+            user_id: *user_id,
+            spell: spell.clone(),
+            version: version.clone(),
+            payload: payload.clone(),
+            input_files: options.input_files,
+            callback_url: options.callback_url,
+            bypass_cache: options.bypass_cache,
+            request_id,
+            cost_cents: spell.effective_price_cents(chrono::Utc::now()),
+            requeue_count: 0,
+        })
+    }
+
+    /// Move a cast from QUEUED to RUNNING
+    /// Returns false if it was already claimed (or requeued since it was loaded), so each
+    /// cast runs at most once per claim.
+    pub async fn mark_running(cast: &PreparedCast, db: &PgPool) -> Result<bool, CastError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE casts
+            SET status = 'RUNNING', started_at = NOW(), heartbeat_at = NOW()
+            WHERE id = $1 AND status = 'QUEUED' AND requeue_count = $2
+            "#,
+        )
+        .bind(cast.id)
+        .bind(cast.requeue_count)
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Reload a recorded cast together with the spell and version it was resolved to
+    pub async fn load(cast_id: &Uuid, db: &PgPool) -> Result<Option<PreparedCast>, CastError> {
+        let row: Option<CastRow> = sqlx::query_as(
+            r#"
+            SELECT user_id, spell_id, spell_version_id, payload, input_files, callback_url, bypass_cache, request_id, requeue_count
+            FROM casts WHERE id = $1
+            "#,
+        )
+        .bind(cast_id)
+        .fetch_optional(db)
+        .await?;
+
+        let Some(CastRow {
+            user_id: Some(user_id),
+            spell_id: Some(spell_id),
+            spell_version_id: Some(version_id),
+            payload,
+            input_files,
+            callback_url,
+            bypass_cache,
+            request_id,
+            requeue_count,
+        }) = row
+        else {
+            return Ok(None);
+        };
+
+        let spell: Option<Spell> = sqlx::query_as("SELECT * FROM spells WHERE id = $1")
+            .bind(spell_id)
+            .fetch_optional(db)
+            .await?;
+        let version: Option<SpellVersion> =
+            sqlx::query_as("SELECT * FROM spell_versions WHERE id = $1")
+                .bind(version_id)
+                .fetch_optional(db)
+                .await?;
+
+        Ok(spell.zip(version).map(|(spell, version)| PreparedCast {
+            id: *cast_id,
+            user_id,
+            cost_cents: spell.effective_price_cents(chrono::Utc::now()),
+            spell,
+            version,
+            payload,
+            input_files: input_files.0,
+            callback_url,
+            bypass_cache,
+            request_id,
+            requeue_count,
+        }))
+    }
+
+    /// Run a RUNNING cast, record its outcome and notify its callback URL
+    /// Retryable failures are retried per the spell's retry policy, recording every attempt.
+    /// Pure versions are served from the result cache when possible, at the spell's cache discount.
+    /// Successful casts are charged once at the spell's current price; failed and canceled
+    /// casts are never charged.
+    pub async fn execute(
+        state: &web::Data<AppState>,
+        cast: &PreparedCast,
+    ) -> Result<Execution, CastError> {
+        Self::execute_with_events(state, cast, &EventSink::default()).await
+    }
+
+    /// `execute`, reporting state transitions, spell logs, output chunks and the final
+    /// result or error to `events`
+    pub async fn execute_with_events(
+        state: &web::Data<AppState>,
+        cast: &PreparedCast,
+        events: &EventSink,
+    ) -> Result<Execution, CastError> {
+        let result = Self::finish(state, cast, events).await;
+
+        match &result {
+            Ok(execution) => events.emit(CastEvent::Result {
+                cast_id: cast.id,
+                result: execution.output.clone(),
+                attempts: execution.attempts,
+                cached: execution.cached,
+                cost_cents: execution.cost_cents,
+            }),
+            Err(e) => events.emit(CastEvent::Error(e.problem())),
+        }
+
+        if cast.callback_url.is_some() {
+            if let Err(e) = WebhookService::enqueue_for_cast(&cast.id, &state.db).await {
+                log::error!("Failed to queue webhook for cast {}: {e}", cast.id);
+            }
+        }
+
+        result
+    }
+
+    async fn finish(
+        state: &web::Data<AppState>,
+        cast: &PreparedCast,
+        events: &EventSink,
+    ) -> Result<Execution, CastError> {
+        let cast_id = cast.id;
+        let cache_key = Self::cache_key(cast);
+
+        if let Some(key) = &cache_key {
+            match ResultCache::get(&state.redis, key).await {
+                Ok(Some(output)) => return Self::complete(state, cast, output, 0, true).await,
+                Ok(None) => {}
+                Err(e) => log::warn!("Result cache lookup failed for cast {cast_id}: {e}"),
+            }
+        }
+
+        let policy = cast.spell.retry_policy();
+        let mut attempt = 0;
+
+        let outcome = loop {
+            // An open circuit fails the cast fast, including between retries
+            let admission =
+                match CircuitBreaker::admit(&state.redis, &cast.spell, &cast.version).await {
+                    Ok(admission) => admission,
+                    Err(e) => break Err(e),
+                };
+            attempt += 1;
+            events.emit(CastEvent::State {
+                cast_id,
+                status: "RUNNING",
+                attempt,
+                retry_in_ms: None,
+            });
+            let started_at = chrono::Utc::now();
+            let outcome = Self::run(state, cast, events, true).await;
+            CircuitBreaker::record(state, &cast.spell, &cast.version, admission, &outcome).await;
+            // The attempt log is bookkeeping; losing a row must not strand a finished cast
+            if let Err(e) =
+                Self::record_attempt(&cast_id, attempt, started_at, &outcome, &state.db).await
+            {
+                log::error!("Failed to record attempt {attempt} of cast {cast_id}: {e}");
+            }
+
+            match outcome {
+                Err(e) if e.is_retryable() && attempt < policy.max_attempts => {
+                    let delay = policy.delay_after(attempt);
+                    log::warn!(
+                        "Cast {cast_id} attempt {attempt} failed ({}), retrying in {delay:?}",
+                        e.error_code()
+                    );
+                    events.emit(CastEvent::State {
+                        cast_id,
+                        status: "RETRYING",
+                        attempt,
+                        retry_in_ms: Some(delay.as_millis() as u64),
+                    });
+                    tokio::time::sleep(delay).await;
+                    if Self::is_canceled(&cast_id, &state.db).await? {
+                        break Err(CastError::Canceled);
+                    }
+                }
+                outcome => break outcome,
+            }
+        };
+
+        match outcome {
+            Ok(output) => {
+                let execution = Self::complete(state, cast, output, attempt, false).await?;
+
+                if let Some(key) = &cache_key {
+                    let ttl_secs = cast.spell.cache_policy().ttl_secs.max(1) as u64;
+                    if let Err(e) =
+                        ResultCache::put(&state.redis, key, &execution.output, ttl_secs).await
+                    {
+                        log::warn!("Failed to cache result of cast {cast_id}: {e}");
+                    }
+                }
+
+                Ok(execution)
+            }
+            Err(CastError::Canceled) => {
+                log::info!("Cast {cast_id} canceled during attempt {attempt}");
+                Err(CastError::Canceled)
+            }
+            Err(e) => {
+                Self::fail(&cast_id, cast.requeue_count, &e, &state.db).await?;
+
+                log::error!("Cast {cast_id} failed after {attempt} attempt(s): {e}");
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Result cache key, if this cast may be served from or stored in the cache
+    fn cache_key(cast: &PreparedCast) -> Option<String> {
+        if cast.bypass_cache || !cast.version.pure {
+            return None;
+        }
+        // Legacy versions without a digest have no stable identity to key on
+        cast.version.digest.as_deref().map(|digest| {
+            if cast.input_files.is_empty() {
+                ResultCache::key(digest, &cast.payload)
+            } else {
+                // Files are content-addressed, so their digests stand in for their bytes
+                let digests: Vec<&str> =
+                    cast.input_files.iter().map(|f| f.digest.as_str()).collect();
+                let input = serde_json::json!({ "payload": cast.payload, "files": digests });
+                ResultCache::key(digest, &input)
+            }
+        })
+    }
+
+    /// Hand a QUEUED cast to the worker pool, failing it if the queue is unavailable
+    pub async fn enqueue(state: &web::Data<AppState>, cast_id: &Uuid) -> Result<(), CastError> {
+        let Err(e) = CastQueue::enqueue(&state.redis, cast_id).await else {
+            return Ok(());
+        };
+        log::error!("Failed to enqueue cast {cast_id}: {e}");
+        let err = CastError::InternalError("Cast queue unavailable".to_string());
+        sqlx::query(
+            r#"
+            UPDATE casts
+            SET status = 'FAILED', error_code = $2, error_message = $3, finished_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(cast_id)
+        .bind(err.error_code())
+        .bind(err.to_string())
+        .execute(&state.db)
+        .await?;
+        Err(err)
+    }
+
+    /// Record a RUNNING cast as FAILED with `error`, if `requeue_count` still holds its claim
+    /// Fails with `Canceled` if the cast was canceled or requeued first.
+    pub async fn fail(
+        cast_id: &Uuid,
+        requeue_count: i32,
+        error: &CastError,
+        db: &PgPool,
+    ) -> Result<(), CastError> {
+        let failed = sqlx::query(
+            r#"
+            UPDATE casts
+            SET status = 'FAILED', error_code = $2, error_message = $3, finished_at = NOW()
+            WHERE id = $1 AND status = 'RUNNING' AND requeue_count = $4
+            "#,
+        )
+        .bind(cast_id)
+        .bind(error.error_code())
+        .bind(error.to_string())
+        .bind(requeue_count)
+        .execute(db)
+        .await?;
+        if failed.rows_affected() == 0 {
+            return Err(CastError::Canceled);
+        }
+        Ok(())
+    }
+
+    /// Record a successful cast and charge for it
+    pub async fn complete(
+        state: &web::Data<AppState>,
+        cast: &PreparedCast,
+        output: Value,
+        attempts: i32,
+        cached: bool,
+    ) -> Result<Execution, CastError> {
+        let cast_id = cast.id;
+
+        // A cancellation that landed after the spell finished still wins: no result, no charge.
+        // So does a requeue: the cast is another run's to finish (and bill) now.
+        let completed = sqlx::query(
+            r#"
+            UPDATE casts
+            SET status = 'COMPLETED', result = $2, cached = $3, finished_at = NOW()
+            WHERE id = $1 AND status = 'RUNNING' AND requeue_count = $4
+            "#,
+        )
+        .bind(cast_id)
+        .bind(&output)
+        .bind(cached)
+        .bind(cast.requeue_count)
+        .execute(&state.db)
+        .await?;
+        if completed.rows_affected() == 0 {
+            log::info!("Cast {cast_id} was canceled or requeued before it could complete");
+            return Err(CastError::Canceled);
+        }
+
+        if cached {
+            log::info!("Cast {cast_id} served from the result cache");
+        } else {
+            log::info!("Cast {cast_id} completed successfully after {attempts} attempt(s)");
+        }
+
+        // Record usage and cost based on spell price
+        let cost_cents = if cached {
+            cast.spell
+                .cache_policy()
+                .cached_price_cents(cast.cost_cents)
+        } else {
+            cast.cost_cents
+        };
+        if cost_cents > 0 {
+            if let Err(e) =
+                BudgetService::record_usage(&cast.user_id, cost_cents, &cast_id, &state.db).await
+            {
+                log::error!("Failed to record usage for cast {cast_id}: {e}");
+                // Continue anyway - don't fail the cast
+            }
+        }
+
+        Ok(Execution {
+            output,
+            attempts,
+            cached,
+            cost_cents,
+        })
+    }
+
+    /// Log one attempt and bump the cast's attempt counter
+    async fn record_attempt(
+        cast_id: &Uuid,
+        attempt: i32,
+        started_at: chrono::DateTime<chrono::Utc>,
+        outcome: &Result<Value, CastError>,
+        db: &PgPool,
+    ) -> Result<(), CastError> {
+        let (status, error_code, error_message) = match outcome {
+            Ok(_) => ("COMPLETED", None, None),
+            Err(CastError::Canceled) => ("CANCELED", None, None),
+            Err(e) => ("FAILED", Some(e.error_code()), Some(e.to_string())),
+        };
+
+        let mut tx = db.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO cast_attempts (cast_id, attempt, status, error_code, error_message, started_at, finished_at)
+            VALUES ($1, $2, $3, $4, $5, $6, NOW())
+            "#,
+        )
+        .bind(cast_id)
+        .bind(attempt)
+        .bind(status)
+        .bind(error_code)
+        .bind(error_message)
+        .bind(started_at)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("UPDATE casts SET attempts = $2 WHERE id = $1")
+            .bind(cast_id)
+            .bind(attempt)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Whether the caller has canceled the cast
+    async fn is_canceled(cast_id: &Uuid, db: &PgPool) -> Result<bool, CastError> {
+        let status: Option<String> = sqlx::query_scalar("SELECT status FROM casts WHERE id = $1")
+            .bind(cast_id)
+            .fetch_optional(db)
+            .await?;
+        Ok(status.as_deref() == Some("CANCELED"))
+    }
+
+    /// Fetch a cast's input files from the blob store
+    async fn load_input_files(
+        state: &web::Data<AppState>,
+        files: &[InputFile],
+    ) -> Result<Vec<InputFileData>, CastError> {
+        let mut loaded = Vec::with_capacity(files.len());
+        for file in files {
+            let bytes = get_verified_blob(state.storage.as_ref(), &file.digest)
+                .await
+                .map_err(|e| {
+                    CastError::InternalError(format!(
+                        "Failed to load input file {}: {e}",
+                        file.name
+                    ))
+                })?;
+            loaded.push(InputFileData {
+                name: file.name.clone(),
+                content_type: file.content_type.clone(),
+                bytes,
+            });
+        }
+        Ok(loaded)
+    }
+
+    /// Run a payload once against a version without recording, caching or billing it
+    /// Used for replays and health checks; there are no retries, but the output contract applies.
+    pub async fn run_sandboxed(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        version: &SpellVersion,
+        payload: &Value,
+        input_files: &[InputFile],
+    ) -> Result<Value, CastError> {
+        let cast = PreparedCast {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            spell: spell.clone(),
+            version: version.clone(),
+            payload: payload.clone(),
+            input_files: input_files.to_vec(),
+            callback_url: None,
+            bypass_cache: true,
+            request_id: request_id::current(),
+            cost_cents: 0,
+            requeue_count: 0,
+        };
+        Self::run(state, &cast, &EventSink::default(), false).await
+    }
+
+    /// Execute WASM, then enforce the spell's output contract
+    /// While a recorded cast runs, its casts row is polled so a cancellation made on any
+    /// instance interrupts it.
+    async fn run(
+        state: &web::Data<AppState>,
+        cast: &PreparedCast,
+        events: &EventSink,
+        recorded: bool,
+    ) -> Result<Value, CastError> {
+        let module = state
+            .wasm
+            .load_module(&cast.spell.name, &cast.version)
+            .await?;
+        let input = SpellInput {
+            payload: cast.payload.clone(),
+            files: Self::load_input_files(state, &cast.input_files).await?,
+            input_format: cast.version.input_format(),
+            output_format: cast.version.output_format(),
+        };
+
+        // Execution is CPU-bound; keep it off the async executor
+        let runtime = state.clone();
+        let spell_name = cast.spell.name.clone();
+        let cancel = CancelToken::default();
+        let token = cancel.clone();
+        let events = events.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            runtime
+                .wasm
+                .execute_spell(&spell_name, &module, input, &token, &events)
+        });
+        tokio::pin!(task);
+
+        let mut poll = tokio::time::interval(CANCEL_POLL_INTERVAL);
+        let output = loop {
+            tokio::select! {
+                joined = &mut task => {
+                    break joined.map_err(|e| {
+                        CastError::InternalError(format!("Execution task failed: {e}"))
+                    })??;
+                }
+                _ = poll.tick(), if recorded && !cancel.is_canceled() => {
+                    match Self::is_canceled(&cast.id, &state.db).await {
+                        Ok(true) => cancel.cancel(),
+                        Ok(false) => {}
+                        Err(e) => log::warn!("Failed to check cancellation of cast {}: {e}", cast.id),
+                    }
+                }
+            }
+        };
+
+        if let Some(schema) = &cast.spell.output_schema {
+            ContractService::validate_output(schema, &output)?;
+        }
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,416 @@
+use actix_web::web;
+use deadpool_redis::Pool;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::errors::CastError;
+use crate::models::spell::{
+    CircuitStatus, SpellVersion, CIRCUIT_CLOSED, CIRCUIT_HALF_OPEN, CIRCUIT_OPEN,
+};
+use crate::models::Spell;
+use crate::services::webhook_service::WebhookService;
+use crate::AppState;
+
+// Failure rates are measured over the last few one-minute buckets
+const WINDOW_MINUTES: i64 = 5;
+// Too few casts say nothing about a version's health
+const MIN_WINDOW_CASTS: u64 = 20;
+const FAILURE_THRESHOLD: f64 = 0.5;
+// Time an open circuit refuses casts; doubles each time a probe fails, up to the max
+const BASE_COOLDOWN_SECS: u64 = 30;
+const MAX_COOLDOWN_SECS: u64 = 10 * 60;
+// Half-open circuits let this many probes through per probe window
+const MAX_PROBES: u64 = 3;
+const PROBE_WINDOW_SECS: u64 = 30;
+// Consecutive successful probes that close the circuit again
+const PROBES_TO_CLOSE: u64 = 3;
+
+/// How a cast was let through the breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Closed,
+    /// One of the limited casts let through a half-open circuit
+    Probe,
+}
+
+/// Per-version circuit breaker, shared across instances through Redis
+/// A version whose spell-caused failures (traps, timeouts, contract violations) pass
+/// the threshold is opened: casts fail fast with a retryable SPELL_UNAVAILABLE instead
+/// of burning compute. After a cooldown a few probes are let through; enough successes
+/// close it again, a failure reopens it for longer. Creators are alerted either way.
+/// If Redis is unreachable the breaker stays out of the way.
+pub struct CircuitBreaker;
+
+impl CircuitBreaker {
+    /// Decide whether a cast of `version` may run now
+    pub async fn admit(
+        redis: &Pool,
+        spell: &Spell,
+        version: &SpellVersion,
+    ) -> Result<Admission, CastError> {
+        match Self::try_admit(redis, &version.id).await {
+            Ok(Ok(admission)) => Ok(admission),
+            Ok(Err(retry_after_secs)) => Err(CastError::SpellUnavailable {
+                spell_name: spell.name.clone(),
+                retry_after_secs,
+            }),
+            Err(e) => {
+                log::warn!("Circuit check failed for version {}: {e}", version.id);
+                Ok(Admission::Closed)
+            }
+        }
+    }
+
+    async fn try_admit(
+        redis: &Pool,
+        version_id: &Uuid,
+    ) -> Result<Result<Admission, u64>, anyhow::Error> {
+        let mut conn = redis.get().await?;
+        let (open_ttl, tripped): (i64, Option<u64>) = redis::pipe()
+            .cmd("TTL")
+            .arg(Self::key(version_id, "open"))
+            .cmd("GET")
+            .arg(Self::key(version_id, "tripped"))
+            .query_async(&mut *conn)
+            .await?;
+
+        if open_ttl > 0 {
+            return Ok(Err(open_ttl as u64));
+        }
+        if tripped.is_none() {
+            return Ok(Ok(Admission::Closed));
+        }
+
+        let probes_key = Self::key(version_id, "probes");
+        let probes: u64 = redis::cmd("INCR")
+            .arg(&probes_key)
+            .query_async(&mut *conn)
+            .await?;
+        if probes == 1 {
+            redis::cmd("EXPIRE")
+                .arg(&probes_key)
+                .arg(PROBE_WINDOW_SECS)
+                .query_async::<_, ()>(&mut *conn)
+                .await?;
+        }
+        if probes <= MAX_PROBES {
+            return Ok(Ok(Admission::Probe));
+        }
+        let ttl: i64 = redis::cmd("TTL")
+            .arg(&probes_key)
+            .query_async(&mut *conn)
+            .await?;
+        Ok(Err(ttl.max(1) as u64))
+    }
+
+    /// Count the outcome of an admitted execution, opening or closing the circuit as needed
+    /// Errors that aren't the spell's fault (bad input, cancellation, our own) don't count.
+    pub async fn record(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        version: &SpellVersion,
+        admission: Admission,
+        outcome: &Result<Value, CastError>,
+    ) {
+        let failed = match outcome {
+            Ok(_) => false,
+            Err(e) if Self::is_spell_failure(e) => true,
+            Err(_) => return,
+        };
+        let result = match admission {
+            Admission::Closed => Self::count(state, spell, version, failed).await,
+            Admission::Probe if failed => {
+                Self::trip(state, spell, version, "A probe cast failed".to_string()).await
+            }
+            Admission::Probe => Self::probe_succeeded(state, spell, version).await,
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to update circuit of version {}: {e}", version.id);
+        }
+    }
+
+    /// Open the circuit of a version, or reopen it for longer if it is half-open
+    pub async fn trip(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        version: &SpellVersion,
+        reason: String,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = state.redis.get().await?;
+        let trips: u64 = redis::cmd("INCR")
+            .arg(Self::key(&version.id, "tripped"))
+            .query_async(&mut *conn)
+            .await?;
+        let cooldown = Self::cooldown_secs(trips);
+        redis::pipe()
+            .cmd("SET")
+            .arg(Self::key(&version.id, "open"))
+            .arg(trips)
+            .arg("EX")
+            .arg(cooldown)
+            .ignore()
+            .cmd("DEL")
+            .arg(Self::key(&version.id, "probes"))
+            .arg(Self::key(&version.id, "probe_ok"))
+            .ignore()
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+
+        log::warn!(
+            "Circuit opened for {}@{} for {cooldown}s: {reason}",
+            spell.name,
+            version.version
+        );
+        Self::notify(state, spell, version, "opened", &reason, Some(cooldown)).await;
+        Ok(())
+    }
+
+    /// Live state of a version's circuit
+    pub async fn status(redis: &Pool, version: &SpellVersion) -> CircuitStatus {
+        let state = async {
+            let mut conn = redis.get().await?;
+            let state: (i64, Option<u64>) = redis::pipe()
+                .cmd("TTL")
+                .arg(Self::key(&version.id, "open"))
+                .cmd("GET")
+                .arg(Self::key(&version.id, "tripped"))
+                .query_async(&mut *conn)
+                .await?;
+            Ok::<_, anyhow::Error>(state)
+        }
+        .await;
+
+        let (state, retry_after_secs) = match state {
+            Ok((ttl, _)) if ttl > 0 => (CIRCUIT_OPEN, Some(ttl as u64)),
+            Ok((_, Some(_))) => (CIRCUIT_HALF_OPEN, None),
+            Ok(_) => (CIRCUIT_CLOSED, None),
+            Err(e) => {
+                log::warn!("Failed to read circuit of version {}: {e}", version.id);
+                (CIRCUIT_CLOSED, None)
+            }
+        };
+        CircuitStatus {
+            version: version.version.clone(),
+            state,
+            retry_after_secs,
+        }
+    }
+
+    /// Whether an error is the spell's fault (traps, timeouts, contract violations)
+    /// rather than the caller's input or the platform's
+    pub fn is_spell_failure(error: &CastError) -> bool {
+        matches!(
+            error,
+            CastError::WasmExecutionFailed(_)
+                | CastError::WasmTimeout
+                | CastError::OutputContractViolation(_)
+        )
+    }
+
+    fn should_trip(succeeded: u64, failed: u64) -> bool {
+        let total = succeeded + failed;
+        total >= MIN_WINDOW_CASTS && failed as f64 / total as f64 >= FAILURE_THRESHOLD
+    }
+
+    fn cooldown_secs(trips: u64) -> u64 {
+        let exponent = trips.saturating_sub(1).min(16) as u32;
+        BASE_COOLDOWN_SECS
+            .saturating_mul(2_u64.pow(exponent))
+            .min(MAX_COOLDOWN_SECS)
+    }
+
+    fn key(version_id: &Uuid, part: &str) -> String {
+        format!("circuit:{version_id}:{part}")
+    }
+
+    fn bucket_key(version_id: &Uuid, minute: i64) -> String {
+        format!("circuit:{version_id}:m:{minute}")
+    }
+
+    /// Add an outcome to the rolling window; a failure may open the circuit
+    async fn count(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        version: &SpellVersion,
+        failed: bool,
+    ) -> Result<(), anyhow::Error> {
+        let minute = chrono::Utc::now().timestamp() / 60;
+        let mut conn = state.redis.get().await?;
+        let bucket = Self::bucket_key(&version.id, minute);
+        redis::pipe()
+            .cmd("HINCRBY")
+            .arg(&bucket)
+            .arg(if failed { "fail" } else { "ok" })
+            .arg(1)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&bucket)
+            .arg((WINDOW_MINUTES + 1) * 60)
+            .ignore()
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+        if !failed {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for m in (minute - WINDOW_MINUTES + 1)..=minute {
+            pipe.cmd("HMGET")
+                .arg(Self::bucket_key(&version.id, m))
+                .arg("ok")
+                .arg("fail");
+        }
+        let buckets: Vec<(Option<u64>, Option<u64>)> = pipe.query_async(&mut *conn).await?;
+        let (succeeded, failures) = buckets.iter().fold((0, 0), |(s, f), (ok, fail)| {
+            (s + ok.unwrap_or(0), f + fail.unwrap_or(0))
+        });
+        if !Self::should_trip(succeeded, failures) {
+            return Ok(());
+        }
+
+        // Only the first instance to see the threshold crossed opens the circuit
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(Self::key(&version.id, "tripped"))
+            .arg(0)
+            .arg("NX")
+            .query_async(&mut *conn)
+            .await?;
+        if claimed.is_none() {
+            return Ok(());
+        }
+        drop(conn);
+
+        let reason = format!(
+            "{failures} of the last {} casts in {WINDOW_MINUTES} minutes failed",
+            succeeded + failures
+        );
+        Self::trip(state, spell, version, reason).await
+    }
+
+    async fn probe_succeeded(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        version: &SpellVersion,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = state.redis.get().await?;
+        let probe_ok_key = Self::key(&version.id, "probe_ok");
+        let (succeeded, _): (u64, ()) = redis::pipe()
+            .cmd("INCR")
+            .arg(&probe_ok_key)
+            .cmd("EXPIRE")
+            .arg(&probe_ok_key)
+            .arg(MAX_COOLDOWN_SECS)
+            .query_async(&mut *conn)
+            .await?;
+        if succeeded < PROBES_TO_CLOSE {
+            return Ok(());
+        }
+
+        // Forget the failures that opened it, so they can't reopen it straight away
+        let minute = chrono::Utc::now().timestamp() / 60;
+        let buckets: Vec<String> = ((minute - WINDOW_MINUTES)..=minute)
+            .map(|m| Self::bucket_key(&version.id, m))
+            .collect();
+        let closed: u64 = redis::cmd("DEL")
+            .arg(Self::key(&version.id, "tripped"))
+            .query_async(&mut *conn)
+            .await?;
+        redis::cmd("DEL")
+            .arg(Self::key(&version.id, "probes"))
+            .arg(&probe_ok_key)
+            .arg(&buckets)
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+        if closed == 0 {
+            // Another probe closed it first
+            return Ok(());
+        }
+
+        log::info!("Circuit closed for {}@{}", spell.name, version.version);
+        let reason = format!("{succeeded} probe casts succeeded");
+        Self::notify(state, spell, version, "closed", &reason, None).await;
+        Ok(())
+    }
+
+    /// Record a transition and alert the creator, if they set an alert URL
+    async fn notify(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        version: &SpellVersion,
+        event: &str,
+        reason: &str,
+        retry_after_secs: Option<u64>,
+    ) {
+        if let Err(e) = sqlx::query(
+            r#"
+            INSERT INTO spell_circuit_events (id, spell_id, spell_version_id, event, reason)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(spell.id)
+        .bind(version.id)
+        .bind(event)
+        .bind(reason)
+        .execute(&state.db)
+        .await
+        {
+            log::error!("Failed to record circuit event for {}: {e}", spell.name);
+        }
+
+        let Some(url) = &spell.alert_url else {
+            return;
+        };
+        let data = serde_json::json!({
+            "spell_name": spell.name,
+            "version": version.version,
+            "reason": reason,
+            "retry_after_secs": retry_after_secs,
+        });
+        if let Err(e) = WebhookService::enqueue_alert(
+            &spell.creator_id,
+            url,
+            &format!("spell.circuit_{event}"),
+            data,
+            &state.db,
+        )
+        .await
+        {
+            log::error!("Failed to queue circuit alert for {}: {e}", spell.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trips_only_on_enough_casts_failing() {
+        assert!(!CircuitBreaker::should_trip(0, 5));
+        assert!(!CircuitBreaker::should_trip(11, 9));
+        assert!(CircuitBreaker::should_trip(10, 10));
+        assert!(CircuitBreaker::should_trip(0, 20));
+    }
+
+    #[test]
+    fn cooldown_doubles_per_trip_up_to_the_max() {
+        assert_eq!(CircuitBreaker::cooldown_secs(1), 30);
+        assert_eq!(CircuitBreaker::cooldown_secs(2), 60);
+        assert_eq!(CircuitBreaker::cooldown_secs(4), 240);
+        assert_eq!(CircuitBreaker::cooldown_secs(10), MAX_COOLDOWN_SECS);
+        assert_eq!(CircuitBreaker::cooldown_secs(u64::MAX), MAX_COOLDOWN_SECS);
+    }
+
+    #[test]
+    fn only_spell_failures_count() {
+        assert!(CircuitBreaker::is_spell_failure(&CastError::WasmTimeout));
+        assert!(CircuitBreaker::is_spell_failure(
+            &CastError::WasmExecutionFailed("trap".to_string())
+        ));
+        assert!(!CircuitBreaker::is_spell_failure(&CastError::InvalidInput(
+            String::new()
+        )));
+        assert!(!CircuitBreaker::is_spell_failure(&CastError::Canceled));
+    }
+}
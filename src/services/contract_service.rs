@@ -0,0 +1,115 @@
+use jsonschema::JSONSchema;
+use serde_json::Value;
+
+use crate::errors::CastError;
+
+/// Maximum number of schema errors echoed back to the caster
+const MAX_REPORTED_ERRORS: usize = 5;
+
+pub struct ContractService;
+
+impl ContractService {
+    /// Validate a spell result against the spell's declared output schema
+    /// Returns Ok(()) if the output conforms, Err with the violations otherwise
+    pub fn validate_output(schema: &Value, output: &Value) -> Result<(), CastError> {
+        let compiled = JSONSchema::compile(schema).map_err(|e| {
+            // A broken schema is the creator's bug, not the caster's
+            CastError::OutputContractViolation(format!("invalid output schema: {e}"))
+        })?;
+
+        violations(&compiled, output).map_err(CastError::OutputContractViolation)
+    }
+
+    /// Validate a cast payload against the input schema declared in a version's manifest
+    /// A schema that doesn't compile can't be enforced; casts go ahead rather than fail
+    /// for a mistake neither the caster nor the spell's code made.
+    pub fn validate_input(schema: &Value, payload: &Value) -> Result<(), CastError> {
+        let compiled = match JSONSchema::compile(schema) {
+            Ok(compiled) => compiled,
+            Err(e) => {
+                log::warn!("Ignoring uncompilable input schema: {e}");
+                return Ok(());
+            }
+        };
+
+        violations(&compiled, payload).map_err(CastError::InvalidInput)
+    }
+
+    /// Check that a schema compiles before it is stored on a spell
+    pub fn check_schema(schema: &Value) -> Result<(), String> {
+        JSONSchema::compile(schema)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// The first few schema errors for `value`, joined for display
+fn violations(compiled: &JSONSchema, value: &Value) -> Result<(), String> {
+    if let Err(errors) = compiled.validate(value) {
+        let messages: Vec<String> = errors
+            .take(MAX_REPORTED_ERRORS)
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                if path.is_empty() {
+                    e.to_string()
+                } else {
+                    format!("{path}: {e}")
+                }
+            })
+            .collect();
+
+        return Err(messages.join("; "));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["width", "height"],
+            "properties": {
+                "width": { "type": "integer", "minimum": 1 },
+                "height": { "type": "integer", "minimum": 1 }
+            }
+        })
+    }
+
+    #[test]
+    fn accepts_conforming_output() {
+        let output = json!({ "width": 640, "height": 480 });
+        assert!(ContractService::validate_output(&schema(), &output).is_ok());
+    }
+
+    #[test]
+    fn rejects_output_with_changed_shape() {
+        let output = json!({ "width": "640" });
+        let err = ContractService::validate_output(&schema(), &output).unwrap_err();
+
+        assert_eq!(err.error_code(), "OUTPUT_CONTRACT_VIOLATION");
+        let message = err.to_string();
+        assert!(message.contains("height"), "{message}");
+        assert!(message.contains("/width"), "{message}");
+    }
+
+    #[test]
+    fn rejects_payload_violating_input_schema_as_invalid_input() {
+        let err = ContractService::validate_input(&schema(), &json!({ "width": 0 })).unwrap_err();
+        assert_eq!(err.error_code(), "INVALID_INPUT");
+        assert!(
+            ContractService::validate_input(&schema(), &json!({ "width": 1, "height": 1 })).is_ok()
+        );
+        assert!(ContractService::validate_input(&json!({ "type": 12 }), &json!({})).is_ok());
+    }
+
+    #[test]
+    fn rejects_uncompilable_schema() {
+        assert!(ContractService::check_schema(&schema()).is_ok());
+        assert!(ContractService::check_schema(&json!({ "type": 12 })).is_err());
+    }
+}
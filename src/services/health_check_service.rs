@@ -0,0 +1,282 @@
+use actix_web::web;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::errors::CastError;
+use crate::models::spell::{
+    ExampleResult, SpellExample, SpellVersion, CIRCUIT_OPEN, HEALTH_ERRORED, HEALTH_FAILING,
+    HEALTH_PASSING,
+};
+use crate::models::Spell;
+use crate::services::cast_service::CastService;
+use crate::services::circuit_breaker::CircuitBreaker;
+use crate::services::poller;
+use crate::utils::json_diff;
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 10;
+// Claiming a version schedules its next check, so this is also the claim lease
+const CHECK_INTERVAL_SECS: i64 = 15 * 60;
+
+/// Runs the examples declared in spell manifests against their versions
+/// Publishing doesn't start a check: the next poll picks a new version up, first in line,
+/// and it is checked every few minutes after that.
+/// Checks are synthetic casts: sandboxed, not recorded as casts and never billed. A
+/// failing check opens the version's circuit, so casters fail fast instead of paying
+/// for a broken update.
+pub struct HealthCheckService;
+
+impl HealthCheckService {
+    /// Start the checker for the lifetime of the process
+    /// Several instances may run it; each due version is claimed by one of them.
+    pub fn spawn_checker(state: web::Data<AppState>) {
+        poller::spawn(
+            "Spell health checker",
+            POLL_INTERVAL,
+            BATCH_SIZE,
+            move || {
+                let state = state.clone();
+                async move { Self::run_due(&state).await }
+            },
+        );
+    }
+
+    async fn run_due(state: &web::Data<AppState>) -> Result<usize, sqlx::Error> {
+        // Never-claimed versions were just published; they come first
+        let due: Vec<SpellVersion> = sqlx::query_as(
+            r#"
+            UPDATE spell_versions
+            SET next_health_check_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT v.id FROM spell_versions v
+                JOIN spells s ON s.id = v.spell_id
+                WHERE s.is_active AND v.status <> 'yanked'
+                  AND (v.sunset_at IS NULL OR v.sunset_at > NOW())
+                  AND v.manifest ? 'examples'
+                  AND (v.next_health_check_at IS NULL OR v.next_health_check_at <= NOW())
+                ORDER BY v.next_health_check_at NULLS FIRST
+                LIMIT $1
+                FOR UPDATE OF v SKIP LOCKED
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(BATCH_SIZE)
+        .bind(CHECK_INTERVAL_SECS as f64)
+        .fetch_all(&state.db)
+        .await?;
+
+        for version in &due {
+            let spell: Option<Spell> = sqlx::query_as("SELECT * FROM spells WHERE id = $1")
+                .bind(version.spell_id)
+                .fetch_optional(&state.db)
+                .await?;
+            if let Some(spell) = spell {
+                if let Err(e) = Self::check(state, &spell, version).await {
+                    log::error!(
+                        "Health check of {}@{} failed to run: {e}",
+                        spell.name,
+                        version.version
+                    );
+                }
+            }
+        }
+        Ok(due.len())
+    }
+
+    /// Run every example against `version`, record the outcome and trip its circuit on failure
+    /// Examples that errored for reasons outside the spell neither fail the version nor
+    /// trip its circuit; such a check is recorded as ERRORED and the version keeps its
+    /// previous health.
+    async fn check(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        version: &SpellVersion,
+    ) -> Result<(), sqlx::Error> {
+        let examples = version.examples();
+        if examples.is_empty() {
+            return Ok(());
+        }
+        // Any recorded check counts, including ERRORED ones that leave the health unset
+        let checked_before: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM spell_health_checks WHERE spell_version_id = $1)",
+        )
+        .bind(version.id)
+        .fetch_one(&state.db)
+        .await?;
+        let triggered_by = if checked_before {
+            "scheduled"
+        } else {
+            "publish"
+        };
+
+        // One at a time: checks share the cast runtime with real traffic
+        let mut results: Vec<ExampleResult> = Vec::with_capacity(examples.len());
+        for example in &examples {
+            let started = Instant::now();
+            let outcome =
+                CastService::run_sandboxed(state, spell, version, &example.payload, &[]).await;
+            results.push(Self::evaluate(
+                example,
+                outcome,
+                started.elapsed().as_millis() as u64,
+            ));
+        }
+
+        let failed = results.iter().filter(|r| !r.passed && !r.errored).count();
+        let errored = results.iter().filter(|r| r.errored).count();
+        let status = match (failed, errored) {
+            (0, 0) => HEALTH_PASSING,
+            (0, _) => HEALTH_ERRORED,
+            _ => HEALTH_FAILING,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO spell_health_checks
+                (id, spell_id, spell_version_id, triggered_by, status, passed, failed, results)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(spell.id)
+        .bind(version.id)
+        .bind(triggered_by)
+        .bind(status)
+        .bind((results.len() - failed - errored) as i32)
+        .bind(failed as i32)
+        .bind(serde_json::to_value(&results).unwrap_or(Value::Null))
+        .execute(&state.db)
+        .await?;
+
+        if status == HEALTH_ERRORED {
+            log::warn!(
+                "{errored} of {} health check example(s) of {}@{} could not run",
+                results.len(),
+                spell.name,
+                version.version
+            );
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE spell_versions SET health_status = $2, health_checked_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(version.id)
+        .bind(status)
+        .execute(&state.db)
+        .await?;
+
+        if failed == 0 {
+            if version.health_status.as_deref() == Some(HEALTH_FAILING) {
+                log::info!(
+                    "{}@{} health checks pass again",
+                    spell.name,
+                    version.version
+                );
+            }
+            return Ok(());
+        }
+
+        log::warn!(
+            "{failed} of {} health check example(s) failed for {}@{}",
+            results.len(),
+            spell.name,
+            version.version
+        );
+        // An open circuit already refuses casts; tripping it again would only extend it
+        if CircuitBreaker::status(&state.redis, version).await.state != CIRCUIT_OPEN {
+            let reason = format!(
+                "{failed} of {} health check example(s) failed",
+                results.len()
+            );
+            if let Err(e) = CircuitBreaker::trip(state, spell, version, reason).await {
+                log::warn!("Failed to update circuit of version {}: {e}", version.id);
+            }
+        }
+        Ok(())
+    }
+
+    /// An example passes if it completes with its expected result, when it declares one
+    /// It fails on a spell failure or a wrong result; any other error marks it errored.
+    fn evaluate(
+        example: &SpellExample,
+        outcome: Result<Value, CastError>,
+        duration_ms: u64,
+    ) -> ExampleResult {
+        let mut result = ExampleResult {
+            name: example.name.clone(),
+            passed: true,
+            errored: false,
+            diff: Vec::new(),
+            error_code: None,
+            error: None,
+            duration_ms,
+        };
+        match (outcome, &example.expected) {
+            (Ok(output), Some(expected)) => {
+                result.diff = json_diff::diff(expected, &output);
+                result.passed = result.diff.is_empty();
+            }
+            (Ok(_), None) => {}
+            (Err(e), _) => {
+                result.passed = false;
+                result.errored = !CircuitBreaker::is_spell_failure(&e);
+                result.error_code = Some(e.error_code().to_string());
+                result.error = Some(e.to_string());
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn example(expected: Option<Value>) -> SpellExample {
+        SpellExample {
+            name: "basic".to_string(),
+            payload: json!({"n": 1}),
+            expected,
+        }
+    }
+
+    #[test]
+    fn examples_pass_on_expected_output() {
+        let exact = example(Some(json!({"n": 2})));
+        assert!(HealthCheckService::evaluate(&exact, Ok(json!({"n": 2})), 3).passed);
+
+        let wrong = HealthCheckService::evaluate(&exact, Ok(json!({"n": 3})), 3);
+        assert!(!wrong.passed);
+        assert_eq!(wrong.diff[0].path, "/n");
+
+        let any = example(None);
+        assert!(HealthCheckService::evaluate(&any, Ok(json!("anything")), 3).passed);
+    }
+
+    #[test]
+    fn failed_casts_fail_the_example() {
+        let result = HealthCheckService::evaluate(&example(None), Err(CastError::WasmTimeout), 3);
+        assert!(!result.passed);
+        assert!(!result.errored);
+        assert_eq!(result.error_code.as_deref(), Some("WASM_TIMEOUT"));
+    }
+
+    #[test]
+    fn platform_errors_mark_the_example_errored() {
+        let missing = CastError::WasmNotFound("echo@1.0.0".to_string());
+        let result = HealthCheckService::evaluate(&example(None), Err(missing), 3);
+        assert!(!result.passed);
+        assert!(result.errored);
+
+        let outage = CastError::InternalError("Cast queue unavailable".to_string());
+        assert!(HealthCheckService::evaluate(&example(None), Err(outage), 3).errored);
+    }
+}
@@ -0,0 +1,268 @@
+use actix_web::body::MessageBody;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use deadpool_redis::Pool;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::errors::CastError;
+
+// How long a completed response is replayed for
+const RECORD_TTL_SECS: u64 = 24 * 60 * 60;
+// An in-flight marker outlives any single cast, but not a crashed server by long
+const IN_PROGRESS_TTL_SECS: u64 = 5 * 60;
+// How long a concurrent duplicate waits for the original before getting 409
+const WAIT_FOR_ORIGINAL: Duration = Duration::from_secs(10);
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const MAX_KEY_LENGTH: usize = 255;
+// Response headers worth replaying; everything else is regenerated per request
+const REPLAYED_HEADERS: &[&str] = &["content-type", "location", "deprecation", "sunset"];
+
+/// A response captured for replay
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// `body` is base64, for MessagePack and CBOR responses
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub base64: bool,
+}
+
+impl StoredResponse {
+    /// Capture a response for storage, returning an equivalent response to send now
+    pub fn capture(response: HttpResponse) -> (Self, HttpResponse) {
+        let status = response.status();
+        let headers: Vec<(String, String)> = response
+            .headers()
+            .iter()
+            .filter(|(name, _)| REPLAYED_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| {
+                value
+                    .to_str()
+                    .ok()
+                    .map(|v| (name.as_str().to_string(), v.to_string()))
+            })
+            .collect();
+        // Responses here are built in full; a streaming body would be stored empty
+        let body = response.into_body().try_into_bytes().unwrap_or_default();
+
+        let (body, base64) = match String::from_utf8(body.to_vec()) {
+            Ok(text) => (text, false),
+            Err(_) => (STANDARD.encode(&body), true),
+        };
+        let stored = StoredResponse {
+            status: status.as_u16(),
+            headers,
+            body,
+            base64,
+        };
+        let response = stored.to_response(false);
+        (stored, response)
+    }
+
+    pub fn to_response(&self, replayed: bool) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let mut builder = HttpResponse::build(status);
+        for (name, value) in &self.headers {
+            builder.insert_header((name.as_str(), value.as_str()));
+        }
+        if replayed {
+            builder.insert_header(("Idempotent-Replayed", "true"));
+        }
+        if self.base64 {
+            builder.body(STANDARD.decode(&self.body).unwrap_or_default())
+        } else {
+            builder.body(self.body.clone())
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IdempotencyRecord {
+    fingerprint: String,
+    response: Option<StoredResponse>,
+}
+
+/// What to do with a request carrying an Idempotency-Key
+pub enum IdempotencyStart {
+    /// First use of the key: run the request, then `complete` or `release` it
+    Proceed,
+    /// An identical request already finished; send its response again
+    Replay(StoredResponse),
+}
+
+/// Per-user Idempotency-Key bookkeeping in Redis
+pub struct IdempotencyService;
+
+impl IdempotencyService {
+    /// Validate a client-supplied key (1-255 visible ASCII characters)
+    pub fn validate_key(key: &str) -> Result<(), CastError> {
+        if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+            return Err(CastError::InvalidInput(format!(
+                "Idempotency-Key must be 1-{MAX_KEY_LENGTH} characters"
+            )));
+        }
+        if !key.chars().all(|c| c.is_ascii_graphic()) {
+            return Err(CastError::InvalidInput(
+                "Idempotency-Key must be visible ASCII characters".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn redis_key(user_id: &Uuid, key: &str) -> String {
+        format!("idem:{user_id}:{key}")
+    }
+
+    /// Claim the key, or resolve it against an earlier request with the same key
+    /// Concurrent duplicates wait briefly for the original to finish before getting a conflict.
+    pub async fn start(
+        redis: &Pool,
+        user_id: &Uuid,
+        key: &str,
+        fingerprint: &str,
+    ) -> Result<IdempotencyStart, CastError> {
+        let redis_key = Self::redis_key(user_id, key);
+        let marker = serde_json::to_string(&IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: None,
+        })
+        .map_err(|e| CastError::InternalError(e.to_string()))?;
+
+        let deadline = tokio::time::Instant::now() + WAIT_FOR_ORIGINAL;
+
+        loop {
+            let mut conn = redis.get().await.map_err(unavailable)?;
+
+            let claimed: Option<String> = redis::cmd("SET")
+                .arg(&redis_key)
+                .arg(&marker)
+                .arg("NX")
+                .arg("EX")
+                .arg(IN_PROGRESS_TTL_SECS)
+                .query_async(&mut *conn)
+                .await
+                .map_err(unavailable)?;
+            if claimed.is_some() {
+                return Ok(IdempotencyStart::Proceed);
+            }
+
+            let existing: Option<String> = redis::cmd("GET")
+                .arg(&redis_key)
+                .query_async(&mut *conn)
+                .await
+                .map_err(unavailable)?;
+            drop(conn);
+
+            // The key expired or was released between SET and GET; try to claim it again
+            let Some(existing) = existing else {
+                continue;
+            };
+
+            let record: IdempotencyRecord = serde_json::from_str(&existing).map_err(|e| {
+                CastError::InternalError(format!("Corrupt idempotency record: {e}"))
+            })?;
+
+            if record.fingerprint != fingerprint {
+                return Err(CastError::IdempotencyKeyReused);
+            }
+            if let Some(response) = record.response {
+                return Ok(IdempotencyStart::Replay(response));
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(CastError::IdempotencyInProgress);
+            }
+            tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Store the final response for replay
+    pub async fn complete(
+        redis: &Pool,
+        user_id: &Uuid,
+        key: &str,
+        fingerprint: &str,
+        response: StoredResponse,
+    ) -> Result<(), anyhow::Error> {
+        let record = serde_json::to_string(&IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: Some(response),
+        })?;
+
+        let mut conn = redis.get().await?;
+        redis::cmd("SET")
+            .arg(Self::redis_key(user_id, key))
+            .arg(record)
+            .arg("EX")
+            .arg(RECORD_TTL_SECS)
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Drop an in-flight claim so the client can retry with the same key
+    pub async fn release(redis: &Pool, user_id: &Uuid, key: &str) -> Result<(), anyhow::Error> {
+        let mut conn = redis.get().await?;
+        redis::cmd("DEL")
+            .arg(Self::redis_key(user_id, key))
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+}
+
+fn unavailable(e: impl std::fmt::Display) -> CastError {
+    log::error!("Idempotency store unavailable: {e}");
+    CastError::InternalError("Idempotency store unavailable".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[test]
+    fn rejects_malformed_keys() {
+        assert!(IdempotencyService::validate_key("order-42/retry").is_ok());
+        assert!(IdempotencyService::validate_key("").is_err());
+        assert!(IdempotencyService::validate_key("has space").is_err());
+        assert!(IdempotencyService::validate_key(&"k".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn captured_response_replays_status_headers_and_body() {
+        let original = HttpResponse::Accepted()
+            .insert_header(("Location", "/v1/casts/abc"))
+            .insert_header(("X-Unrelated", "1"))
+            .json(serde_json::json!({"status": "QUEUED"}));
+
+        let (stored, live) = StoredResponse::capture(original);
+        assert_eq!(live.status(), StatusCode::ACCEPTED);
+
+        let replay = stored.to_response(true);
+        assert_eq!(replay.status(), StatusCode::ACCEPTED);
+        assert_eq!(replay.headers().get("location").unwrap(), "/v1/casts/abc");
+        assert!(replay.headers().get("x-unrelated").is_none());
+        assert_eq!(replay.headers().get("idempotent-replayed").unwrap(), "true");
+        assert_eq!(stored.body, r#"{"status":"QUEUED"}"#);
+    }
+
+    #[tokio::test]
+    async fn binary_responses_replay_byte_for_byte() {
+        let msgpack = vec![0x81, 0xa2, b'o', b'k', 0xc3, 0xff];
+        let original = HttpResponse::Ok()
+            .content_type("application/msgpack")
+            .body(msgpack.clone());
+
+        let (stored, _) = StoredResponse::capture(original);
+        assert!(stored.base64);
+
+        let replay = stored.to_response(true);
+        let body = to_bytes(replay.into_body()).await.unwrap();
+        assert_eq!(body.as_ref(), msgpack.as_slice());
+    }
+}
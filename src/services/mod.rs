@@ -1,3 +1,18 @@
 pub mod billing_service;
 pub mod budget_service;
+pub mod cast_events;
+pub mod cast_queue;
+pub mod cast_service;
+pub mod circuit_breaker;
+pub mod contract_service;
+pub mod health_check_service;
+pub mod idempotency_service;
+pub mod pipeline_service;
+pub mod poller;
+pub mod replay_service;
+pub mod result_cache;
+pub mod schedule_service;
+pub mod session_service;
+pub mod spell_service;
 pub mod stripe_service;
+pub mod webhook_service;
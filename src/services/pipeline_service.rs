@@ -0,0 +1,613 @@
+use actix_web::web;
+use futures::future::join_all;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::errors::CastError;
+use crate::middleware::request_id;
+use crate::models::pipeline::{
+    PipelineDefinition, PipelineRun, PipelineStep, PipelineStepResult, MAX_PIPELINE_STEPS,
+    PIPELINE_INPUT, RUN_COMPLETED, RUN_FAILED, RUN_PARTIAL, STEP_SKIPPED,
+};
+use crate::models::spell::SpellVersion;
+use crate::models::Spell;
+use crate::services::budget_service::BudgetService;
+use crate::services::cast_service::{CastOptions, CastService};
+use crate::AppState;
+
+/// Chained casts run server-side as one request
+/// Steps are ordered into stages; the steps of a stage run concurrently once every step
+/// they depend on has completed. A failed step only skips the steps downstream of it.
+pub struct PipelineService;
+
+impl PipelineService {
+    /// Check a definition and order its steps into stages that may run concurrently
+    pub fn plan(definition: &PipelineDefinition) -> Result<Vec<Vec<usize>>, String> {
+        let steps = &definition.steps;
+        if steps.is_empty() || steps.len() > MAX_PIPELINE_STEPS {
+            return Err(format!("A pipeline needs 1-{MAX_PIPELINE_STEPS} steps"));
+        }
+
+        let mut index = HashMap::new();
+        for (i, step) in steps.iter().enumerate() {
+            if step.id.is_empty()
+                || step.id.len() > 64
+                || !step
+                    .id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+            {
+                return Err(format!(
+                    "Step id '{}' must be 1-64 characters of [A-Za-z0-9_-]",
+                    step.id
+                ));
+            }
+            if index.insert(step.id.as_str(), i).is_some() {
+                return Err(format!("Step id '{}' is used twice", step.id));
+            }
+        }
+
+        let mut deps: Vec<HashSet<usize>> = Vec::with_capacity(steps.len());
+        for (i, step) in steps.iter().enumerate() {
+            let mut step_deps = HashSet::new();
+            for mapping in &step.inputs {
+                for pointer in [&mapping.pointer, &mapping.to] {
+                    if !pointer.is_empty() && !pointer.starts_with('/') {
+                        return Err(format!(
+                            "Step '{}': '{pointer}' is not a JSON pointer",
+                            step.id
+                        ));
+                    }
+                }
+            }
+            let previous = i.checked_sub(1).map(|p| steps[p].id.as_str());
+            for name in dependency_names(step, previous) {
+                match index.get(name) {
+                    Some(&dep) if dep != i => {
+                        step_deps.insert(dep);
+                    }
+                    Some(_) => return Err(format!("Step '{}' depends on itself", step.id)),
+                    None => {
+                        return Err(format!(
+                            "Step '{}' refers to unknown step '{name}'",
+                            step.id
+                        ))
+                    }
+                }
+            }
+            deps.push(step_deps);
+        }
+
+        if let Some(output) = &definition.output_step {
+            if !index.contains_key(output.as_str()) {
+                return Err(format!("output_step '{output}' is not a step"));
+            }
+        }
+
+        // Peel off the steps whose dependencies are all placed, stage by stage
+        let mut placed = vec![false; steps.len()];
+        let mut stages = Vec::new();
+        while placed.iter().any(|p| !p) {
+            let stage: Vec<usize> = (0..steps.len())
+                .filter(|&i| !placed[i] && deps[i].iter().all(|&d| placed[d]))
+                .collect();
+            if stage.is_empty() {
+                return Err("Pipeline steps depend on each other in a cycle".to_string());
+            }
+            for &i in &stage {
+                placed[i] = true;
+            }
+            stages.push(stage);
+        }
+        Ok(stages)
+    }
+
+    /// Run a pipeline to completion and record the run
+    /// Every step's spell is resolved and the combined price reserved against the budget
+    /// before anything runs. Steps are recorded as casts linked to the run and billed
+    /// individually on success. The run is detached from the caller, so it finishes (and
+    /// its reservation is released) even if the request is dropped.
+    pub async fn run(
+        state: web::Data<AppState>,
+        user_id: Uuid,
+        pipeline_id: Option<Uuid>,
+        definition: PipelineDefinition,
+        input: Value,
+        bypass_cache: bool,
+    ) -> Result<PipelineRun, CastError> {
+        let run = tokio::spawn(request_id::scope(request_id::current(), async move {
+            Self::run_to_completion(
+                &state,
+                &user_id,
+                pipeline_id,
+                &definition,
+                input,
+                bypass_cache,
+            )
+            .await
+        }));
+        run.await.unwrap_or_else(|e| {
+            Err(CastError::InternalError(format!(
+                "Pipeline task failed: {e}"
+            )))
+        })
+    }
+
+    async fn run_to_completion(
+        state: &web::Data<AppState>,
+        user_id: &Uuid,
+        pipeline_id: Option<Uuid>,
+        definition: &PipelineDefinition,
+        input: Value,
+        bypass_cache: bool,
+    ) -> Result<PipelineRun, CastError> {
+        let stages = Self::plan(definition).map_err(CastError::InvalidInput)?;
+
+        let mut resolved = Vec::with_capacity(definition.steps.len());
+        for step in &definition.steps {
+            resolved.push(
+                CastService::resolve(&step.spell_name, step.version.as_deref(), &state.db).await?,
+            );
+        }
+
+        let now = chrono::Utc::now();
+        let prices: Vec<i32> = resolved
+            .iter()
+            .map(|(spell, _)| spell.effective_price_cents(now))
+            .collect();
+        let price: i64 = prices.iter().copied().map(i64::from).sum();
+        let reserve_cents = i32::try_from(price).map_err(|_| {
+            CastError::InvalidInput("Pipeline cost exceeds the maximum".to_string())
+        })?;
+        let reservation = BudgetService::reserve(user_id, reserve_cents, &state.db).await?;
+
+        let run_id = Uuid::new_v4();
+        let recorded = sqlx::query(
+            r#"
+            INSERT INTO pipeline_runs (id, user_id, pipeline_id, definition, input, request_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(run_id)
+        .bind(user_id)
+        .bind(pipeline_id)
+        .bind(sqlx::types::Json(definition))
+        .bind(&input)
+        .bind(request_id::current())
+        .execute(&state.db)
+        .await;
+
+        let run = match recorded {
+            Ok(_) => {
+                log::info!(
+                    "Pipeline run {run_id} starting: {} steps in {} stages by user {user_id}",
+                    definition.steps.len(),
+                    stages.len()
+                );
+                let ctx = RunContext {
+                    state,
+                    user_id,
+                    run_id,
+                    definition,
+                    resolved: &resolved,
+                    prices: &prices,
+                    reservation,
+                    input: &input,
+                    bypass_cache,
+                };
+                let steps = ctx.execute(&stages).await;
+                Self::finish(&ctx, steps).await
+            }
+            Err(e) => Err(e.into()),
+        };
+
+        if let Some(reservation_id) = reservation {
+            if let Err(e) = BudgetService::release(&reservation_id, &state.db).await {
+                log::error!("Failed to release budget reservation {reservation_id}: {e}");
+            }
+        }
+
+        run
+    }
+
+    /// Work out the run's status and output, and store them with the step outcomes
+    async fn finish(
+        ctx: &RunContext<'_>,
+        steps: Vec<PipelineStepResult>,
+    ) -> Result<PipelineRun, CastError> {
+        let succeeded = steps.iter().filter(|s| s.status == RUN_COMPLETED).count();
+        let status = if succeeded == steps.len() {
+            RUN_COMPLETED
+        } else if succeeded == 0 {
+            RUN_FAILED
+        } else {
+            RUN_PARTIAL
+        };
+        let output_id = ctx
+            .definition
+            .output_step
+            .as_deref()
+            .or_else(|| ctx.definition.steps.last().map(|s| s.id.as_str()));
+        let output = steps
+            .iter()
+            .find(|s| Some(s.id.as_str()) == output_id)
+            .and_then(|s| s.result.clone());
+        let cost_cents: i32 = steps.iter().map(|s| s.cost_cents).sum();
+
+        log::info!(
+            "Pipeline run {} finished {status}: {succeeded}/{} steps, {cost_cents} cents",
+            ctx.run_id,
+            steps.len()
+        );
+
+        let run: PipelineRun = sqlx::query_as(
+            r#"
+            UPDATE pipeline_runs
+            SET status = $2, output = $3, steps = $4, cost_cents = $5, finished_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(ctx.run_id)
+        .bind(status)
+        .bind(&output)
+        .bind(sqlx::types::Json(&steps))
+        .bind(cost_cents)
+        .fetch_one(&ctx.state.db)
+        .await?;
+        Ok(run)
+    }
+}
+
+/// Everything the steps of one run share
+struct RunContext<'a> {
+    state: &'a web::Data<AppState>,
+    user_id: &'a Uuid,
+    run_id: Uuid,
+    definition: &'a PipelineDefinition,
+    resolved: &'a [(Spell, SpellVersion)],
+    /// Each step's share of the reservation, given back as the step finishes
+    prices: &'a [i32],
+    reservation: Option<Uuid>,
+    input: &'a Value,
+    bypass_cache: bool,
+}
+
+impl RunContext<'_> {
+    /// Run the stages in order; returns one result per step, in definition order
+    async fn execute(&self, stages: &[Vec<usize>]) -> Vec<PipelineStepResult> {
+        let mut outcomes: HashMap<usize, PipelineStepResult> = HashMap::new();
+        for stage in stages {
+            let done = &outcomes;
+            let results = join_all(stage.iter().map(|&i| async move {
+                let result = self.run_step(i, done).await;
+                self.draw_down(i).await;
+                result
+            }))
+            .await;
+            for (&i, result) in stage.iter().zip(results) {
+                outcomes.insert(i, result);
+            }
+        }
+        (0..self.definition.steps.len())
+            .filter_map(|i| outcomes.remove(&i))
+            .collect()
+    }
+
+    /// Stop holding a finished step's share of the reservation
+    async fn draw_down(&self, index: usize) {
+        let Some(reservation_id) = &self.reservation else {
+            return;
+        };
+        if let Err(e) =
+            BudgetService::draw_down(reservation_id, self.prices[index], &self.state.db).await
+        {
+            log::error!("Failed to draw down reservation {reservation_id}: {e}");
+        }
+    }
+
+    async fn run_step(
+        &self,
+        index: usize,
+        done: &HashMap<usize, PipelineStepResult>,
+    ) -> PipelineStepResult {
+        let step = &self.definition.steps[index];
+        let mut outcome = PipelineStepResult {
+            id: step.id.clone(),
+            spell_name: step.spell_name.clone(),
+            status: STEP_SKIPPED.to_string(),
+            cast_id: None,
+            result: None,
+            error_code: None,
+            error: None,
+            attempts: None,
+            cached: false,
+            cost_cents: 0,
+        };
+
+        let results: HashMap<&str, &Value> = done
+            .values()
+            .filter(|s| s.status == RUN_COMPLETED)
+            .filter_map(|s| s.result.as_ref().map(|r| (s.id.as_str(), r)))
+            .collect();
+        let previous = index
+            .checked_sub(1)
+            .map(|i| self.definition.steps[i].id.as_str());
+        if let Some(blocked) = dependency_names(step, previous).find(|d| !results.contains_key(d)) {
+            outcome.error = Some(format!("Step '{blocked}' did not complete"));
+            return outcome;
+        }
+
+        let payload = match step_payload(step, previous, self.input, &results) {
+            Ok(payload) => payload,
+            Err(e) => return failed(outcome, None, e),
+        };
+
+        let (spell, version) = &self.resolved[index];
+        let options = CastOptions {
+            bypass_cache: self.bypass_cache,
+            pipeline_run_id: Some(self.run_id),
+            pipeline_step: Some(step.id.clone()),
+            ..Default::default()
+        };
+        let cast = match CastService::record(
+            self.user_id,
+            spell,
+            version,
+            &payload,
+            options,
+            &self.state.db,
+        )
+        .await
+        {
+            Ok(cast) => cast,
+            Err(e) => return failed(outcome, None, e),
+        };
+
+        let execution = match CastService::mark_running(&cast, &self.state.db).await {
+            Ok(_) => CastService::execute(self.state, &cast).await,
+            Err(e) => Err(e),
+        };
+        match execution {
+            Ok(execution) => {
+                outcome.status = RUN_COMPLETED.to_string();
+                outcome.cast_id = Some(cast.id);
+                outcome.result = Some(execution.output);
+                outcome.attempts = Some(execution.attempts);
+                outcome.cached = execution.cached;
+                outcome.cost_cents = execution.cost_cents;
+                outcome
+            }
+            Err(e) => failed(outcome, Some(cast.id), e),
+        }
+    }
+}
+
+fn failed(
+    mut outcome: PipelineStepResult,
+    cast_id: Option<Uuid>,
+    error: CastError,
+) -> PipelineStepResult {
+    outcome.status = match error {
+        CastError::Canceled => "CANCELED",
+        _ => "FAILED",
+    }
+    .to_string();
+    outcome.cast_id = cast_id;
+    outcome.error_code = Some(error.error_code().to_string());
+    outcome.error = Some(error.to_string());
+    outcome
+}
+
+/// A step with neither mappings nor a payload is fed the previous step's result
+fn takes_previous_result(step: &PipelineStep) -> bool {
+    step.inputs.is_empty() && step.payload.is_none()
+}
+
+/// Ids of the steps `step` waits for; `previous` is the step listed before it
+fn dependency_names<'a>(
+    step: &'a PipelineStep,
+    previous: Option<&'a str>,
+) -> impl Iterator<Item = &'a str> {
+    step.inputs
+        .iter()
+        .map(|m| m.from.as_str())
+        .filter(|from| *from != PIPELINE_INPUT)
+        .chain(step.depends_on.iter().map(String::as_str))
+        .chain(previous.filter(|_| takes_previous_result(step)))
+}
+
+/// Build a step's payload from its template and mappings
+fn step_payload(
+    step: &PipelineStep,
+    previous: Option<&str>,
+    input: &Value,
+    results: &HashMap<&str, &Value>,
+) -> Result<Value, CastError> {
+    if takes_previous_result(step) {
+        return Ok(match previous {
+            Some(id) => results.get(id).map(|r| (*r).clone()).unwrap_or_default(),
+            None => input.clone(),
+        });
+    }
+
+    let mut payload = step
+        .payload
+        .clone()
+        .unwrap_or_else(|| Value::Object(Map::new()));
+    for mapping in &step.inputs {
+        let source = if mapping.from == PIPELINE_INPUT {
+            input
+        } else {
+            results
+                .get(mapping.from.as_str())
+                .copied()
+                .unwrap_or(&Value::Null)
+        };
+        let value = source.pointer(&mapping.pointer).ok_or_else(|| {
+            CastError::InvalidInput(format!(
+                "Step '{}': nothing at '{}' in {}",
+                step.id, mapping.pointer, mapping.from
+            ))
+        })?;
+        set_pointer(&mut payload, &mapping.to, value.clone())
+            .map_err(|e| CastError::InvalidInput(format!("Step '{}': {e}", step.id)))?;
+    }
+    Ok(payload)
+}
+
+/// Write `value` at a JSON pointer, creating objects along the way
+/// Array elements may be replaced, or appended with `-`.
+fn set_pointer(target: &mut Value, pointer: &str, value: Value) -> Result<(), String> {
+    if pointer.is_empty() {
+        *target = value;
+        return Ok(());
+    }
+    let tokens: Vec<String> = pointer
+        .split('/')
+        .skip(1)
+        .map(|t| t.replace("~1", "/").replace("~0", "~"))
+        .collect();
+
+    let (last, parents) = tokens.split_last().ok_or("empty JSON pointer")?;
+    let mut current = target;
+    for token in parents {
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+        current = match current {
+            Value::Object(map) => map
+                .entry(token.clone())
+                .or_insert_with(|| Value::Object(Map::new())),
+            Value::Array(items) => token
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| items.get_mut(i))
+                .ok_or_else(|| format!("no array element '{token}' in '{pointer}'"))?,
+            _ => return Err(format!("can't write '{pointer}' through a non-container")),
+        };
+    }
+
+    if current.is_null() {
+        *current = Value::Object(Map::new());
+    }
+    match current {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(items) if last == "-" => items.push(value),
+        Value::Array(items) => {
+            let slot = last
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| items.get_mut(i))
+                .ok_or_else(|| format!("no array element '{last}' in '{pointer}'"))?;
+            *slot = value;
+        }
+        _ => return Err(format!("can't write '{pointer}' through a non-container")),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pipeline::InputMapping;
+
+    fn step(id: &str, inputs: &[(&str, &str, &str)], depends_on: &[&str]) -> PipelineStep {
+        PipelineStep {
+            id: id.to_string(),
+            spell_name: "echo".to_string(),
+            version: None,
+            payload: None,
+            inputs: inputs
+                .iter()
+                .map(|(from, pointer, to)| InputMapping {
+                    from: from.to_string(),
+                    pointer: pointer.to_string(),
+                    to: to.to_string(),
+                })
+                .collect(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    fn definition(steps: Vec<PipelineStep>) -> PipelineDefinition {
+        PipelineDefinition {
+            steps,
+            output_step: None,
+        }
+    }
+
+    #[test]
+    fn plans_sequences_and_dags_into_stages() {
+        // A plain sequence chains each step to the one before it
+        let sequence = definition(vec![step("a", &[], &[]), step("b", &[], &[])]);
+        assert_eq!(
+            PipelineService::plan(&sequence).unwrap(),
+            vec![vec![0], vec![1]]
+        );
+
+        // Two branches off the input run together, then merge
+        let dag = definition(vec![
+            step("left", &[("$input", "/a", "")], &[]),
+            step("right", &[("$input", "/b", "")], &[]),
+            step("merge", &[("left", "", "/l"), ("right", "", "/r")], &[]),
+        ]);
+        assert_eq!(
+            PipelineService::plan(&dag).unwrap(),
+            vec![vec![0, 1], vec![2]]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_steps_and_cycles() {
+        let unknown = definition(vec![step("a", &[("ghost", "", "")], &[])]);
+        assert!(PipelineService::plan(&unknown)
+            .unwrap_err()
+            .contains("ghost"));
+
+        let cycle = definition(vec![
+            step("a", &[("$input", "", "")], &["b"]),
+            step("b", &[("a", "", "")], &[]),
+        ]);
+        assert!(PipelineService::plan(&cycle).unwrap_err().contains("cycle"));
+
+        let duplicate = definition(vec![step("a", &[], &[]), step("a", &[], &[])]);
+        assert!(PipelineService::plan(&duplicate).is_err());
+    }
+
+    #[test]
+    fn mappings_build_step_payloads() {
+        let mut merge = step(
+            "merge",
+            &[
+                ("left", "/url", "/images/0"),
+                ("$input", "/size", "/opts/size"),
+            ],
+            &[],
+        );
+        merge.payload = Some(serde_json::json!({"images": [null], "mode": "fit"}));
+
+        let left = serde_json::json!({"url": "https://img/1.png"});
+        let results = HashMap::from([("left", &left)]);
+        let input = serde_json::json!({"size": 128});
+
+        let payload = step_payload(&merge, None, &input, &results).unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "images": ["https://img/1.png"],
+                "mode": "fit",
+                "opts": {"size": 128}
+            })
+        );
+
+        let missing = step("m", &[("$input", "/nope", "")], &[]);
+        assert!(matches!(
+            step_payload(&missing, None, &input, &results),
+            Err(CastError::InvalidInput(_))
+        ));
+    }
+}
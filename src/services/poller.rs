@@ -0,0 +1,27 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+/// Run a background poll for the lifetime of the process
+/// Each poll claims and handles up to `batch_size` due items and returns how many it
+/// took. A full batch means more may be waiting, so the next poll starts immediately;
+/// otherwise it waits `interval`. A failed poll is logged and retried after `interval`.
+pub fn spawn<F, Fut, E>(name: &'static str, interval: Duration, batch_size: i64, mut poll: F)
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<usize, E>> + Send,
+    E: Display,
+{
+    tokio::spawn(async move {
+        log::info!("{name} started");
+
+        loop {
+            match poll().await {
+                Ok(n) if n as i64 == batch_size => continue,
+                Ok(_) => {}
+                Err(e) => log::error!("{name} failed: {e}"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
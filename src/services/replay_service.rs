@@ -0,0 +1,231 @@
+use actix_web::web;
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{Postgres, QueryBuilder};
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::errors::CastError;
+use crate::models::replay::{
+    ReplayReport, ReplayRequest, ReplayResult, DEFAULT_REPLAY_CASTS, MAX_REPLAY_CASTS,
+};
+use crate::models::spell::SpellVersion;
+use crate::models::{InputFile, Spell};
+use crate::services::cast_service::CastService;
+use crate::utils::json_diff;
+use crate::AppState;
+
+const REPLAY_PARALLELISM: usize = 4;
+
+/// A finished cast to replay, with what it originally produced
+#[derive(sqlx::FromRow)]
+struct ReplaySource {
+    id: Uuid,
+    payload: Value,
+    input_files: Json<Vec<InputFile>>,
+    status: String,
+    result: Option<Value>,
+    error_code: Option<String>,
+    spell_version: Option<String>,
+}
+
+/// Re-runs past casts of a spell against another of its versions and diffs the outputs
+/// Replays run sandboxed: they are not recorded as casts, never touch the result cache
+/// and are not billed to anyone.
+pub struct ReplayService;
+
+impl ReplayService {
+    pub async fn replay(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        version: &SpellVersion,
+        req: &ReplayRequest,
+    ) -> Result<ReplayReport, CastError> {
+        let sources = Self::sources(state, spell, req).await?;
+
+        log::info!(
+            "Replaying {} cast(s) of {} against version {}",
+            sources.len(),
+            spell.name,
+            version.version
+        );
+
+        let results: Vec<ReplayResult> = stream::iter(&sources)
+            .map(|source| async move {
+                let started = Instant::now();
+                let outcome = CastService::run_sandboxed(
+                    state,
+                    spell,
+                    version,
+                    &source.payload,
+                    &source.input_files,
+                )
+                .await;
+                Self::compare(source, outcome, started.elapsed().as_millis() as u64)
+            })
+            .buffered(REPLAY_PARALLELISM)
+            .collect()
+            .await;
+
+        let identical = results.iter().filter(|r| r.identical).count();
+        Ok(ReplayReport {
+            spell_name: spell.name.clone(),
+            version: version.version.clone(),
+            replayed: results.len(),
+            identical,
+            changed: results.len() - identical,
+            results,
+        })
+    }
+
+    /// The casts a request selects: the listed ids, or the newest matching the filters
+    /// Only COMPLETED and FAILED casts of the spell can be replayed.
+    async fn sources(
+        state: &web::Data<AppState>,
+        spell: &Spell,
+        req: &ReplayRequest,
+    ) -> Result<Vec<ReplaySource>, CastError> {
+        let limit = if req.cast_ids.is_empty() {
+            req.limit.unwrap_or(DEFAULT_REPLAY_CASTS)
+        } else {
+            req.cast_ids.len()
+        };
+        if limit == 0 || limit > MAX_REPLAY_CASTS {
+            return Err(CastError::InvalidInput(format!(
+                "A replay covers 1-{MAX_REPLAY_CASTS} casts"
+            )));
+        }
+
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+            "SELECT c.id, c.payload, c.input_files, c.status, c.result, c.error_code, \
+             v.version AS spell_version \
+             FROM casts c LEFT JOIN spell_versions v ON v.id = c.spell_version_id \
+             WHERE c.status IN ('COMPLETED', 'FAILED') AND c.spell_id = ",
+        );
+        qb.push_bind(spell.id);
+
+        if req.cast_ids.is_empty() {
+            let status = req.status.as_deref().unwrap_or("COMPLETED").to_uppercase();
+            qb.push(" AND c.status = ").push_bind(status);
+            if let Some(from_version) = &req.from_version {
+                qb.push(" AND v.version = ").push_bind(from_version);
+            }
+            if let Some(from) = req.from {
+                qb.push(" AND c.created_at >= ").push_bind(from);
+            }
+            if let Some(to) = req.to {
+                qb.push(" AND c.created_at < ").push_bind(to);
+            }
+        } else {
+            qb.push(" AND c.id = ANY(")
+                .push_bind(&req.cast_ids)
+                .push(")");
+        }
+        qb.push(" ORDER BY c.created_at DESC LIMIT ")
+            .push_bind(limit as i64);
+
+        let sources: Vec<ReplaySource> = qb.build_query_as().fetch_all(&state.db).await?;
+
+        let missing: Vec<String> = req
+            .cast_ids
+            .iter()
+            .filter(|id| !sources.iter().any(|s| s.id == **id))
+            .map(Uuid::to_string)
+            .collect();
+        if !missing.is_empty() {
+            return Err(CastError::InvalidInput(format!(
+                "Not finished casts of {}: {}",
+                spell.name,
+                missing.join(", ")
+            )));
+        }
+
+        Ok(sources)
+    }
+
+    fn compare(
+        source: &ReplaySource,
+        outcome: Result<Value, CastError>,
+        duration_ms: u64,
+    ) -> ReplayResult {
+        let mut result = ReplayResult {
+            cast_id: source.id,
+            original_version: source.spell_version.clone(),
+            original_status: source.status.clone(),
+            replay_status: "COMPLETED".to_string(),
+            identical: false,
+            diff: Vec::new(),
+            original_error_code: source.error_code.clone(),
+            error_code: None,
+            error: None,
+            duration_ms,
+        };
+
+        match (source.status.as_str(), &source.result, outcome) {
+            ("COMPLETED", Some(original), Ok(output)) => {
+                result.diff = json_diff::diff(original, &output);
+                result.identical = result.diff.is_empty();
+            }
+            (_, _, Ok(_)) => {}
+            (_, _, Err(e)) => {
+                result.replay_status = "FAILED".to_string();
+                result.identical = source.status == "FAILED"
+                    && source.error_code.as_deref() == Some(e.error_code());
+                result.error_code = Some(e.error_code().to_string());
+                result.error = Some(e.to_string());
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn source(status: &str, result: Option<Value>, error_code: Option<&str>) -> ReplaySource {
+        ReplaySource {
+            id: Uuid::new_v4(),
+            payload: json!({}),
+            input_files: Json(Vec::new()),
+            status: status.to_string(),
+            result,
+            error_code: error_code.map(str::to_string),
+            spell_version: Some("1.0.0".to_string()),
+        }
+    }
+
+    #[test]
+    fn completed_replays_are_diffed_against_the_original_result() {
+        let original = source("COMPLETED", Some(json!({"n": 1, "tag": "a"})), None);
+
+        let same = ReplayService::compare(&original, Ok(json!({"n": 1, "tag": "a"})), 5);
+        assert!(same.identical);
+        assert!(same.diff.is_empty());
+
+        let changed = ReplayService::compare(&original, Ok(json!({"n": 2, "tag": "a"})), 5);
+        assert!(!changed.identical);
+        assert_eq!(changed.diff.len(), 1);
+        assert_eq!(changed.diff[0].path, "/n");
+    }
+
+    #[test]
+    fn failures_match_only_on_error_code() {
+        let failed = source("FAILED", None, Some("WASM_TIMEOUT"));
+
+        let same = ReplayService::compare(&failed, Err(CastError::WasmTimeout), 5);
+        assert_eq!(same.replay_status, "FAILED");
+        assert!(same.identical);
+
+        let fixed = ReplayService::compare(&failed, Ok(json!({"ok": true})), 5);
+        assert_eq!(fixed.replay_status, "COMPLETED");
+        assert!(!fixed.identical);
+
+        let completed = source("COMPLETED", Some(json!(1)), None);
+        let broken = ReplayService::compare(&completed, Err(CastError::WasmTimeout), 5);
+        assert!(!broken.identical);
+        assert_eq!(broken.error_code.as_deref(), Some("WASM_TIMEOUT"));
+    }
+}
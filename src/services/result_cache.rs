@@ -0,0 +1,59 @@
+use deadpool_redis::Pool;
+use serde_json::Value;
+
+use crate::utils::canonical::canonical_hash;
+
+/// Redis cache of outputs from pure spell versions
+/// Entries are keyed by module digest and canonical input hash, so a republished module
+/// or any change to the input misses.
+pub struct ResultCache;
+
+impl ResultCache {
+    pub fn key(digest: &str, payload: &Value) -> String {
+        format!("cast:result:{digest}:{}", canonical_hash(payload))
+    }
+
+    pub async fn get(redis: &Pool, key: &str) -> Result<Option<Value>, anyhow::Error> {
+        let mut conn = redis.get().await?;
+        let cached: Option<String> = redis::cmd("GET").arg(key).query_async(&mut *conn).await?;
+        Ok(cached.map(|raw| serde_json::from_str(&raw)).transpose()?)
+    }
+
+    pub async fn put(
+        redis: &Pool,
+        key: &str,
+        output: &Value,
+        ttl_secs: u64,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = redis.get().await?;
+        redis::cmd("SET")
+            .arg(key)
+            .arg(serde_json::to_string(output)?)
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async::<_, ()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn key_ignores_input_key_order_but_not_digest() {
+        let a = json!({"w": 10, "h": 20});
+        let b: Value = serde_json::from_str(r#"{"h": 20, "w": 10}"#).unwrap();
+
+        assert_eq!(
+            ResultCache::key("sha256:1", &a),
+            ResultCache::key("sha256:1", &b)
+        );
+        assert_ne!(
+            ResultCache::key("sha256:1", &a),
+            ResultCache::key("sha256:2", &a)
+        );
+    }
+}
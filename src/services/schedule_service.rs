@@ -0,0 +1,282 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde_json::Value;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::errors::ApiError;
+use crate::middleware::rate_limit::{check_rate_limit, user_key, USER_LIMIT_PER_MIN};
+use crate::middleware::request_id;
+use crate::models::schedule::{RUN_FAILED, RUN_QUEUED, RUN_SKIPPED};
+use crate::models::CastRequest;
+use crate::services::cast_service::{CastOptions, CastService};
+use crate::services::poller;
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 20;
+// Claimed schedules are hidden from other schedulers for this long
+const CLAIM_LEASE_SECS: i64 = 60;
+
+#[derive(sqlx::FromRow)]
+struct DueSchedule {
+    id: Uuid,
+    user_id: Uuid,
+    spell_name: String,
+    version: Option<String>,
+    payload: Value,
+    cron: String,
+}
+
+/// Casts spells on their owners' cron schedules
+/// Each due run is cast like an async `/v1/cast` request: it counts against the owner's
+/// rate limit and hard budget, is recorded as a cast linked to its schedule and runs on
+/// the worker pool. Runs missed while no scheduler was up are not made up.
+pub struct ScheduleService;
+
+impl ScheduleService {
+    /// Parse a five-field cron expression (minute hour day-of-month month day-of-week)
+    /// or one of `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly`.
+    /// Day-of-week numbers follow standard cron: 0 or 7 is Sunday, 1 is Monday.
+    pub fn parse(expression: &str) -> Result<Schedule, String> {
+        let expression = expression.trim();
+        let normalized = if expression.starts_with('@') {
+            expression.to_string()
+        } else {
+            let fields: Vec<&str> = expression.split_whitespace().collect();
+            let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+                return Err(
+                    "Cron expressions have five fields: minute hour day-of-month month day-of-week"
+                        .to_string(),
+                );
+            };
+            let day_of_week = Self::standard_days_of_week(day_of_week)?;
+            // The parser wants a leading seconds field; runs start on the minute
+            format!("0 {minute} {hour} {day_of_month} {month} {day_of_week}")
+        };
+        Schedule::from_str(&normalized).map_err(|e| format!("Invalid cron expression: {e}"))
+    }
+
+    /// First run strictly after `after`, or an error if the expression never fires again
+    pub fn next_run(expression: &str, after: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+        Self::parse(expression)?
+            .after(&after)
+            .next()
+            .ok_or_else(|| "Cron expression never fires".to_string())
+    }
+
+    /// Renumber a day-of-week field from standard cron (Sunday 0) to the parser's (Sunday 1)
+    fn standard_days_of_week(field: &str) -> Result<String, String> {
+        let day = |raw: &str| -> Result<Option<u8>, String> {
+            if !raw.bytes().all(|b| b.is_ascii_digit()) {
+                // `*` or a day name, which both conventions share
+                return Ok(None);
+            }
+            match raw.parse::<u8>() {
+                Ok(day) if day <= 7 => Ok(Some(day)),
+                _ => Err(format!("Day of week {raw} is out of range 0-7")),
+            }
+        };
+
+        let mut items = Vec::new();
+        for item in field.split(',') {
+            let (base, step) = match item.split_once('/') {
+                Some((base, step)) => (base, Some(step)),
+                None => (item, None),
+            };
+            let step = step.map_or(String::new(), |step| format!("/{step}"));
+            match base.split_once('-') {
+                Some((start, end)) => match (day(start)?, day(end)?) {
+                    // 7 ends the range on Saturday, then wraps to Sunday
+                    (Some(start), Some(7)) if step.is_empty() => {
+                        items.push(format!("{}-7", start + 1));
+                        if start > 0 {
+                            items.push("1".to_string());
+                        }
+                    }
+                    (Some(_), Some(7)) => {
+                        return Err("Day-of-week ranges with a step must end by 6".to_string())
+                    }
+                    (Some(start), Some(end)) => {
+                        items.push(format!("{}-{}{step}", start + 1, end + 1))
+                    }
+                    (None, None) => items.push(item.to_string()),
+                    _ => return Err(format!("Day-of-week range {base} mixes numbers and names")),
+                },
+                None => match day(base)? {
+                    Some(day) => items.push(format!("{}{step}", day % 7 + 1)),
+                    None => items.push(item.to_string()),
+                },
+            }
+        }
+        Ok(items.join(","))
+    }
+
+    /// Start the scheduler for the lifetime of the process
+    /// Several instances may run it; each due schedule is claimed by one of them.
+    pub fn spawn_scheduler(state: web::Data<AppState>) {
+        poller::spawn("Cast scheduler", POLL_INTERVAL, BATCH_SIZE, move || {
+            let state = state.clone();
+            async move { Self::run_due(&state).await }
+        });
+    }
+
+    async fn run_due(state: &web::Data<AppState>) -> Result<usize, sqlx::Error> {
+        let due: Vec<DueSchedule> = sqlx::query_as(
+            r#"
+            UPDATE cast_schedules
+            SET next_run_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM cast_schedules
+                WHERE status = 'ACTIVE' AND next_run_at <= NOW()
+                ORDER BY next_run_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, user_id, spell_name, version, payload, cron
+            "#,
+        )
+        .bind(BATCH_SIZE)
+        .bind(CLAIM_LEASE_SECS as f64)
+        .fetch_all(&state.db)
+        .await?;
+
+        let count = due.len();
+        futures::future::join_all(due.iter().map(|schedule| {
+            let request_id = Some(Uuid::new_v4().to_string());
+            request_id::scope(request_id, Self::trigger(state, schedule))
+        }))
+        .await;
+        Ok(count)
+    }
+
+    /// Cast one due run and record its outcome and the next run time on the schedule
+    async fn trigger(state: &web::Data<AppState>, schedule: &DueSchedule) {
+        let schedule_id = schedule.id;
+        let next_run_at = match Self::next_run(&schedule.cron, Utc::now()) {
+            Ok(next_run_at) => Some(next_run_at),
+            Err(e) => {
+                log::warn!("Schedule {schedule_id} will not run again: {e}");
+                None
+            }
+        };
+
+        let (cast_id, status, error) = match Self::cast(state, schedule).await {
+            Ok(cast_id) => match CastService::enqueue(state, &cast_id).await {
+                Ok(()) => {
+                    log::info!("Schedule {schedule_id} queued cast {cast_id}");
+                    (Some(cast_id), RUN_QUEUED, None)
+                }
+                Err(e) => {
+                    log::warn!("Schedule {schedule_id} run failed: {e}");
+                    (Some(cast_id), RUN_FAILED, Some(ApiError::from(e)))
+                }
+            },
+            Err(e) => {
+                log::warn!("Schedule {schedule_id} run skipped: {e}");
+                (None, RUN_SKIPPED, Some(e))
+            }
+        };
+
+        // A schedule paused while this run was being cast stays paused
+        if let Err(e) = sqlx::query(
+            r#"
+            UPDATE cast_schedules
+            SET next_run_at = CASE WHEN status = 'ACTIVE' THEN $2 ELSE next_run_at END,
+                last_run_at = NOW(), last_cast_id = $3, last_status = $4,
+                last_error_code = $5, last_error = $6
+            WHERE id = $1
+            "#,
+        )
+        .bind(schedule_id)
+        .bind(next_run_at)
+        .bind(cast_id)
+        .bind(status)
+        .bind(error.as_ref().map(|e| e.error_code().to_string()))
+        .bind(error.as_ref().map(|e| e.to_string()))
+        .execute(&state.db)
+        .await
+        {
+            log::error!("Failed to record run of schedule {schedule_id}: {e}");
+        }
+    }
+
+    /// Record the run's cast, subject to the owner's rate limit and budget
+    async fn cast(state: &web::Data<AppState>, schedule: &DueSchedule) -> Result<Uuid, ApiError> {
+        let user_id = schedule.user_id;
+        match check_rate_limit(&state.redis, &user_key(&user_id), USER_LIMIT_PER_MIN).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err(ApiError::RateLimited {
+                    retry_after_secs: 60,
+                })
+            }
+            // Fail open, like the request rate limiter
+            Err(e) => log::error!("Rate limit check failed: {e}"),
+        }
+
+        let req = CastRequest {
+            spell_name: schedule.spell_name.clone(),
+            version: schedule.version.clone(),
+            payload: schedule.payload.clone(),
+            run_async: true,
+            callback_url: None,
+            bypass_cache: false,
+        };
+        let options = CastOptions {
+            schedule_id: Some(schedule.id),
+            run_async: true,
+            ..Default::default()
+        };
+        let cast = CastService::prepare(&user_id, &req, options, &state.db).await?;
+        Ok(cast.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone, Timelike, Weekday};
+
+    #[test]
+    fn five_field_expressions_run_on_the_minute() {
+        let after = Utc.with_ymd_and_hms(2026, 3, 14, 10, 20, 30).unwrap();
+
+        let hourly = ScheduleService::next_run("0 * * * *", after).unwrap();
+        assert_eq!(hourly, Utc.with_ymd_and_hms(2026, 3, 14, 11, 0, 0).unwrap());
+
+        let quarterly = ScheduleService::next_run("*/15 * * * *", after).unwrap();
+        assert_eq!(
+            quarterly,
+            Utc.with_ymd_and_hms(2026, 3, 14, 10, 30, 0).unwrap()
+        );
+
+        let daily = ScheduleService::next_run("@daily", after).unwrap();
+        assert_eq!((daily.day(), daily.hour()), (15, 0));
+
+        assert!(ScheduleService::parse("* * * * * *").is_err());
+        assert!(ScheduleService::parse("61 * * * *").is_err());
+        assert!(ScheduleService::next_run("0 0 30 2 *", after).is_err());
+    }
+
+    #[test]
+    fn day_of_week_numbers_follow_standard_cron() {
+        // 2026-03-14 is a Saturday
+        let after = Utc.with_ymd_and_hms(2026, 3, 14, 10, 0, 0).unwrap();
+        let weekday = |expression: &str| {
+            ScheduleService::next_run(expression, after)
+                .unwrap()
+                .weekday()
+        };
+
+        assert_eq!(weekday("0 9 * * 1-5"), Weekday::Mon);
+        assert_eq!(weekday("0 9 * * 0"), Weekday::Sun);
+        assert_eq!(weekday("0 9 * * 7"), Weekday::Sun);
+        assert_eq!(weekday("0 9 * * 3,5"), Weekday::Wed);
+        assert_eq!(weekday("0 9 * * 1-7"), Weekday::Sun);
+        assert_eq!(weekday("0 9 * * MON-FRI"), Weekday::Mon);
+        assert!(ScheduleService::parse("0 9 * * 8").is_err());
+    }
+}
@@ -0,0 +1,429 @@
+use actix_web::web;
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, CloseCode, CloseReason};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::mpsc::Receiver;
+use uuid::Uuid;
+
+use crate::errors::CastError;
+use crate::middleware::request_id;
+use crate::models::spell::SpellVersion;
+use crate::models::Spell;
+use crate::services::budget_service::BudgetService;
+use crate::services::cast_events::{CastEvent, EventSink};
+use crate::services::cast_service::{CastOptions, CastService};
+use crate::services::circuit_breaker::{Admission, CircuitBreaker};
+use crate::services::contract_service::ContractService;
+use crate::wasm::{CancelToken, SpellSession};
+use crate::AppState;
+
+// A session with no messages for this long is closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// Hard cap on how long one instance stays warm
+const MAX_SESSION_DURATION: Duration = Duration::from_secs(60 * 60);
+// A single message may not keep the instance busy longer than this
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
+pub const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+// Sessions a user may hold open at once; each pins a warm instance
+pub const MAX_OPEN_SESSIONS: i64 = 5;
+
+/// An opened session, before its instance is started
+pub struct SessionContext {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub spell: Spell,
+    pub version: SpellVersion,
+    pub module: wasmtime::Module,
+    /// How the circuit let the session through; reported with its first message
+    pub admission: Admission,
+}
+
+/// The client connection plus the spell's log and output events awaiting forwarding
+struct SessionIo {
+    ws: actix_ws::Session,
+    messages: AggregatedMessageStream,
+    sink: EventSink,
+    events: Receiver<CastEvent>,
+}
+
+impl SessionIo {
+    async fn send(&mut self, event: &CastEvent) -> Result<(), actix_ws::Closed> {
+        self.ws
+            .text(serde_json::to_string(event).unwrap_or_default())
+            .await
+    }
+}
+
+/// Running totals reported when the session closes
+#[derive(Default)]
+struct SessionTotals {
+    messages: i32,
+    cost_cents: i32,
+}
+
+/// Interactive WebSocket sessions on one warm spell instance
+/// Every message is recorded as a cast of the session. `per_call` spells charge each
+/// message; `per_session` spells charge their price once, on the first successful message.
+pub struct SessionService;
+
+impl SessionService {
+    /// Record a new OPEN session
+    /// Returns None when the user already holds `MAX_OPEN_SESSIONS`. Sessions left OPEN
+    /// by a crashed instance stop counting once they are older than the longest session.
+    pub async fn open(
+        user_id: &Uuid,
+        api_key_id: &Uuid,
+        spell: &Spell,
+        version: &SpellVersion,
+        db: &PgPool,
+    ) -> Result<Option<Uuid>, CastError> {
+        let mut tx = db.begin().await?;
+
+        // Serialize opens per user so concurrent handshakes can't all pass the count
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1::text, 0))")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        let (open,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM spell_sessions
+            WHERE user_id = $1 AND status = 'OPEN'
+              AND started_at > NOW() - make_interval(secs => $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(MAX_SESSION_DURATION.as_secs_f64())
+        .fetch_one(&mut *tx)
+        .await?;
+        if open >= MAX_OPEN_SESSIONS {
+            return Ok(None);
+        }
+
+        let session_id = Uuid::new_v4();
+        sqlx::query(
+            r#"
+            INSERT INTO spell_sessions (id, user_id, api_key_id, spell_id, spell_version_id, request_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(api_key_id)
+        .bind(spell.id)
+        .bind(version.id)
+        .bind(request_id::current())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(Some(session_id))
+    }
+
+    /// Serve a session until the caller leaves, a limit is hit or the budget runs out
+    pub async fn run(
+        state: web::Data<AppState>,
+        ws: actix_ws::Session,
+        messages: AggregatedMessageStream,
+        ctx: SessionContext,
+    ) {
+        let (events, rx) = EventSink::channel();
+        let mut io = SessionIo {
+            ws,
+            messages,
+            sink: events.clone(),
+            events: rx,
+        };
+        let cancel = CancelToken::default();
+
+        let started = {
+            let runtime = state.clone();
+            let (name, module) = (ctx.spell.name.clone(), ctx.module.clone());
+            let formats = (ctx.version.input_format(), ctx.version.output_format());
+            let (cancel, events) = (cancel.clone(), events.clone());
+            tokio::task::spawn_blocking(move || {
+                runtime
+                    .wasm
+                    .start_session(&name, &module, formats, &cancel, &events)
+            })
+            .await
+            .unwrap_or_else(|e| {
+                Err(CastError::InternalError(format!(
+                    "Session task failed: {e}"
+                )))
+            })
+        };
+
+        let mut totals = SessionTotals::default();
+        let close = match started {
+            Ok(instance) => {
+                let _ = io
+                    .send(&CastEvent::Session {
+                        session_id: ctx.id,
+                        status: "OPEN",
+                        messages: 0,
+                        cost_cents: 0,
+                        reason: None,
+                    })
+                    .await;
+                Self::serve(&state, &mut io, &ctx, instance, &cancel, &mut totals).await
+            }
+            Err(e) => {
+                let _ = io.send(&CastEvent::Error(e.problem())).await;
+                CloseReason {
+                    code: CloseCode::Error,
+                    description: Some(e.error_code().to_string()),
+                }
+            }
+        };
+
+        // Stop anything still running on the instance before releasing it
+        cancel.cancel();
+
+        let reason = close.description.clone().unwrap_or_default();
+        if let Err(e) = Self::close(&ctx.id, &totals, &reason, &state.db).await {
+            log::error!("Failed to close session {}: {e}", ctx.id);
+        }
+        log::info!(
+            "Session {} closed ({reason}) after {} message(s), {} cents",
+            ctx.id,
+            totals.messages,
+            totals.cost_cents
+        );
+
+        let _ = io
+            .send(&CastEvent::Session {
+                session_id: ctx.id,
+                status: "CLOSED",
+                messages: totals.messages,
+                cost_cents: totals.cost_cents,
+                reason: Some(reason),
+            })
+            .await;
+        let _ = io.ws.close(Some(close)).await;
+    }
+
+    /// Message loop; returns why the session ended
+    async fn serve(
+        state: &web::Data<AppState>,
+        io: &mut SessionIo,
+        ctx: &SessionContext,
+        mut instance: SpellSession,
+        cancel: &CancelToken,
+        totals: &mut SessionTotals,
+    ) -> CloseReason {
+        let deadline = tokio::time::sleep(MAX_SESSION_DURATION);
+        tokio::pin!(deadline);
+
+        loop {
+            let message = tokio::select! {
+                message = io.messages.recv() => message,
+                _ = tokio::time::sleep(IDLE_TIMEOUT) => return close_reason(CloseCode::Normal, "idle_timeout"),
+                _ = &mut deadline => return close_reason(CloseCode::Normal, "max_duration"),
+            };
+
+            let text = match message {
+                Some(Ok(AggregatedMessage::Text(text))) => text,
+                Some(Ok(AggregatedMessage::Ping(bytes))) => {
+                    if io.ws.pong(&bytes).await.is_err() {
+                        return close_reason(CloseCode::Away, "client_gone");
+                    }
+                    continue;
+                }
+                Some(Ok(AggregatedMessage::Pong(_))) => continue,
+                Some(Ok(AggregatedMessage::Binary(_))) => {
+                    let error = CastError::InvalidInput(
+                        "Session messages must be JSON text frames".to_string(),
+                    );
+                    let _ = io.send(&CastEvent::Error(error.problem())).await;
+                    continue;
+                }
+                Some(Ok(AggregatedMessage::Close(_))) | None => {
+                    return close_reason(CloseCode::Normal, "client_closed")
+                }
+                Some(Err(e)) => {
+                    log::warn!("Session {} protocol error: {e}", ctx.id);
+                    return close_reason(CloseCode::Protocol, "protocol_error");
+                }
+            };
+
+            let payload: Value = match serde_json::from_str(&text) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    let error = CastError::InvalidInput(format!("Invalid JSON: {e}"));
+                    let _ = io.send(&CastEvent::Error(error.problem())).await;
+                    continue;
+                }
+            };
+
+            // Checked before every message so an exhausted budget ends the session cleanly
+            if let Err(budget_err) = BudgetService::check_hard_limit(&ctx.user_id, &state.db).await
+            {
+                let error = CastError::BudgetExceeded(budget_err);
+                let _ = io.send(&CastEvent::Error(error.problem())).await;
+                return close_reason(CloseCode::Policy, "budget_exhausted");
+            }
+
+            // Each message is its own cast, with its own streaming budget
+            io.sink.next_cast();
+            let (returned, outcome) =
+                Self::handle_message(state, ctx, instance, payload, cancel, totals).await;
+
+            // Forward what the spell logged or emitted before its result
+            while let Ok(event) = io.events.try_recv() {
+                if io.send(&event).await.is_err() {
+                    return close_reason(CloseCode::Away, "client_gone");
+                }
+            }
+
+            let event = match &outcome {
+                Ok(event) => event,
+                Err(e) => &CastEvent::Error(e.problem()),
+            };
+            if io.send(event).await.is_err() {
+                return close_reason(CloseCode::Away, "client_gone");
+            }
+
+            match (returned, outcome) {
+                (Some(warm), Ok(_)) => instance = warm,
+                // The instance may be left mid-update by a trap; keep it only when the
+                // spell itself ran cleanly
+                (
+                    Some(warm),
+                    Err(CastError::InvalidInput(_) | CastError::OutputContractViolation(_)),
+                ) => instance = warm,
+                (_, Err(CastError::WasmTimeout)) => {
+                    return close_reason(CloseCode::Error, "message_timeout")
+                }
+                (_, Err(e)) => return close_reason(CloseCode::Error, e.error_code()),
+                (None, Ok(_)) => return close_reason(CloseCode::Error, "instance_lost"),
+            }
+        }
+    }
+
+    /// Record one message as a cast, run it on the warm instance and bill it
+    /// Returns the instance (unless it was lost to a timeout) and the event to send.
+    async fn handle_message(
+        state: &web::Data<AppState>,
+        ctx: &SessionContext,
+        instance: SpellSession,
+        payload: Value,
+        cancel: &CancelToken,
+        totals: &mut SessionTotals,
+    ) -> (Option<SpellSession>, Result<CastEvent, CastError>) {
+        let options = CastOptions {
+            session_id: Some(ctx.id),
+            ..Default::default()
+        };
+        let mut cast = match CastService::record(
+            &ctx.user_id,
+            &ctx.spell,
+            &ctx.version,
+            &payload,
+            options,
+            &state.db,
+        )
+        .await
+        {
+            Ok(cast) => cast,
+            Err(e) => return (Some(instance), Err(e)),
+        };
+        if ctx.spell.bills_per_session() && totals.cost_cents > 0 {
+            cast.cost_cents = 0;
+        }
+        if let Err(e) = CastService::mark_running(&cast, &state.db).await {
+            return (Some(instance), Err(e));
+        }
+
+        let mut instance = instance;
+        let task = tokio::task::spawn_blocking(move || {
+            let result = instance.call(payload);
+            (instance, result)
+        });
+        let (returned, result) = match tokio::time::timeout(MESSAGE_TIMEOUT, task).await {
+            Ok(Ok((instance, result))) => (Some(instance), result),
+            Ok(Err(e)) => (
+                None,
+                Err(CastError::InternalError(format!(
+                    "Session task failed: {e}"
+                ))),
+            ),
+            Err(_) => {
+                // The epoch callback traps the instance shortly; it is not reused
+                cancel.cancel();
+                (None, Err(CastError::WasmTimeout))
+            }
+        };
+
+        let result = match result {
+            Ok(output) => match &ctx.spell.output_schema {
+                Some(schema) => ContractService::validate_output(schema, &output).map(|_| output),
+                None => Ok(output),
+            },
+            Err(e) => Err(e),
+        };
+
+        // A probe admission covers only the message that answers it
+        let admission = match totals.messages {
+            0 => ctx.admission,
+            _ => Admission::Closed,
+        };
+        CircuitBreaker::record(state, &ctx.spell, &ctx.version, admission, &result).await;
+
+        totals.messages += 1;
+        let outcome = match result {
+            Ok(output) => CastService::complete(state, &cast, output, 1, false)
+                .await
+                .map(|execution| {
+                    totals.cost_cents += execution.cost_cents;
+                    CastEvent::Result {
+                        cast_id: cast.id,
+                        result: execution.output,
+                        attempts: execution.attempts,
+                        cached: false,
+                        cost_cents: execution.cost_cents,
+                    }
+                }),
+            Err(e) => {
+                if let Err(fail_err) =
+                    CastService::fail(&cast.id, cast.requeue_count, &e, &state.db).await
+                {
+                    log::error!(
+                        "Failed to record failed session cast {}: {fail_err}",
+                        cast.id
+                    );
+                }
+                Err(e)
+            }
+        };
+        (returned, outcome)
+    }
+
+    async fn close(
+        session_id: &Uuid,
+        totals: &SessionTotals,
+        reason: &str,
+        db: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE spell_sessions
+            SET status = 'CLOSED', messages = $2, cost_cents = $3, close_reason = $4, ended_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(session_id)
+        .bind(totals.messages)
+        .bind(totals.cost_cents)
+        .bind(reason)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
+
+fn close_reason(code: CloseCode, reason: &str) -> CloseReason {
+    CloseReason {
+        code,
+        description: Some(reason.to_string()),
+    }
+}
@@ -0,0 +1,80 @@
+use actix_web::HttpResponseBuilder;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::spell::SpellVersion;
+
+pub struct SpellService;
+
+impl SpellService {
+    /// Resolve the version a cast should run
+    /// Pinned casts may reach any version, including yanked ones. Unpinned casts get the
+    /// newest active version, falling back to the newest deprecated one that has not
+    /// reached its sunset date.
+    pub async fn resolve_version(
+        spell_id: &Uuid,
+        pinned: Option<&str>,
+        db: &PgPool,
+    ) -> Result<Option<SpellVersion>, sqlx::Error> {
+        match pinned {
+            Some(version) => {
+                sqlx::query_as(
+                    r#"
+                    SELECT * FROM spell_versions WHERE spell_id = $1 AND version = $2
+                    "#,
+                )
+                .bind(spell_id)
+                .bind(version)
+                .fetch_optional(db)
+                .await
+            }
+            None => {
+                sqlx::query_as(
+                    r#"
+                    SELECT * FROM spell_versions
+                    WHERE spell_id = $1
+                      AND status <> 'yanked'
+                      AND (sunset_at IS NULL OR sunset_at > NOW())
+                    ORDER BY (status = 'active') DESC, created_at DESC
+                    LIMIT 1
+                    "#,
+                )
+                .bind(spell_id)
+                .fetch_optional(db)
+                .await
+            }
+        }
+    }
+
+    /// Add `Deprecation` (RFC 9745) and `Sunset` (RFC 8594) headers for a deprecated version
+    pub fn add_deprecation_headers(builder: &mut HttpResponseBuilder, version: &SpellVersion) {
+        if !version.is_deprecated() {
+            return;
+        }
+
+        let deprecated_at = version.deprecated_at.unwrap_or(version.created_at);
+        builder.insert_header(("Deprecation", format!("@{}", deprecated_at.timestamp())));
+
+        if let Some(sunset) = version.sunset_at {
+            builder.insert_header(("Sunset", http_date(sunset)));
+        }
+    }
+}
+
+/// Format a timestamp as an IMF-fixdate HTTP-date
+fn http_date(at: DateTime<Utc>) -> String {
+    at.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn formats_http_date() {
+        let at = Utc.with_ymd_and_hms(2025, 11, 6, 8, 49, 37).unwrap();
+        assert_eq!(http_date(at), "Thu, 06 Nov 2025 08:49:37 GMT");
+    }
+}
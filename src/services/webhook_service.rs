@@ -0,0 +1,530 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use reqwest::Url;
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::models::webhook::{WebhookSecret, DELIVERY_DELIVERED, DELIVERY_FAILED};
+use crate::models::Cast;
+use crate::services::poller;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const MAX_URL_LENGTH: usize = 2048;
+// Attempts before a delivery is given up on; with the backoff below that spans about a day
+const MAX_ATTEMPTS: i32 = 8;
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const BATCH_SIZE: i64 = 20;
+// Claimed deliveries are hidden from other dispatchers for this long
+const CLAIM_LEASE_SECS: i64 = 60;
+
+#[derive(sqlx::FromRow)]
+struct DueDelivery {
+    id: Uuid,
+    user_id: Uuid,
+    url: String,
+    event: String,
+    payload: serde_json::Value,
+    attempts: i32,
+}
+
+/// Signed outbound webhooks for cast completion
+pub struct WebhookService;
+
+impl WebhookService {
+    /// Callback URLs must be absolute https URLs on a public host
+    /// Hostnames are checked again when delivering, once they are resolved.
+    pub fn validate_callback_url(url: &str) -> Result<(), String> {
+        if url.len() > MAX_URL_LENGTH {
+            return Err(format!(
+                "callback_url must be at most {MAX_URL_LENGTH} characters"
+            ));
+        }
+        let parsed = Url::parse(url).map_err(|e| format!("Invalid callback_url: {e}"))?;
+        if parsed.scheme() != "https" {
+            return Err("callback_url must use https".to_string());
+        }
+        let public = match host_ip(&parsed) {
+            Some(ip) => is_public_ip(ip),
+            None => {
+                let Some(domain) = parsed.host_str() else {
+                    return Err("callback_url must include a host".to_string());
+                };
+                let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+                domain != "localhost" && !domain.ends_with(".localhost")
+            }
+        };
+        if !public {
+            return Err("callback_url must point to a public host".to_string());
+        }
+        Ok(())
+    }
+
+    /// Resolve a webhook URL's host, refusing it unless every address is public
+    /// Returns the domain and the address to pin the connection to, so a name that
+    /// re-resolves to an internal address between check and connect (DNS rebinding) is
+    /// never followed. IP literals need no resolution and give None.
+    async fn resolve_public(url: &str) -> Result<Option<(String, SocketAddr)>, String> {
+        Self::validate_callback_url(url)?;
+        let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+        if host_ip(&parsed).is_some() {
+            return Ok(None);
+        }
+        let Some(domain) = parsed.host_str() else {
+            return Ok(None);
+        };
+        let port = parsed.port_or_known_default().unwrap_or(443);
+
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|e| format!("Failed to resolve {domain}: {e}"))?
+            .collect();
+        let addr = Self::pick_public(domain, &addrs)?;
+        Ok(Some((domain.to_string(), addr)))
+    }
+
+    /// The address to connect to, if none of a name's addresses is internal
+    fn pick_public(domain: &str, addrs: &[SocketAddr]) -> Result<SocketAddr, String> {
+        if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+            return Err(format!(
+                "{domain} resolves to non-public address {}",
+                addr.ip()
+            ));
+        }
+        addrs
+            .first()
+            .copied()
+            .ok_or_else(|| format!("{domain} has no addresses"))
+    }
+
+    /// The caller's signing secret, created on first use
+    pub async fn signing_secret(user_id: &Uuid, db: &PgPool) -> Result<WebhookSecret, sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_secrets (user_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(Self::generate_secret())
+        .execute(db)
+        .await?;
+
+        sqlx::query_as("SELECT secret, created_at FROM webhook_secrets WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(db)
+            .await
+    }
+
+    /// Replace the caller's signing secret; pending retries are signed with the new one
+    pub async fn rotate_secret(user_id: &Uuid, db: &PgPool) -> Result<WebhookSecret, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            INSERT INTO webhook_secrets (user_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret, created_at = NOW()
+            RETURNING secret, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(Self::generate_secret())
+        .fetch_one(db)
+        .await
+    }
+
+    fn generate_secret() -> String {
+        let raw: [u8; 32] = rand::thread_rng().gen();
+        format!("whsec_{}", URL_SAFE_NO_PAD.encode(raw))
+    }
+
+    /// `Spell-Signature` header value: HMAC-SHA256 over "{timestamp}.{body}"
+    /// Receivers recompute it with their secret and reject stale timestamps to stop replays.
+    pub fn signature_header(secret: &str, timestamp: i64, body: &str) -> String {
+        let mut mac =
+            HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(format!("{timestamp}.{body}").as_bytes());
+        format!(
+            "t={timestamp},v1={}",
+            hex::encode(mac.finalize().into_bytes())
+        )
+    }
+
+    /// Delay before the next attempt after `attempts` failures
+    fn backoff(attempts: i32) -> chrono::Duration {
+        let exponent = (attempts - 1).clamp(0, 20) as u32;
+        let secs = BASE_BACKOFF_SECS.saturating_mul(2_i64.saturating_pow(exponent));
+        chrono::Duration::seconds(secs.min(MAX_BACKOFF_SECS))
+    }
+
+    /// Queue a webhook with the final state of a cast, if it has a callback URL
+    pub async fn enqueue_for_cast(cast_id: &Uuid, db: &PgPool) -> Result<(), sqlx::Error> {
+        let cast: Option<Cast> = sqlx::query_as(
+            r#"
+            SELECT c.*, v.version AS spell_version
+            FROM casts c
+            LEFT JOIN spell_versions v ON v.id = c.spell_version_id
+            WHERE c.id = $1
+            "#,
+        )
+        .bind(cast_id)
+        .fetch_optional(db)
+        .await?;
+
+        let Some(cast) = cast else {
+            return Ok(());
+        };
+        let (Some(url), Some(user_id)) = (cast.callback_url.clone(), cast.user_id) else {
+            return Ok(());
+        };
+
+        let delivery_id = Uuid::new_v4();
+        let event = format!("cast.{}", cast.status.to_ascii_lowercase());
+        let payload = serde_json::json!({
+            "id": delivery_id,
+            "event": event,
+            "created_at": Utc::now(),
+            "data": { "cast": cast },
+        });
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (id, user_id, cast_id, url, event, payload)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(delivery_id)
+        .bind(user_id)
+        .bind(cast_id)
+        .bind(&url)
+        .bind(&event)
+        .bind(&payload)
+        .execute(db)
+        .await?;
+
+        log::info!("Queued webhook {delivery_id} ({event}) for cast {cast_id}");
+        Ok(())
+    }
+
+    /// Queue an alert to a creator about one of their spells, e.g. a circuit opening
+    pub async fn enqueue_alert(
+        user_id: &Uuid,
+        url: &str,
+        event: &str,
+        data: serde_json::Value,
+        db: &PgPool,
+    ) -> Result<(), sqlx::Error> {
+        let delivery_id = Uuid::new_v4();
+        let payload = serde_json::json!({
+            "id": delivery_id,
+            "event": event,
+            "created_at": Utc::now(),
+            "data": data,
+        });
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (id, user_id, url, event, payload)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(delivery_id)
+        .bind(user_id)
+        .bind(url)
+        .bind(event)
+        .bind(&payload)
+        .execute(db)
+        .await?;
+
+        log::info!("Queued webhook {delivery_id} ({event}) for user {user_id}");
+        Ok(())
+    }
+
+    /// Deliver due webhooks in the background for the lifetime of the process
+    pub fn spawn_dispatcher(db: PgPool) {
+        poller::spawn("Webhook dispatcher", POLL_INTERVAL, BATCH_SIZE, move || {
+            let db = db.clone();
+            async move { Self::dispatch_due(&db).await }
+        });
+    }
+
+    async fn dispatch_due(db: &PgPool) -> Result<usize, sqlx::Error> {
+        let due: Vec<DueDelivery> = sqlx::query_as(
+            r#"
+            UPDATE webhook_deliveries
+            SET next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM webhook_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, user_id, url, event, payload, attempts
+            "#,
+        )
+        .bind(BATCH_SIZE)
+        .bind(CLAIM_LEASE_SECS as f64)
+        .fetch_all(db)
+        .await?;
+
+        let count = due.len();
+        futures::future::join_all(due.iter().map(|d| Self::attempt(db, d))).await;
+        Ok(count)
+    }
+
+    async fn attempt(db: &PgPool, delivery: &DueDelivery) {
+        let started = Instant::now();
+        let outcome = Self::send(db, delivery).await;
+        let duration_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+        let (status_code, error) = match &outcome {
+            Ok(status) if status.is_success() => (Some(status.as_u16() as i32), None),
+            Ok(status) => (Some(status.as_u16() as i32), Some(format!("HTTP {status}"))),
+            Err(e) => (None, Some(e.clone())),
+        };
+
+        if let Err(e) = Self::record_attempt(db, delivery, status_code, error, duration_ms).await {
+            log::error!("Failed to record webhook attempt for {}: {e}", delivery.id);
+        }
+    }
+
+    async fn send(db: &PgPool, delivery: &DueDelivery) -> Result<reqwest::StatusCode, String> {
+        // One client per delivery, connecting only to the address that was checked
+        let mut builder = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .user_agent("Spell-Webhooks/1.0");
+        if let Some((domain, addr)) = Self::resolve_public(&delivery.url).await? {
+            builder = builder.resolve(&domain, addr);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {e}"))?;
+
+        let secret = Self::signing_secret(&delivery.user_id, db)
+            .await
+            .map_err(|e| format!("Signing secret unavailable: {e}"))?;
+        let body = delivery.payload.to_string();
+        let signature = Self::signature_header(&secret.secret, Utc::now().timestamp(), &body);
+
+        client
+            .post(&delivery.url)
+            .header("Content-Type", "application/json")
+            .header("Spell-Event", &delivery.event)
+            .header("Spell-Delivery", delivery.id.to_string())
+            .header("Spell-Signature", signature)
+            .body(body)
+            .send()
+            .await
+            .map(|response| response.status())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn record_attempt(
+        db: &PgPool,
+        delivery: &DueDelivery,
+        status_code: Option<i32>,
+        error: Option<String>,
+        duration_ms: i32,
+    ) -> Result<(), sqlx::Error> {
+        let attempts = delivery.attempts + 1;
+        let mut tx = db.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_delivery_attempts (delivery_id, status_code, error, duration_ms)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(delivery.id)
+        .bind(status_code)
+        .bind(&error)
+        .bind(duration_ms)
+        .execute(&mut *tx)
+        .await?;
+
+        match &error {
+            None => {
+                sqlx::query(
+                    r#"
+                    UPDATE webhook_deliveries
+                    SET status = $2, attempts = $3, last_status_code = $4, last_error = NULL,
+                        delivered_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(delivery.id)
+                .bind(DELIVERY_DELIVERED)
+                .bind(attempts)
+                .bind(status_code)
+                .execute(&mut *tx)
+                .await?;
+            }
+            Some(error) => {
+                let exhausted = attempts >= MAX_ATTEMPTS;
+                if exhausted {
+                    log::warn!(
+                        "Webhook {} failed after {attempts} attempts: {error}",
+                        delivery.id
+                    );
+                }
+
+                sqlx::query(
+                    r#"
+                    UPDATE webhook_deliveries
+                    SET status = CASE WHEN $5 THEN $6 ELSE status END,
+                        attempts = $2, last_status_code = $3, last_error = $4,
+                        next_attempt_at = $7
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(delivery.id)
+                .bind(attempts)
+                .bind(status_code)
+                .bind(error)
+                .bind(exhausted)
+                .bind(DELIVERY_FAILED)
+                .bind(Utc::now() + Self::backoff(attempts))
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await
+    }
+}
+
+/// The host of `url` when it is an IP literal
+fn host_ip(url: &Url) -> Option<IpAddr> {
+    let host = url.host_str()?;
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether an address is reachable on the public internet
+/// Loopback, private, link-local, unique-local, shared (CGNAT), unspecified, broadcast,
+/// multicast and documentation ranges are not, including IPv4 addresses mapped into IPv6.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ip(mapped.into()),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_hmac_of_timestamp_and_body() {
+        let header = WebhookService::signature_header("whsec_test", 1_700_000_000, r#"{"a":1}"#);
+
+        let mut mac = HmacSha256::new_from_slice(b"whsec_test").unwrap();
+        mac.update(br#"1700000000.{"a":1}"#);
+        let expected = hex::encode(mac.finalize().into_bytes());
+
+        assert_eq!(header, format!("t=1700000000,v1={expected}"));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap() {
+        assert_eq!(WebhookService::backoff(1).num_seconds(), 30);
+        assert_eq!(WebhookService::backoff(2).num_seconds(), 60);
+        assert_eq!(WebhookService::backoff(4).num_seconds(), 240);
+        assert_eq!(WebhookService::backoff(30).num_seconds(), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn callback_urls_must_be_https() {
+        assert!(WebhookService::validate_callback_url("https://example.com/hooks").is_ok());
+        assert!(WebhookService::validate_callback_url("http://example.com/hooks").is_err());
+        assert!(WebhookService::validate_callback_url("not a url").is_err());
+    }
+
+    #[test]
+    fn callback_urls_must_not_reach_internal_hosts() {
+        let blocked = [
+            "https://localhost/hooks",
+            "https://api.localhost/hooks",
+            "https://127.0.0.1/hooks",
+            "https://169.254.169.254/latest/meta-data",
+            "https://10.0.0.5/hooks",
+            "https://172.16.0.1/hooks",
+            "https://192.168.1.1/hooks",
+            "https://100.64.0.1/hooks",
+            "https://0.0.0.0/hooks",
+            "https://[::1]/hooks",
+            "https://[::]/hooks",
+            "https://[fe80::1]/hooks",
+            "https://[fd00::1]/hooks",
+            "https://[::ffff:10.0.0.5]/hooks",
+        ];
+        for url in blocked {
+            assert!(
+                WebhookService::validate_callback_url(url).is_err(),
+                "{url} should be refused"
+            );
+        }
+        assert!(WebhookService::validate_callback_url("https://93.184.216.34/hooks").is_ok());
+        assert!(WebhookService::validate_callback_url("https://[2606:4700::1]/hooks").is_ok());
+    }
+
+    #[tokio::test]
+    async fn delivery_refuses_names_resolving_to_internal_addresses() {
+        // A name that passed validation but was rebound to an internal address
+        let public: SocketAddr = "93.184.216.34:443".parse().unwrap();
+        for internal in ["127.0.0.1:443", "169.254.169.254:443", "[fd00::1]:443"] {
+            let addrs = [public, internal.parse().unwrap()];
+            assert!(WebhookService::pick_public("hooks.example.com", &addrs).is_err());
+        }
+        assert_eq!(
+            WebhookService::pick_public("hooks.example.com", &[public]),
+            Ok(public)
+        );
+        assert!(WebhookService::pick_public("hooks.example.com", &[]).is_err());
+
+        assert!(
+            WebhookService::resolve_public("https://localhost.:8443/hooks")
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            WebhookService::resolve_public("https://93.184.216.34/hooks")
+                .await
+                .unwrap(),
+            None
+        );
+    }
+}
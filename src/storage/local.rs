@@ -0,0 +1,97 @@
+use futures::future::BoxFuture;
+use std::path::PathBuf;
+
+use super::{blob_key, BlobStore, StorageError};
+
+/// Blob store backed by a local directory (single instance and development setups)
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path_for(&self, digest: &str) -> Result<PathBuf, StorageError> {
+        Ok(self.root.join(blob_key(digest)?))
+    }
+}
+
+impl BlobStore for LocalBlobStore {
+    fn put<'a>(
+        &'a self,
+        digest: &'a str,
+        bytes: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            let path = self.path_for(digest)?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| StorageError::Backend(e.to_string()))?;
+            }
+
+            // Write to a temporary file first so readers never see a partial blob
+            let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+            tokio::fs::write(&tmp, bytes)
+                .await
+                .map_err(|e| StorageError::Backend(e.to_string()))?;
+            tokio::fs::rename(&tmp, &path)
+                .await
+                .map_err(|e| StorageError::Backend(e.to_string()))
+        })
+    }
+
+    fn get<'a>(&'a self, digest: &'a str) -> BoxFuture<'a, Result<Vec<u8>, StorageError>> {
+        Box::pin(async move {
+            let path = self.path_for(digest)?;
+            tokio::fs::read(&path).await.map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => StorageError::NotFound(digest.to_string()),
+                _ => StorageError::Backend(e.to_string()),
+            })
+        })
+    }
+
+    fn exists<'a>(&'a self, digest: &'a str) -> BoxFuture<'a, Result<bool, StorageError>> {
+        Box::pin(async move {
+            let path = self.path_for(digest)?;
+            tokio::fs::try_exists(&path)
+                .await
+                .map_err(|e| StorageError::Backend(e.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{get_verified_blob, put_blob};
+
+    #[tokio::test]
+    async fn round_trips_blobs_by_digest() {
+        let root = std::env::temp_dir().join(format!("spell-blobs-{}", uuid::Uuid::new_v4()));
+        let store = LocalBlobStore::new(&root);
+
+        let digest = put_blob(&store, b"\0asm module bytes").await.unwrap();
+        assert!(store.exists(&digest).await.unwrap());
+        assert_eq!(
+            get_verified_blob(&store, &digest).await.unwrap(),
+            b"\0asm module bytes"
+        );
+
+        // Storing identical content again is a no-op returning the same digest
+        assert_eq!(
+            put_blob(&store, b"\0asm module bytes").await.unwrap(),
+            digest
+        );
+
+        let missing = crate::storage::digest_of(b"missing");
+        assert!(matches!(
+            store.get(&missing).await,
+            Err(StorageError::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(root).ok();
+    }
+}
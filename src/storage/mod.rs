@@ -0,0 +1,128 @@
+pub mod local;
+pub mod s3;
+
+use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+
+pub use local::LocalBlobStore;
+pub use s3::S3BlobStore;
+
+#[derive(Debug)]
+pub enum StorageError {
+    NotFound(String),
+    DigestMismatch { expected: String, actual: String },
+    InvalidDigest(String),
+    Backend(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::NotFound(digest) => write!(f, "Blob not found: {digest}"),
+            StorageError::DigestMismatch { expected, actual } => {
+                write!(f, "Blob digest mismatch: expected {expected}, got {actual}")
+            }
+            StorageError::InvalidDigest(digest) => write!(f, "Invalid blob digest: {digest}"),
+            StorageError::Backend(msg) => write!(f, "Storage backend error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+/// Content-addressed storage for spell packages and artifacts
+/// Blobs are identified by their digest ("sha256:<hex>"), so writes are idempotent
+pub trait BlobStore: Send + Sync {
+    /// Store bytes under the given (already computed and verified) digest
+    fn put<'a>(
+        &'a self,
+        digest: &'a str,
+        bytes: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), StorageError>>;
+
+    /// Fetch the bytes stored under a digest
+    fn get<'a>(&'a self, digest: &'a str) -> BoxFuture<'a, Result<Vec<u8>, StorageError>>;
+
+    fn exists<'a>(&'a self, digest: &'a str) -> BoxFuture<'a, Result<bool, StorageError>>;
+}
+
+/// Compute the content digest of a blob
+pub fn digest_of(bytes: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(bytes)))
+}
+
+/// Map a digest to a backend key ("sha256/<hex>"), rejecting anything malformed
+pub fn blob_key(digest: &str) -> Result<String, StorageError> {
+    let hex_part = digest
+        .strip_prefix("sha256:")
+        .filter(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| StorageError::InvalidDigest(digest.to_string()))?;
+
+    Ok(format!("sha256/{}", hex_part.to_ascii_lowercase()))
+}
+
+/// Store a blob and return its digest
+pub async fn put_blob(store: &dyn BlobStore, bytes: &[u8]) -> Result<String, StorageError> {
+    let digest = digest_of(bytes);
+    if !store.exists(&digest).await? {
+        store.put(&digest, bytes).await?;
+    }
+    Ok(digest)
+}
+
+/// Fetch a blob and verify it still matches its digest
+pub async fn get_verified_blob(
+    store: &dyn BlobStore,
+    digest: &str,
+) -> Result<Vec<u8>, StorageError> {
+    let bytes = store.get(digest).await?;
+    let actual = digest_of(&bytes);
+    if !actual.eq_ignore_ascii_case(digest) {
+        return Err(StorageError::DigestMismatch {
+            expected: digest.to_string(),
+            actual,
+        });
+    }
+    Ok(bytes)
+}
+
+/// Build the blob store selected by STORAGE_BACKEND (local | s3)
+pub fn from_env() -> Result<Arc<dyn BlobStore>, anyhow::Error> {
+    let backend = env::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
+
+    match backend.as_str() {
+        "local" => {
+            let root = env::var("BLOB_STORAGE_PATH").unwrap_or_else(|_| "./blobs".to_string());
+            Ok(Arc::new(LocalBlobStore::new(root)))
+        }
+        "s3" => Ok(Arc::new(S3BlobStore::from_env()?)),
+        other => Err(anyhow::anyhow!("Unknown STORAGE_BACKEND: {other}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_are_content_addressed() {
+        let digest = digest_of(b"hello");
+        assert_eq!(
+            digest,
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(
+            blob_key(&digest).unwrap(),
+            "sha256/2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn rejects_path_traversal_digests() {
+        assert!(blob_key("sha256:../../etc/passwd").is_err());
+        assert!(blob_key("md5:abc").is_err());
+    }
+}
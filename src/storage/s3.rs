@@ -0,0 +1,354 @@
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use reqwest::{Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::env;
+
+use super::{blob_key, BlobStore, StorageError};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Blob store for any S3-compatible object store (AWS S3, R2, MinIO)
+/// Uses path-style addressing ({endpoint}/{bucket}/{key}) signed with AWS Signature V4
+pub struct S3BlobStore {
+    client: reqwest::Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl S3BlobStore {
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let endpoint = env::var("S3_ENDPOINT")
+            .unwrap_or_else(|_| "https://s3.amazonaws.com".to_string())
+            .parse::<Url>()
+            .map_err(|e| anyhow::anyhow!("Invalid S3_ENDPOINT: {e}"))?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            endpoint,
+            bucket: env::var("S3_BUCKET").map_err(|_| anyhow::anyhow!("S3_BUCKET must be set"))?,
+            region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            access_key_id: env::var("S3_ACCESS_KEY_ID")
+                .map_err(|_| anyhow::anyhow!("S3_ACCESS_KEY_ID must be set"))?,
+            secret_access_key: env::var("S3_SECRET_ACCESS_KEY")
+                .map_err(|_| anyhow::anyhow!("S3_SECRET_ACCESS_KEY must be set"))?,
+        })
+    }
+
+    fn object_path(&self, digest: &str) -> Result<String, StorageError> {
+        let base = self.endpoint.path().trim_end_matches('/');
+        Ok(format!("{base}/{}/{}", self.bucket, blob_key(digest)?))
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        digest: &str,
+        body: Option<&[u8]>,
+    ) -> Result<reqwest::Response, StorageError> {
+        let path = self.object_path(digest)?;
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let payload = body.unwrap_or_default();
+        let payload_hash = hex::encode(Sha256::digest(payload));
+        let now = Utc::now();
+
+        let authorization = sign_v4(
+            &SigningParams {
+                method: method.as_str(),
+                path: &path,
+                host: &host,
+                payload_hash: &payload_hash,
+                region: &self.region,
+                access_key_id: &self.access_key_id,
+                secret_access_key: &self.secret_access_key,
+            },
+            now,
+        );
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("Authorization", authorization)
+            .header("x-amz-content-sha256", &payload_hash)
+            .header("x-amz-date", amz_date(now));
+
+        if let Some(bytes) = body {
+            request = request.body(bytes.to_vec());
+        }
+
+        request
+            .send()
+            .await
+            .map_err(|e| StorageError::Backend(e.to_string()))
+    }
+}
+
+impl BlobStore for S3BlobStore {
+    fn put<'a>(
+        &'a self,
+        digest: &'a str,
+        bytes: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), StorageError>> {
+        Box::pin(async move {
+            let response = self.send(Method::PUT, digest, Some(bytes)).await?;
+            if !response.status().is_success() {
+                return Err(StorageError::Backend(format!(
+                    "PUT {digest} returned {}",
+                    response.status()
+                )));
+            }
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, digest: &'a str) -> BoxFuture<'a, Result<Vec<u8>, StorageError>> {
+        Box::pin(async move {
+            let response = self.send(Method::GET, digest, None).await?;
+            match response.status() {
+                StatusCode::NOT_FOUND => Err(StorageError::NotFound(digest.to_string())),
+                status if status.is_success() => response
+                    .bytes()
+                    .await
+                    .map(|b| b.to_vec())
+                    .map_err(|e| StorageError::Backend(e.to_string())),
+                status => Err(StorageError::Backend(format!(
+                    "GET {digest} returned {status}"
+                ))),
+            }
+        })
+    }
+
+    fn exists<'a>(&'a self, digest: &'a str) -> BoxFuture<'a, Result<bool, StorageError>> {
+        Box::pin(async move {
+            let response = self.send(Method::HEAD, digest, None).await?;
+            match response.status() {
+                StatusCode::NOT_FOUND => Ok(false),
+                status if status.is_success() => Ok(true),
+                status => Err(StorageError::Backend(format!(
+                    "HEAD {digest} returned {status}"
+                ))),
+            }
+        })
+    }
+}
+
+struct SigningParams<'a> {
+    method: &'a str,
+    path: &'a str,
+    host: &'a str,
+    payload_hash: &'a str,
+    region: &'a str,
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+}
+
+fn amz_date(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let k_date = hmac_sha256(format!("AWS4{secret_access_key}").as_bytes(), date);
+    let k_region = hmac_sha256(&k_date, region);
+    let k_service = hmac_sha256(&k_region, service);
+    hmac_sha256(&k_service, "aws4_request")
+}
+
+/// Build the SigV4 Authorization header for a request without query parameters
+fn sign_v4(params: &SigningParams<'_>, now: DateTime<Utc>) -> String {
+    let amz_date = amz_date(now);
+    let date = now.format("%Y%m%d").to_string();
+    let scope = format!("{date}/{}/s3/aws4_request", params.region);
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+
+    let canonical_request = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{}",
+        params.method, params.path, params.host, params.payload_hash, params.payload_hash
+    );
+
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = signing_key(params.secret_access_key, &date, params.region, "s3");
+    let signature = hex::encode(hmac_sha256(&key, &string_to_sign));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        params.access_key_id
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{digest_of, get_verified_blob, put_blob};
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use chrono::TimeZone;
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Minimal S3 stand-in: path-style PUT/GET/HEAD of objects held in memory
+    /// Rejects unsigned requests and bodies that don't match their declared hash.
+    async fn stand_in(
+        req: HttpRequest,
+        body: web::Bytes,
+        objects: web::Data<Objects>,
+    ) -> HttpResponse {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        };
+        if !header("authorization").starts_with("AWS4-HMAC-SHA256 Credential=minioadmin/") {
+            return HttpResponse::Forbidden().finish();
+        }
+        if header("x-amz-content-sha256") != hex::encode(Sha256::digest(&body)) {
+            return HttpResponse::BadRequest().finish();
+        }
+
+        let key = req.path().to_string();
+        let mut objects = objects.lock();
+        match req.method().as_str() {
+            "PUT" => {
+                objects.insert(key, body.to_vec());
+                HttpResponse::Ok().finish()
+            }
+            "GET" | "HEAD" => match objects.get(&key) {
+                Some(bytes) => HttpResponse::Ok().body(bytes.clone()),
+                None => HttpResponse::NotFound().finish(),
+            },
+            _ => HttpResponse::MethodNotAllowed().finish(),
+        }
+    }
+
+    fn start_stand_in() -> (S3BlobStore, Objects) {
+        let objects: Objects = Arc::default();
+        let data = web::Data::new(objects.clone());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .default_service(web::to(stand_in))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+
+        let store = S3BlobStore {
+            client: reqwest::Client::new(),
+            endpoint: format!("http://127.0.0.1:{port}").parse().unwrap(),
+            bucket: "spells".to_string(),
+            region: "us-east-1".to_string(),
+            access_key_id: "minioadmin".to_string(),
+            secret_access_key: "minioadmin".to_string(),
+        };
+        (store, objects)
+    }
+
+    #[actix_web::test]
+    async fn stores_and_verifies_blobs_against_a_stand_in() {
+        let (store, objects) = start_stand_in();
+
+        let digest = put_blob(&store, b"module bytes").await.unwrap();
+        assert!(store.exists(&digest).await.unwrap());
+        assert_eq!(
+            get_verified_blob(&store, &digest).await.unwrap(),
+            b"module bytes"
+        );
+        let key = format!("/spells/{}", blob_key(&digest).unwrap());
+        assert!(objects.lock().contains_key(&key));
+
+        // A second put of the same content is skipped
+        objects.lock().clear();
+        objects.lock().insert(key.clone(), b"module bytes".to_vec());
+        assert_eq!(put_blob(&store, b"module bytes").await.unwrap(), digest);
+
+        objects.lock().insert(key, b"tampered".to_vec());
+        assert!(matches!(
+            get_verified_blob(&store, &digest).await,
+            Err(StorageError::DigestMismatch { .. })
+        ));
+    }
+
+    #[actix_web::test]
+    async fn missing_blobs_and_refused_requests_are_reported() {
+        let (mut store, _objects) = start_stand_in();
+        let digest = digest_of(b"never stored");
+
+        assert!(!store.exists(&digest).await.unwrap());
+        assert!(matches!(
+            store.get(&digest).await,
+            Err(StorageError::NotFound(_))
+        ));
+
+        store.access_key_id = "someone-else".to_string();
+        assert!(matches!(
+            store.put(&digest, b"never stored").await,
+            Err(StorageError::Backend(_))
+        ));
+    }
+
+    #[test]
+    fn derives_aws_documented_signing_key() {
+        // Example from the AWS SigV4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex::encode(key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn authorization_header_carries_scope_and_signed_headers() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap();
+        let header = sign_v4(
+            &SigningParams {
+                method: "GET",
+                path: "/spells/sha256/abc",
+                host: "localhost:9000",
+                payload_hash: &hex::encode(Sha256::digest(b"")),
+                region: "us-east-1",
+                access_key_id: "minioadmin",
+                secret_access_key: "minioadmin",
+            },
+            now,
+        );
+
+        assert!(header.starts_with(
+            "AWS4-HMAC-SHA256 Credential=minioadmin/20250102/us-east-1/s3/aws4_request, "
+        ));
+        assert!(header.contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date"));
+        assert_eq!(header.rsplit("Signature=").next().unwrap().len(), 64);
+    }
+}
@@ -0,0 +1,355 @@
+use actix_web::dev::Payload;
+use actix_web::http::header::{ACCEPT, CONTENT_TYPE, VARY};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError};
+use futures::future::LocalBoxFuture;
+use serde::de::{self, DeserializeOwned, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::fmt;
+
+use crate::errors::ApiError;
+
+/// Wire formats for cast payloads and results
+/// Everything is held as JSON values internally; MessagePack and CBOR input that JSON
+/// can't represent exactly (binary strings, non-string map keys, NaN or infinite floats)
+/// is rejected rather than silently altered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Codec {
+    #[default]
+    Json,
+    MsgPack,
+    Cbor,
+}
+
+impl Codec {
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::Json => "json",
+            Codec::MsgPack => "msgpack",
+            Codec::Cbor => "cbor",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Codec::Json => "application/json",
+            Codec::MsgPack => "application/msgpack",
+            Codec::Cbor => "application/cbor",
+        }
+    }
+
+    /// Format named in a spell manifest's `input_format` or `output_format`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Codec::Json),
+            "msgpack" | "messagepack" => Some(Codec::MsgPack),
+            "cbor" => Some(Codec::Cbor),
+            _ => None,
+        }
+    }
+
+    /// Format of a media type such as `application/msgpack; charset=binary`
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match essence.as_str() {
+            "application/json" => Some(Codec::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Codec::MsgPack)
+            }
+            "application/cbor" => Some(Codec::Cbor),
+            other if other.ends_with("+json") => Some(Codec::Json),
+            _ => None,
+        }
+    }
+
+    /// Format of the request body, if its Content-Type names one
+    pub fn of_body(req: &HttpRequest) -> Option<Self> {
+        req.headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Self::from_media_type)
+    }
+
+    /// Response format preferred by the `Accept` header; JSON unless another is preferred
+    pub fn negotiate(req: &HttpRequest) -> Self {
+        let Some(accept) = req.headers().get(ACCEPT).and_then(|v| v.to_str().ok()) else {
+            return Codec::Json;
+        };
+
+        let mut best: Option<(Codec, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let media_type = params.next().unwrap_or_default().trim();
+            let codec = match media_type {
+                "*/*" | "application/*" => Codec::Json,
+                other => match Self::from_media_type(other) {
+                    Some(codec) => codec,
+                    None => continue,
+                },
+            };
+            let q = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            // Earlier ranges win ties
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((codec, q));
+            }
+        }
+        best.map_or(Codec::Json, |(codec, _)| codec)
+    }
+
+    pub fn decode_value(self, bytes: &[u8]) -> Result<Value, String> {
+        match self {
+            Codec::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Codec::MsgPack => rmp_serde::from_slice::<ExactValue>(bytes)
+                .map(|v| v.0)
+                .map_err(|e| e.to_string()),
+            Codec::Cbor => ciborium::from_reader::<ExactValue, _>(bytes)
+                .map(|v| v.0)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, String> {
+        serde_json::from_value(self.decode_value(bytes)?).map_err(|e| e.to_string())
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Codec::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            // Named fields, so structs arrive as maps rather than positional arrays
+            Codec::MsgPack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Codec::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes).map_err(|e| e.to_string())?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Finish a response with `body` in this format
+    pub fn respond<T: Serialize>(
+        self,
+        builder: &mut HttpResponseBuilder,
+        body: &T,
+    ) -> HttpResponse {
+        builder.insert_header((VARY, "Accept"));
+        if self == Codec::Json {
+            return builder.json(body);
+        }
+        match self.encode(body) {
+            Ok(bytes) => builder.content_type(self.content_type()).body(bytes),
+            Err(e) => {
+                log::error!("Failed to encode {} response: {e}", self.name());
+                ApiError::Internal("Failed to encode response".to_string()).error_response()
+            }
+        }
+    }
+}
+
+/// Request body in JSON, MessagePack or CBOR, chosen by its Content-Type
+/// JSON bodies go through `web::Json`, so its configured limit and errors apply unchanged;
+/// binary formats are limited by the route's `PayloadConfig`.
+pub struct Encoded<T>(pub T);
+
+impl<T> Encoded<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> std::ops::Deref for Encoded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Encoded<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        match Codec::of_body(req) {
+            Some(codec) if codec != Codec::Json => {
+                let bytes = web::Bytes::from_request(req, payload);
+                Box::pin(async move {
+                    let bytes = bytes.await?;
+                    codec.decode(&bytes).map(Encoded).map_err(|e| {
+                        ApiError::InvalidRequest(format!("Invalid {} body: {e}", codec.name()))
+                            .into()
+                    })
+                })
+            }
+            _ => {
+                let json = web::Json::<T>::from_request(req, payload);
+                Box::pin(async move { json.await.map(|json| Encoded(json.into_inner())) })
+            }
+        }
+    }
+}
+
+/// A JSON value decoded without loss, or not at all
+struct ExactValue(Value);
+
+impl<'de> Deserialize<'de> for ExactValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ExactVisitor).map(ExactValue)
+    }
+}
+
+struct ExactVisitor;
+
+impl<'de> Visitor<'de> for ExactVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a value representable as JSON")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Value, E> {
+        i64::try_from(v)
+            .map(|v| Value::Number(v.into()))
+            .or_else(|_| u64::try_from(v).map(|v| Value::Number(v.into())))
+            .map_err(|_| E::custom(format!("integer {v} is out of the 64-bit range")))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Value, E> {
+        u64::try_from(v)
+            .map(|v| Value::Number(v.into()))
+            .map_err(|_| E::custom(format!("integer {v} is out of the 64-bit range")))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Number::from_f64(v)
+            .map(Value::Number)
+            .ok_or_else(|| E::custom(format!("{v} can't be represented in JSON")))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, _: &[u8]) -> Result<Value, E> {
+        Err(E::custom(
+            "binary values can't be represented in JSON; send them as input files",
+        ))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(ExactValue(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Map::new();
+        while let Some(ExactValue(key)) = map.next_key()? {
+            let Value::String(key) = key else {
+                return Err(de::Error::custom(format!("map key {key} is not a string")));
+            };
+            let ExactValue(value) = map.next_value()?;
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn binary_formats_round_trip_exactly() {
+        let value = serde_json::json!({
+            "name": "résumé",
+            "big": u64::MAX,
+            "neg": i64::MIN,
+            "ratio": 0.1,
+            "items": [null, true, [1, 2], {"nested": "yes"}],
+        });
+        for codec in [Codec::Json, Codec::MsgPack, Codec::Cbor] {
+            let bytes = codec.encode(&value).unwrap();
+            assert_eq!(
+                codec.decode_value(&bytes).unwrap(),
+                value,
+                "{}",
+                codec.name()
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_values_json_cannot_hold() {
+        // bin 8 with three bytes
+        assert!(Codec::MsgPack.decode_value(&[0xc4, 3, 1, 2, 3]).is_err());
+
+        let nan = Codec::Cbor.encode(&f64::NAN).unwrap();
+        assert!(Codec::Cbor.decode_value(&nan).is_err());
+
+        let int_keys = Codec::MsgPack
+            .encode(&std::collections::BTreeMap::from([(1, "one")]))
+            .unwrap();
+        assert!(Codec::MsgPack.decode_value(&int_keys).is_err());
+    }
+
+    #[test]
+    fn accept_header_picks_preferred_format() {
+        let negotiate = |accept: Option<&str>| {
+            let mut req = TestRequest::default();
+            if let Some(accept) = accept {
+                req = req.insert_header((ACCEPT, accept));
+            }
+            Codec::negotiate(&req.to_http_request())
+        };
+        assert_eq!(negotiate(None), Codec::Json);
+        assert_eq!(negotiate(Some("application/msgpack")), Codec::MsgPack);
+        assert_eq!(
+            negotiate(Some("application/json;q=0.5, application/cbor")),
+            Codec::Cbor
+        );
+        assert_eq!(
+            negotiate(Some("application/cbor;q=0, */*;q=0.1")),
+            Codec::Json
+        );
+        assert_eq!(negotiate(Some("text/html")), Codec::Json);
+    }
+}
@@ -0,0 +1,46 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Encode a keyset position as an opaque, URL-safe pagination cursor
+pub fn encode_cursor<T: Serialize>(position: &T) -> String {
+    // Serializing plain structs to JSON cannot fail
+    let json = serde_json::to_vec(position).unwrap_or_default();
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+/// Decode a cursor produced by `encode_cursor`
+/// Returns None for anything malformed or tampered with
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Option<T> {
+    let json = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        name: String,
+        id: u32,
+    }
+
+    #[test]
+    fn round_trips_position() {
+        let position = Position {
+            name: "resize".to_string(),
+            id: 7,
+        };
+
+        let cursor = encode_cursor(&position);
+        assert!(!cursor.contains('='));
+        assert_eq!(decode_cursor::<Position>(&cursor), Some(position));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(decode_cursor::<Position>("not a cursor!"), None);
+        assert_eq!(decode_cursor::<Position>(&encode_cursor(&42)), None);
+    }
+}
@@ -0,0 +1,129 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// One difference between two JSON values, located by JSON pointer
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffEntry {
+    pub path: String,
+    /// `added`, `removed` or `changed`
+    pub op: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+/// Structural differences from `before` to `after`
+/// Objects are compared key by key and arrays index by index; any other mismatch,
+/// including a change of type, is reported whole at its path.
+pub fn diff(before: &Value, after: &Value) -> Vec<DiffEntry> {
+    let mut entries = Vec::new();
+    diff_at(&mut String::new(), before, after, &mut entries);
+    entries
+}
+
+fn diff_at(path: &mut String, before: &Value, after: &Value, entries: &mut Vec<DiffEntry>) {
+    match (before, after) {
+        (Value::Object(b), Value::Object(a)) => {
+            for (key, b_value) in b {
+                with_segment(path, key, |path| match a.get(key) {
+                    Some(a_value) => diff_at(path, b_value, a_value, entries),
+                    None => entries.push(removed(path, b_value)),
+                });
+            }
+            for (key, a_value) in a.iter().filter(|(key, _)| !b.contains_key(*key)) {
+                with_segment(path, key, |path| entries.push(added(path, a_value)));
+            }
+        }
+        (Value::Array(b), Value::Array(a)) => {
+            for index in 0..b.len().max(a.len()) {
+                with_segment(path, &index.to_string(), |path| {
+                    match (b.get(index), a.get(index)) {
+                        (Some(b_value), Some(a_value)) => diff_at(path, b_value, a_value, entries),
+                        (Some(b_value), None) => entries.push(removed(path, b_value)),
+                        (None, Some(a_value)) => entries.push(added(path, a_value)),
+                        (None, None) => {}
+                    }
+                });
+            }
+        }
+        _ if before != after => entries.push(DiffEntry {
+            path: path.clone(),
+            op: "changed",
+            before: Some(before.clone()),
+            after: Some(after.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Run `f` with `segment` appended to `path` (escaped per RFC 6901), then restore it
+fn with_segment(path: &mut String, segment: &str, f: impl FnOnce(&mut String)) {
+    let len = path.len();
+    path.push('/');
+    path.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+    f(path);
+    path.truncate(len);
+}
+
+fn added(path: &str, value: &Value) -> DiffEntry {
+    DiffEntry {
+        path: path.to_string(),
+        op: "added",
+        before: None,
+        after: Some(value.clone()),
+    }
+}
+
+fn removed(path: &str, value: &Value) -> DiffEntry {
+    DiffEntry {
+        path: path.to_string(),
+        op: "removed",
+        before: Some(value.clone()),
+        after: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reports_each_difference_by_pointer() {
+        let before = json!({"total": 10, "items": [1, 2, 3], "a/b": true, "gone": null});
+        let after = json!({"total": 12, "items": [1, 2], "a/b": true, "new": {"x": 1}});
+
+        let entries = diff(&before, &after);
+        let mut summary: Vec<(&str, &str)> =
+            entries.iter().map(|e| (e.path.as_str(), e.op)).collect();
+        summary.sort();
+        assert_eq!(
+            summary,
+            [
+                ("/gone", "removed"),
+                ("/items/2", "removed"),
+                ("/new", "added"),
+                ("/total", "changed"),
+            ]
+        );
+        let total = entries.iter().find(|e| e.path == "/total").unwrap();
+        assert_eq!(
+            (&total.before, &total.after),
+            (&Some(json!(10)), &Some(json!(12)))
+        );
+    }
+
+    #[test]
+    fn equal_values_and_whole_value_changes() {
+        let value = json!({"nested": {"list": [{"k": "v"}]}});
+        assert!(diff(&value, &value).is_empty());
+
+        let entries = diff(&json!([1]), &json!("one"));
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].path.as_str(), entries[0].op), ("", "changed"));
+
+        let escaped = diff(&json!({"a~b/c": 1}), &json!({"a~b/c": 2}));
+        assert_eq!(escaped[0].path, "/a~0b~1c");
+    }
+}
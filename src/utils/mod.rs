@@ -1 +1,5 @@
 pub mod apikey;
+pub mod canonical;
+pub mod codec;
+pub mod cursor;
+pub mod json_diff;
@@ -1,41 +1,453 @@
 use crate::errors::CastError;
+use crate::models::spell::SpellVersion;
+use crate::services::cast_events::{CastEvent, EventSink};
+use crate::storage::{get_verified_blob, BlobStore, StorageError};
+use crate::utils::codec::Codec;
+use parking_lot::Mutex;
 use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use wasmtime::*;
 
+// How often running instances check whether their cast was canceled
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+// Longest log line or output chunk a spell may hand to the host in one call
+const MAX_HOST_MESSAGE_BYTES: usize = 64 * 1024;
+// Largest encoded result a spell may hand back with `set_output`
+const MAX_OUTPUT_BYTES: usize = 10 * 1024 * 1024;
+// Import module under which host functions are offered to spells
+const HOST_MODULE: &str = "spell";
+
+/// Shared flag that interrupts a running spell at its next epoch tick
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Compiled modules kept in memory, evicted oldest-first
+struct ModuleCache {
+    capacity: usize,
+    modules: HashMap<String, Module>,
+    order: VecDeque<String>,
+}
+
+impl ModuleCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            modules: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<Module> {
+        self.modules.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: String, module: Module) {
+        if self.modules.contains_key(&key) {
+            return;
+        }
+        while self.modules.len() >= self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    self.modules.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+        self.order.push_back(key.clone());
+        self.modules.insert(key, module);
+    }
+}
+
+/// A file sent with the cast, readable by the spell through host functions
+pub struct InputFileData {
+    pub name: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Everything a spell receives for one cast
+#[derive(Default)]
+pub struct SpellInput {
+    pub payload: Value,
+    pub files: Vec<InputFileData>,
+    /// Encoding of the payload handed to `read_input`
+    pub input_format: Codec,
+    /// Encoding the spell passes its result to `set_output` in
+    pub output_format: Codec,
+}
+
+impl From<Value> for SpellInput {
+    fn from(payload: Value) -> Self {
+        Self {
+            payload,
+            ..Default::default()
+        }
+    }
+}
+
+/// Per-instance state reachable from host functions
+#[derive(Default)]
+struct HostState {
+    events: EventSink,
+    files: Arc<Vec<InputFileData>>,
+    /// The payload, encoded in the spell's input format
+    input: Arc<Vec<u8>>,
+    /// Encoded result passed to `set_output`, if any
+    output: Option<Vec<u8>>,
+}
+
+impl HostState {
+    fn file(&self, index: i32) -> Option<&InputFileData> {
+        usize::try_from(index).ok().and_then(|i| self.files.get(i))
+    }
+}
+
+/// Read a UTF-8 message the guest placed in its exported memory
+fn read_guest_str(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String> {
+    let bytes = read_guest_bytes(caller, ptr, len, MAX_HOST_MESSAGE_BYTES)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Read bytes the guest placed in its exported memory, refusing more than `max`
+fn read_guest_bytes(
+    caller: &mut Caller<'_, HostState>,
+    ptr: i32,
+    len: i32,
+    max: usize,
+) -> Result<Vec<u8>> {
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    if len > max {
+        anyhow::bail!("host message of {len} bytes exceeds {max}");
+    }
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| anyhow::anyhow!("spell does not export its memory"))?;
+    let bytes = memory
+        .data(&caller)
+        .get(ptr..ptr + len)
+        .ok_or_else(|| anyhow::anyhow!("host message is out of bounds"))?;
+    Ok(bytes.to_vec())
+}
+
+/// Copy as much of `bytes` as fits into a guest buffer; returns the number of bytes copied
+fn write_guest_bytes(
+    caller: &mut Caller<'_, HostState>,
+    ptr: i32,
+    len: i32,
+    bytes: &[u8],
+) -> Result<i32> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| anyhow::anyhow!("spell does not export its memory"))?;
+    let n = bytes.len().min(len.max(0) as usize);
+    memory
+        .write(caller, ptr as u32 as usize, &bytes[..n])
+        .map_err(|_| anyhow::anyhow!("input file buffer is out of bounds"))?;
+    Ok(n as i32)
+}
+
+/// Copy a field of input file `index` into the guest; -1 if there is no such file
+fn copy_input_file(
+    caller: &mut Caller<'_, HostState>,
+    index: i32,
+    ptr: i32,
+    len: i32,
+    field: fn(&InputFileData) -> &[u8],
+) -> Result<i32> {
+    let files = caller.data().files.clone();
+    match usize::try_from(index).ok().and_then(|i| files.get(i)) {
+        Some(file) => write_guest_bytes(caller, ptr, len, field(file)),
+        None => Ok(-1),
+    }
+}
+
+/// Background thread advancing an engine's epoch, stopped and joined on drop
+struct EpochTicker {
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl EpochTicker {
+    fn spawn(engine: Engine) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = std::thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                std::thread::park_timeout(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
 pub struct WasmRuntime {
     engine: Engine,
     module_path: PathBuf,
+    store: Arc<dyn BlobStore>,
+    cache: Mutex<ModuleCache>,
+    _ticker: EpochTicker,
 }
 
 impl WasmRuntime {
-    pub fn new(module_path: &str) -> Self {
-        let config = Config::new();
+    pub fn new(module_path: &str, store: Arc<dyn BlobStore>, cache_capacity: usize) -> Self {
+        let mut config = Config::new();
+        config.epoch_interruption(true);
         let engine = Engine::new(&config).expect("Failed to create WASM engine");
 
+        // Advance the epoch so every running store gets a chance to notice cancellation
+        let ticker = EpochTicker::spawn(engine.clone());
+
         Self {
             engine,
             module_path: PathBuf::from(module_path),
+            store,
+            cache: Mutex::new(ModuleCache::new(cache_capacity)),
+            _ticker: ticker,
         }
     }
 
-    pub fn execute_spell(&self, spell_name: &str, input: Value) -> Result<Value, CastError> {
-        let wasm_file = self.module_path.join(format!("{spell_name}.wasm"));
+    /// Check that bytes are a valid module for this engine before publishing them
+    pub fn validate_module(&self, bytes: &[u8]) -> Result<(), String> {
+        Module::validate(&self.engine, bytes).map_err(|e| e.to_string())
+    }
+
+    /// Load the compiled module for a spell version
+    /// Versions with a digest are fetched from the blob store on first use and cached;
+    /// legacy versions without one are read from the local module directory, at their
+    /// `wasm_path` or, if that is empty, at `{spell_name}.wasm` as before versions existed.
+    pub async fn load_module(
+        &self,
+        spell_name: &str,
+        version: &SpellVersion,
+    ) -> Result<Module, CastError> {
+        let wasm_path = match version.wasm_path.as_str() {
+            "" => format!("{spell_name}.wasm"),
+            path => path.to_string(),
+        };
+        let cache_key = match &version.digest {
+            Some(digest) => digest.clone(),
+            // Files can be replaced in place; a changed file gets a fresh entry
+            None => {
+                let metadata = std::fs::metadata(self.module_path.join(&wasm_path))
+                    .map_err(|_| CastError::WasmNotFound(wasm_path.clone()))?;
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|at| at.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(0, |since| since.as_nanos());
+                format!("file:{wasm_path}:{modified}:{}", metadata.len())
+            }
+        };
 
-        if !wasm_file.exists() {
-            return Err(CastError::WasmNotFound(spell_name.to_string()));
+        if let Some(module) = self.cache.lock().get(&cache_key) {
+            return Ok(module);
         }
 
-        let module = Module::from_file(&self.engine, &wasm_file)
-            .map_err(|e| CastError::WasmExecutionFailed(format!("Failed to load module: {e}")))?;
+        let module = match &version.digest {
+            Some(digest) => {
+                let bytes = get_verified_blob(self.store.as_ref(), digest)
+                    .await
+                    .map_err(|e| match e {
+                        StorageError::NotFound(_) => CastError::WasmNotFound(digest.clone()),
+                        other => CastError::InternalError(other.to_string()),
+                    })?;
+
+                // Compilation is CPU-bound; keep it off the async executor
+                let engine = self.engine.clone();
+                tokio::task::spawn_blocking(move || Module::new(&engine, bytes))
+                    .await
+                    .map_err(|e| CastError::InternalError(format!("Compile task failed: {e}")))?
+                    .map_err(|e| {
+                        CastError::WasmExecutionFailed(format!("Failed to load module: {e}"))
+                    })?
+            }
+            None => {
+                let wasm_file = self.module_path.join(&wasm_path);
+                if !wasm_file.exists() {
+                    return Err(CastError::WasmNotFound(wasm_path));
+                }
+                Module::from_file(&self.engine, &wasm_file).map_err(|e| {
+                    CastError::WasmExecutionFailed(format!("Failed to load module: {e}"))
+                })?
+            }
+        };
+
+        self.cache.lock().insert(cache_key, module.clone());
+        Ok(module)
+    }
+
+    /// Host functions spells may import from the `spell` module
+    /// `log(ptr, len)` and `emit_output(ptr, len)` forward UTF-8 text to `events`.
+    /// `input_size()` and `read_input(ptr, len)` give the payload encoded in the spell's
+    /// input format; `set_output(ptr, len)` replaces the result with bytes in its output format.
+    /// Input files are read with `input_file_count()`, `input_file_size(i)` (-1 if absent),
+    /// `input_file_name(i, ptr, len)` and `read_input_file(i, ptr, len)`; the last two
+    /// return the number of bytes copied, or -1 if there is no such file.
+    fn linker(&self) -> Result<Linker<HostState>> {
+        let mut linker = Linker::new(&self.engine);
+        linker.func_wrap(
+            HOST_MODULE,
+            "log",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<()> {
+                let line = read_guest_str(&mut caller, ptr, len)?;
+                caller.data().events.emit(CastEvent::Log { line });
+                Ok(())
+            },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "emit_output",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<()> {
+                let chunk = read_guest_str(&mut caller, ptr, len)?;
+                caller.data().events.emit(CastEvent::Output { chunk });
+                Ok(())
+            },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "input_size",
+            |caller: Caller<'_, HostState>| -> i32 { caller.data().input.len() as i32 },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "read_input",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<i32> {
+                let input = caller.data().input.clone();
+                write_guest_bytes(&mut caller, ptr, len, &input)
+            },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "set_output",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<()> {
+                let output = read_guest_bytes(&mut caller, ptr, len, MAX_OUTPUT_BYTES)?;
+                caller.data_mut().output = Some(output);
+                Ok(())
+            },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "input_file_count",
+            |caller: Caller<'_, HostState>| -> i32 { caller.data().files.len() as i32 },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "input_file_size",
+            |caller: Caller<'_, HostState>, index: i32| -> i64 {
+                caller
+                    .data()
+                    .file(index)
+                    .map_or(-1, |file| file.bytes.len() as i64)
+            },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "input_file_name",
+            |mut caller: Caller<'_, HostState>, index: i32, ptr: i32, len: i32| -> Result<i32> {
+                copy_input_file(&mut caller, index, ptr, len, |file| file.name.as_bytes())
+            },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "read_input_file",
+            |mut caller: Caller<'_, HostState>, index: i32, ptr: i32, len: i32| -> Result<i32> {
+                copy_input_file(&mut caller, index, ptr, len, |file| &file.bytes)
+            },
+        )?;
+        Ok(linker)
+    }
+
+    /// Instantiate a spell in a fresh store holding `host`, wired to `cancel`
+    fn instantiate(
+        &self,
+        module: &Module,
+        host: HostState,
+        cancel: &CancelToken,
+    ) -> Result<(Store<HostState>, Instance), CastError> {
+        let mut store = Store::new(&self.engine, host);
+
+        let token = cancel.clone();
+        store.epoch_deadline_callback(move |_| {
+            if token.is_canceled() {
+                Err(anyhow::anyhow!("cast canceled"))
+            } else {
+                Ok(UpdateDeadline::Continue(1))
+            }
+        });
+        store.set_epoch_deadline(1);
+
+        let linker = self
+            .linker()
+            .map_err(|e| CastError::InternalError(format!("Failed to link host functions: {e}")))?;
 
-        let mut store = Store::new(&self.engine, ());
+        let instance = linker.instantiate(&mut store, module).map_err(|e| {
+            if cancel.is_canceled() {
+                CastError::Canceled
+            } else {
+                CastError::WasmExecutionFailed(format!("Failed to instantiate: {e}"))
+            }
+        })?;
 
-        let linker = Linker::new(&self.engine);
+        if cancel.is_canceled() {
+            return Err(CastError::Canceled);
+        }
+
+        Ok((store, instance))
+    }
 
-        let _instance = linker
-            .instantiate(&mut store, &module)
-            .map_err(|e| CastError::WasmExecutionFailed(format!("Failed to instantiate: {e}")))?;
+    /// Run a spell; traps with `CastError::Canceled` soon after `cancel` is triggered
+    /// Log lines and output chunks the spell emits are sent to `events` as they happen.
+    pub fn execute_spell(
+        &self,
+        spell_name: &str,
+        module: &Module,
+        input: SpellInput,
+        cancel: &CancelToken,
+        events: &EventSink,
+    ) -> Result<Value, CastError> {
+        let host = HostState {
+            events: events.clone(),
+            files: Arc::new(input.files),
+            input: Arc::new(encode_input(input.input_format, &input.payload)?),
+            output: None,
+        };
+        let (store, _instance) = self.instantiate(module, host, cancel)?;
+
+        // A result handed back explicitly wins over the mock below
+        if let Some(bytes) = &store.data().output {
+            return decode_output(input.output_format, bytes);
+        }
+        let files = &store.data().files;
 
         // For now, return mock success response
         // In a real implementation, we would:
@@ -43,11 +455,445 @@ impl WasmRuntime {
         // 2. Parse memory to get output
         // 3. Convert to JSON
 
-        Ok(serde_json::json!({
+        let mut output = serde_json::json!({
             "spell": spell_name,
+            "input": input.payload,
+            "output": "WASM execution successful (mock)",
+            "status": "ok"
+        });
+        if !files.is_empty() {
+            output["files"] = files
+                .iter()
+                .map(|file| {
+                    serde_json::json!({
+                        "name": file.name,
+                        "content_type": file.content_type,
+                        "size_bytes": file.bytes.len(),
+                    })
+                })
+                .collect();
+        }
+        Ok(output)
+    }
+
+    /// Instantiate a spell once and keep it warm for the messages of an interactive session
+    /// The instance stays bound to `cancel`; once it fires, every later message fails.
+    /// Messages are handed to the spell in `input_format` and results read back in
+    /// `output_format`, as for a one-shot cast.
+    pub fn start_session(
+        &self,
+        spell_name: &str,
+        module: &Module,
+        (input_format, output_format): (Codec, Codec),
+        cancel: &CancelToken,
+        events: &EventSink,
+    ) -> Result<SpellSession, CastError> {
+        let host = HostState {
+            events: events.clone(),
+            ..Default::default()
+        };
+        let (store, instance) = self.instantiate(module, host, cancel)?;
+        Ok(SpellSession {
+            spell_name: spell_name.to_string(),
+            store,
+            instance,
+            input_format,
+            output_format,
+            cancel: cancel.clone(),
+            messages: 0,
+        })
+    }
+}
+
+/// A live spell instance whose state carries over between session messages
+pub struct SpellSession {
+    spell_name: String,
+    store: Store<HostState>,
+    instance: Instance,
+    input_format: Codec,
+    output_format: Codec,
+    cancel: CancelToken,
+    messages: u64,
+}
+
+impl SpellSession {
+    /// Handle one session message on the warm instance
+    /// Spells exporting `on_message` have it called once per message, with the message
+    /// as their input; a result set with `set_output` is the reply.
+    pub fn call(&mut self, input: Value) -> Result<Value, CastError> {
+        if self.cancel.is_canceled() {
+            return Err(CastError::Canceled);
+        }
+        self.messages += 1;
+
+        let host = self.store.data_mut();
+        host.input = Arc::new(encode_input(self.input_format, &input)?);
+        host.output = None;
+
+        if let Ok(on_message) = self
+            .instance
+            .get_typed_func::<(), ()>(&mut self.store, "on_message")
+        {
+            on_message.call(&mut self.store, ()).map_err(|e| {
+                if self.cancel.is_canceled() {
+                    CastError::Canceled
+                } else {
+                    CastError::WasmExecutionFailed(format!("on_message trapped: {e}"))
+                }
+            })?;
+        }
+
+        if let Some(bytes) = &self.store.data().output {
+            return decode_output(self.output_format, bytes);
+        }
+
+        // Same mock output as a one-shot cast, numbered within the session
+        Ok(serde_json::json!({
+            "spell": self.spell_name,
             "input": input,
             "output": "WASM execution successful (mock)",
+            "message": self.messages,
             "status": "ok"
         }))
     }
 }
+
+/// Encode a payload for `read_input`
+fn encode_input(format: Codec, payload: &Value) -> Result<Vec<u8>, CastError> {
+    format.encode(payload).map_err(|e| {
+        CastError::InvalidInput(format!(
+            "Payload can't be encoded as {}: {e}",
+            format.name()
+        ))
+    })
+}
+
+/// Decode a result handed back with `set_output`
+fn decode_output(format: Codec, bytes: &[u8]) -> Result<Value, CastError> {
+    format.decode_value(bytes).map_err(|e| {
+        CastError::OutputContractViolation(format!("Output is not valid {}: {e}", format.name()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::local::LocalBlobStore;
+
+    #[test]
+    fn cancel_interrupts_a_running_spell() {
+        let runtime = WasmRuntime::new(
+            "./modules",
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            1,
+        );
+        // The start function never returns on its own
+        let module = Module::new(
+            &runtime.engine,
+            "(module (func $spin (loop (br 0))) (start $spin))",
+        )
+        .unwrap();
+
+        let cancel = CancelToken::default();
+        let trigger = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            trigger.cancel();
+        });
+
+        let result = runtime.execute_spell(
+            "spin",
+            &module,
+            Value::Null.into(),
+            &cancel,
+            &EventSink::default(),
+        );
+        assert!(matches!(result, Err(CastError::Canceled)));
+    }
+
+    #[test]
+    fn dropping_the_runtime_stops_its_epoch_ticker() {
+        let runtime = WasmRuntime::new(
+            "./modules",
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            1,
+        );
+        let stop = runtime._ticker.stop.clone();
+
+        // Drop joins the ticker thread, so this would hang if it kept running
+        let started = std::time::Instant::now();
+        drop(runtime);
+        assert!(stop.load(Ordering::SeqCst));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn host_functions_stream_logs_and_output() {
+        let runtime = WasmRuntime::new(
+            "./modules",
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            1,
+        );
+        let module = Module::new(
+            &runtime.engine,
+            r#"(module
+                (import "spell" "log" (func $log (param i32 i32)))
+                (import "spell" "emit_output" (func $emit (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "startingpart-1")
+                (func $main
+                    (call $log (i32.const 0) (i32.const 8))
+                    (call $emit (i32.const 8) (i32.const 6)))
+                (start $main))"#,
+        )
+        .unwrap();
+
+        let (events, mut rx) = EventSink::channel();
+        runtime
+            .execute_spell(
+                "chatty",
+                &module,
+                Value::Null.into(),
+                &CancelToken::default(),
+                &events,
+            )
+            .unwrap();
+
+        assert!(matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line == "starting"));
+        assert!(matches!(rx.try_recv(), Ok(CastEvent::Output { chunk }) if chunk == "part-1"));
+    }
+
+    #[test]
+    fn session_instance_keeps_state_between_messages() {
+        let runtime = WasmRuntime::new(
+            "./modules",
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            1,
+        );
+        // Logs "again" from the second message on, which only a warm instance can know
+        let module = Module::new(
+            &runtime.engine,
+            r#"(module
+                (import "spell" "log" (func $log (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "again")
+                (global $seen (mut i32) (i32.const 0))
+                (func (export "on_message")
+                    (if (global.get $seen) (then (call $log (i32.const 0) (i32.const 5))))
+                    (global.set $seen (i32.const 1))))"#,
+        )
+        .unwrap();
+
+        let (events, mut rx) = EventSink::channel();
+        let cancel = CancelToken::default();
+        let mut session = runtime
+            .start_session("echo", &module, Default::default(), &cancel, &events)
+            .unwrap();
+
+        assert_eq!(session.call(Value::Null).unwrap()["message"], 1);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(session.call(Value::Null).unwrap()["message"], 2);
+        assert!(matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line == "again"));
+
+        cancel.cancel();
+        assert!(matches!(
+            session.call(Value::Null),
+            Err(CastError::Canceled)
+        ));
+    }
+
+    #[test]
+    fn session_messages_reach_the_spell() {
+        let runtime = WasmRuntime::new(
+            "./modules",
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            1,
+        );
+        // Echoes each message back as its result
+        let module = Module::new(
+            &runtime.engine,
+            r#"(module
+                (import "spell" "input_size" (func $size (result i32)))
+                (import "spell" "read_input" (func $read (param i32 i32) (result i32)))
+                (import "spell" "set_output" (func $set (param i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "on_message")
+                    (call $set (i32.const 0) (call $read (i32.const 0) (call $size)))))"#,
+        )
+        .unwrap();
+
+        let formats = (Codec::MsgPack, Codec::MsgPack);
+        let mut session = runtime
+            .start_session(
+                "echo",
+                &module,
+                formats,
+                &CancelToken::default(),
+                &EventSink::default(),
+            )
+            .unwrap();
+
+        let first = serde_json::json!({"n": 1, "text": "hello"});
+        assert_eq!(session.call(first.clone()).unwrap(), first);
+        let second = serde_json::json!(["a", 2]);
+        assert_eq!(session.call(second.clone()).unwrap(), second);
+    }
+
+    #[test]
+    fn spells_read_input_files_through_host_functions() {
+        let runtime = WasmRuntime::new(
+            "./modules",
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            1,
+        );
+        // Logs the first file's name and emits its contents; a missing file reads as -1
+        let module = Module::new(
+            &runtime.engine,
+            r#"(module
+                (import "spell" "log" (func $log (param i32 i32)))
+                (import "spell" "emit_output" (func $emit (param i32 i32)))
+                (import "spell" "input_file_count" (func $count (result i32)))
+                (import "spell" "input_file_name" (func $name (param i32 i32 i32) (result i32)))
+                (import "spell" "read_input_file" (func $read (param i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func $main
+                    (if (i32.ne (call $count) (i32.const 1)) (then unreachable))
+                    (if (i32.ne (call $read (i32.const 1) (i32.const 0) (i32.const 64)) (i32.const -1))
+                        (then unreachable))
+                    (call $log (i32.const 64) (call $name (i32.const 0) (i32.const 64) (i32.const 64)))
+                    (call $emit (i32.const 0) (call $read (i32.const 0) (i32.const 0) (i32.const 64))))
+                (start $main))"#,
+        )
+        .unwrap();
+
+        let (events, mut rx) = EventSink::channel();
+        let input = SpellInput {
+            payload: serde_json::json!({"mode": "upper"}),
+            files: vec![InputFileData {
+                name: "notes.txt".to_string(),
+                content_type: "text/plain".to_string(),
+                bytes: b"hello file".to_vec(),
+            }],
+            ..Default::default()
+        };
+        let output = runtime
+            .execute_spell("reader", &module, input, &CancelToken::default(), &events)
+            .unwrap();
+
+        assert!(matches!(rx.try_recv(), Ok(CastEvent::Log { line }) if line == "notes.txt"));
+        assert!(matches!(rx.try_recv(), Ok(CastEvent::Output { chunk }) if chunk == "hello file"));
+        assert_eq!(output["input"]["mode"], "upper");
+        assert_eq!(output["files"][0]["size_bytes"], 10);
+    }
+
+    /// A version published before modules were content-addressed
+    fn legacy_version() -> SpellVersion {
+        SpellVersion {
+            id: uuid::Uuid::new_v4(),
+            spell_id: uuid::Uuid::new_v4(),
+            version: "0.1.0".to_string(),
+            wasm_path: String::new(),
+            manifest: Value::Null,
+            status: "active".to_string(),
+            status_reason: None,
+            deprecated_at: None,
+            sunset_at: None,
+            yanked_at: None,
+            digest: None,
+            size_bytes: None,
+            pure: false,
+            health_status: None,
+            health_checked_at: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn legacy_versions_without_a_path_load_by_spell_name() {
+        let dir = std::env::temp_dir().join(format!("modules-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("legacy.wasm"), "(module)").unwrap();
+        let runtime = WasmRuntime::new(
+            dir.to_str().unwrap(),
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            1,
+        );
+        let version = legacy_version();
+
+        assert!(runtime.load_module("legacy", &version).await.is_ok());
+        assert!(matches!(
+            runtime.load_module("other", &version).await,
+            Err(CastError::WasmNotFound(path)) if path == "other.wasm"
+        ));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn replaced_legacy_files_are_reloaded() {
+        let dir = std::env::temp_dir().join(format!("modules-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("swap.wasm"), "(module)").unwrap();
+        let runtime = WasmRuntime::new(
+            dir.to_str().unwrap(),
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            4,
+        );
+        let version = SpellVersion {
+            wasm_path: "swap.wasm".to_string(),
+            ..legacy_version()
+        };
+
+        assert!(runtime.load_module("swap", &version).await.is_ok());
+        std::fs::write(dir.join("swap.wasm"), "not a module").unwrap();
+        assert!(matches!(
+            runtime.load_module("swap", &version).await,
+            Err(CastError::WasmExecutionFailed(_))
+        ));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn payload_and_result_use_declared_formats() {
+        let runtime = WasmRuntime::new(
+            "./modules",
+            Arc::new(LocalBlobStore::new(std::env::temp_dir())),
+            1,
+        );
+        // Hands its encoded input straight back as the result
+        let module = Module::new(
+            &runtime.engine,
+            r#"(module
+                (import "spell" "input_size" (func $size (result i32)))
+                (import "spell" "read_input" (func $read (param i32 i32) (result i32)))
+                (import "spell" "set_output" (func $output (param i32 i32)))
+                (memory (export "memory") 1)
+                (func $main
+                    (call $output (i32.const 0) (call $read (i32.const 0) (call $size))))
+                (start $main))"#,
+        )
+        .unwrap();
+        let payload = serde_json::json!({"ids": [1, u64::MAX], "ratio": 0.5});
+        let run = |input_format, output_format| {
+            let input = SpellInput {
+                payload: payload.clone(),
+                input_format,
+                output_format,
+                ..Default::default()
+            };
+            runtime.execute_spell(
+                "echo",
+                &module,
+                input,
+                &CancelToken::default(),
+                &EventSink::default(),
+            )
+        };
+
+        assert_eq!(run(Codec::MsgPack, Codec::MsgPack).unwrap(), payload);
+        assert_eq!(run(Codec::Cbor, Codec::Cbor).unwrap(), payload);
+        assert!(matches!(
+            run(Codec::MsgPack, Codec::Json),
+            Err(CastError::OutputContractViolation(_))
+        ));
+    }
+}